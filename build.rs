@@ -31,4 +31,19 @@ fn main() {
     omit.insert(shadow_rs::COMMIT_EMAIL);
     omit.insert(shadow_rs::GIT_STATUS_FILE);
     ShadowBuilder::builder().deny_const(omit).build().unwrap();
+
+    // The version of the RDF/Borsh encoder, reported by `asimov-dataset doctor`:
+    let rdf_borsh_version = std::fs::read_to_string("Cargo.lock")
+        .ok()
+        .and_then(|lock| locked_version(&lock, "rdf-borsh"))
+        .unwrap_or_else(|| "unknown".into());
+    println!("cargo:rustc-env=RDF_BORSH_VERSION={rdf_borsh_version}");
+}
+
+/// Returns the version of the package in the `Cargo.lock` file.
+fn locked_version(lock: &str, package: &str) -> Option<String> {
+    let name = format!("name = \"{package}\"");
+    let mut lines = lock.lines().skip_while(|line| *line != name).skip(1);
+    let version = lines.next()?.strip_prefix("version = \"")?;
+    Some(version.trim_end_matches('"').to_string())
 }
@@ -0,0 +1,294 @@
+// This is free and unencumbered software released into the public domain.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use color_eyre::Section;
+use eyre::{Context as _, Result};
+use openssl::{
+    bn::BigNum,
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    rsa::{Padding, Rsa},
+    sign::{RsaPssSaltlen, Signer},
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256, Sha384};
+use std::path::Path;
+
+use crate::store::{BatchStore, Target};
+
+/// Max size of the chunks of the data of a transaction.
+const MAX_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Min size of the last chunk of the data of a transaction.
+const MIN_CHUNK_SIZE: usize = 32 * 1024;
+
+/// The content type tagging the stored batches.
+const CONTENT_TYPE: &str = "application/x-rdf-borsh";
+
+/// An Arweave wallet, in its JSON Web Key form.
+#[derive(Deserialize)]
+struct Jwk {
+    n: String,
+    e: String,
+    d: String,
+    p: String,
+    q: String,
+    dp: String,
+    dq: String,
+    qi: String,
+}
+
+/// Stores batches on Arweave as transactions of a wallet, sent to a gateway.
+pub struct ArweaveStore {
+    gateway_url: String,
+    /// The modulus of the wallet's key, identifying the wallet.
+    owner: Vec<u8>,
+    key: PKey<Private>,
+    client: reqwest::Client,
+}
+
+impl ArweaveStore {
+    /// Returns a store using the wallet of the JSON Web Key file and the
+    /// gateway at the URL, e.g. `https://arweave.net`.
+    pub fn new(wallet: &Path, gateway_url: &str) -> Result<Self> {
+        let json = std::fs::read(wallet)
+            .with_context(|| format!("Failed to read Arweave wallet {}", wallet.display()))?;
+        let jwk: Jwk = serde_json::from_slice(&json)
+            .with_context(|| format!("Invalid Arweave wallet {}", wallet.display()))?;
+        let number = |value: &str| -> Result<BigNum> {
+            Ok(BigNum::from_slice(&URL_SAFE_NO_PAD.decode(value)?)?)
+        };
+        let rsa = Rsa::from_private_components(
+            number(&jwk.n)?,
+            number(&jwk.e)?,
+            number(&jwk.d)?,
+            number(&jwk.p)?,
+            number(&jwk.q)?,
+            number(&jwk.dp)?,
+            number(&jwk.dq)?,
+            number(&jwk.qi)?,
+        )
+        .with_context(|| format!("Invalid Arweave wallet {}", wallet.display()))?;
+        Ok(Self {
+            gateway_url: gateway_url.trim_end_matches('/').to_string(),
+            owner: URL_SAFE_NO_PAD.decode(&jwk.n)?,
+            key: PKey::from_rsa(rsa)?,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    async fn get_text(&self, path: &str) -> Result<String> {
+        let url = format!("{}/{path}", self.gateway_url);
+        Ok(self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to fetch {url}"))?
+            .text()
+            .await?)
+    }
+
+    /// Signs the deep hash of the fields of a transaction with RSA-PSS.
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.set_rsa_padding(Padding::PKCS1_PSS)?;
+        signer.set_rsa_pss_saltlen(RsaPssSaltlen::custom(32))?;
+        signer.set_rsa_mgf1_md(MessageDigest::sha256())?;
+        signer.update(message)?;
+        Ok(signer.sign_to_vec()?)
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchStore for ArweaveStore {
+    fn target(&self) -> Target {
+        Target::Arweave
+    }
+
+    async fn store(&self, path: &Path, _dataset: &str) -> Result<String> {
+        let data = std::fs::read(path)?;
+        let msg = || {
+            format!(
+                "Failed to send the batch to Arweave at {}",
+                self.gateway_url
+            )
+        };
+
+        let last_tx = URL_SAFE_NO_PAD
+            .decode(self.get_text("tx_anchor").await.wrap_err_with(msg)?.trim())
+            .context("Invalid transaction anchor")?;
+        let reward = self
+            .get_text(&format!("price/{}", data.len()))
+            .await
+            .wrap_err_with(msg)?
+            .trim()
+            .to_string();
+        let data_size = data.len().to_string();
+        let data_root = data_root(&data);
+        let tags = [("Content-Type", CONTENT_TYPE)];
+
+        let signature_data = deep_hash(&DeepHashItem::List(vec![
+            DeepHashItem::Blob(b"2".to_vec()),
+            DeepHashItem::Blob(self.owner.clone()),
+            DeepHashItem::Blob(Vec::new()),    // target
+            DeepHashItem::Blob(b"0".to_vec()), // quantity
+            DeepHashItem::Blob(reward.as_bytes().to_vec()),
+            DeepHashItem::Blob(last_tx.clone()),
+            DeepHashItem::List(
+                tags.iter()
+                    .map(|(name, value)| {
+                        DeepHashItem::List(vec![
+                            DeepHashItem::Blob(name.as_bytes().to_vec()),
+                            DeepHashItem::Blob(value.as_bytes().to_vec()),
+                        ])
+                    })
+                    .collect(),
+            ),
+            DeepHashItem::Blob(data_size.as_bytes().to_vec()),
+            DeepHashItem::Blob(data_root.to_vec()),
+        ]));
+        let signature = self.sign(&signature_data)?;
+        let id = URL_SAFE_NO_PAD.encode(Sha256::digest(&signature));
+
+        let transaction = serde_json::json!({
+            "format": 2,
+            "id": id,
+            "last_tx": URL_SAFE_NO_PAD.encode(&last_tx),
+            "owner": URL_SAFE_NO_PAD.encode(&self.owner),
+            "tags": tags
+                .iter()
+                .map(|(name, value)| serde_json::json!({
+                    "name": URL_SAFE_NO_PAD.encode(name),
+                    "value": URL_SAFE_NO_PAD.encode(value),
+                }))
+                .collect::<Vec<_>>(),
+            "target": "",
+            "quantity": "0",
+            "data": URL_SAFE_NO_PAD.encode(&data),
+            "data_size": data_size,
+            "data_root": URL_SAFE_NO_PAD.encode(data_root),
+            "reward": reward,
+            "signature": URL_SAFE_NO_PAD.encode(&signature),
+        });
+        self.client
+            .post(format!("{}/tx", self.gateway_url))
+            .json(&transaction)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .wrap_err_with(msg)
+            .with_suggestion(|| {
+                "Check that the wallet holds enough AR for the transaction reward"
+            })?;
+        Ok(id)
+    }
+
+    fn url(&self, id: &str) -> Option<String> {
+        Some(format!("{}/{id}", self.gateway_url))
+    }
+}
+
+/// A value of the deep hash of a transaction's fields.
+enum DeepHashItem {
+    Blob(Vec<u8>),
+    List(Vec<DeepHashItem>),
+}
+
+/// Returns the SHA-384 deep hash of the item, as signed by Arweave transactions.
+fn deep_hash(item: &DeepHashItem) -> [u8; 48] {
+    match item {
+        DeepHashItem::Blob(data) => {
+            let tag = Sha384::digest(format!("blob{}", data.len()));
+            let mut hasher = Sha384::new();
+            hasher.update(tag);
+            hasher.update(Sha384::digest(data));
+            hasher.finalize().into()
+        }
+        DeepHashItem::List(items) => {
+            let mut acc: [u8; 48] = Sha384::digest(format!("list{}", items.len())).into();
+            for item in items {
+                let mut hasher = Sha384::new();
+                hasher.update(acc);
+                hasher.update(deep_hash(item));
+                acc = hasher.finalize().into();
+            }
+            acc
+        }
+    }
+}
+
+/// A node of the Merkle tree of the chunks of a transaction's data.
+struct MerkleNode {
+    id: [u8; 32],
+    max_byte_range: usize,
+}
+
+/// Returns the root of the Merkle tree of the chunks of the data.
+fn data_root(data: &[u8]) -> [u8; 32] {
+    let mut nodes: Vec<MerkleNode> = chunk_ranges(data.len())
+        .into_iter()
+        .map(|(start, end)| {
+            let data_hash = Sha256::digest(&data[start..end]);
+            MerkleNode {
+                id: hash_all(&[&Sha256::digest(data_hash), &Sha256::digest(note(end))]),
+                max_byte_range: end,
+            }
+        })
+        .collect();
+    while nodes.len() > 1 {
+        let mut layer = Vec::with_capacity(nodes.len().div_ceil(2));
+        let mut nodes_iter = nodes.into_iter();
+        while let Some(left) = nodes_iter.next() {
+            let Some(right) = nodes_iter.next() else {
+                layer.push(left);
+                break;
+            };
+            layer.push(MerkleNode {
+                id: hash_all(&[
+                    &Sha256::digest(left.id),
+                    &Sha256::digest(right.id),
+                    &Sha256::digest(note(left.max_byte_range)),
+                ]),
+                max_byte_range: right.max_byte_range,
+            });
+        }
+        nodes = layer;
+    }
+    nodes[0].id
+}
+
+/// Returns the byte ranges of the chunks of data of the given size, where a
+/// last chunk smaller than [`MIN_CHUNK_SIZE`] is balanced with the one before.
+fn chunk_ranges(size: usize) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut cursor = 0;
+    while size - cursor >= MAX_CHUNK_SIZE {
+        let rest = size - cursor;
+        let mut chunk_size = MAX_CHUNK_SIZE;
+        let next_chunk_size = rest - MAX_CHUNK_SIZE;
+        if next_chunk_size > 0 && next_chunk_size < MIN_CHUNK_SIZE {
+            chunk_size = rest.div_ceil(2);
+        }
+        ranges.push((cursor, cursor + chunk_size));
+        cursor += chunk_size;
+    }
+    ranges.push((cursor, size));
+    ranges
+}
+
+/// Returns the offset as a 32-byte big-endian integer.
+fn note(offset: usize) -> [u8; 32] {
+    let mut note = [0; 32];
+    note[24..].copy_from_slice(&(offset as u64).to_be_bytes());
+    note
+}
+
+fn hash_all(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
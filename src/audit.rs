@@ -0,0 +1,243 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, Context as _, Result};
+use near_api::{
+    near_primitives::{
+        hash::CryptoHash,
+        views::{ActionView, FinalExecutionStatus},
+    },
+    AccountId, NetworkConfig,
+};
+use serde::Serialize;
+use std::path::PathBuf;
+
+use crate::{
+    cache,
+    ledger::LedgerEntry,
+    publish::Operation,
+    rpc::{self, WaitUntil},
+};
+
+/// The audit of a transaction recorded in a receipt ledger.
+#[derive(Debug, Serialize)]
+pub struct TransactionAudit {
+    pub transaction: String,
+    /// The block including the transaction, as found on the network.
+    pub block_hash: Option<String>,
+    pub block_height: Option<u64>,
+    /// The batches recorded in the transaction.
+    pub filenames: Vec<PathBuf>,
+    /// Discrepancies between the ledger and the network.
+    pub problems: Vec<String>,
+    /// Records that couldn't be checked, e.g. batches without a hash.
+    pub warnings: Vec<String>,
+}
+
+impl TransactionAudit {
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// A compliance report of the transactions recorded in a receipt ledger.
+#[derive(Debug, Serialize)]
+pub struct AuditReport {
+    pub repository: String,
+    pub network: String,
+    /// The block height the ledger was audited up to, if not the last one.
+    pub block_height: Option<u64>,
+    /// Unix timestamp (seconds) of the audit.
+    pub timestamp: u64,
+    pub passed: usize,
+    pub failed: usize,
+    pub transactions: Vec<TransactionAudit>,
+}
+
+/// Audits the transactions of the ledger entries publishing to the
+/// repository on the network, in the order of the ledger.
+///
+/// Given a block height, the ledger is audited as of that block: entries
+/// recorded at a later height are left out, and transactions found at a
+/// later height fail.
+pub async fn audit(
+    entries: &[LedgerEntry],
+    repository: &AccountId,
+    network: &NetworkConfig,
+    block_height: Option<u64>,
+) -> AuditReport {
+    let recorded_by_block = |entry: &LedgerEntry| match (entry.block_height, block_height) {
+        (Some(recorded), Some(height)) => recorded <= height,
+        _ => true,
+    };
+    // Batches packed into one transaction have an entry each:
+    let mut transactions: Vec<Vec<&LedgerEntry>> = Vec::new();
+    for entry in entries.iter().filter(|entry| {
+        entry.repository == repository.as_str()
+            && entry.network == network.network_name
+            && recorded_by_block(entry)
+    }) {
+        match transactions
+            .iter_mut()
+            .find(|batches| batches[0].transaction == entry.transaction)
+        {
+            Some(batches) => batches.push(entry),
+            None => transactions.push(vec![entry]),
+        }
+    }
+
+    let mut audits = Vec::with_capacity(transactions.len());
+    for batches in transactions {
+        audits.push(audit_transaction(&batches, repository, network, block_height).await);
+    }
+    let passed = audits.iter().filter(|audit| audit.passed()).count();
+    AuditReport {
+        repository: repository.to_string(),
+        network: network.network_name.clone(),
+        block_height,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs(),
+        passed,
+        failed: audits.len() - passed,
+        transactions: audits,
+    }
+}
+
+/// Checks that the transaction of the entries exists, succeeded, is in the
+/// recorded block and calls the repository with the recorded batches.
+async fn audit_transaction(
+    entries: &[&LedgerEntry],
+    repository: &AccountId,
+    network: &NetworkConfig,
+    block_height: Option<u64>,
+) -> TransactionAudit {
+    let mut audit = TransactionAudit {
+        transaction: entries[0].transaction.clone(),
+        block_hash: None,
+        block_height: None,
+        filenames: entries.iter().map(|entry| entry.filename.clone()).collect(),
+        problems: Vec::new(),
+        warnings: Vec::new(),
+    };
+    if let Err(err) =
+        check_transaction(&mut audit, entries, repository, network, block_height).await
+    {
+        audit.problems.push(format!("{err:#}"));
+    }
+    audit
+}
+
+async fn check_transaction(
+    audit: &mut TransactionAudit,
+    entries: &[&LedgerEntry],
+    repository: &AccountId,
+    network: &NetworkConfig,
+    block_height: Option<u64>,
+) -> Result<()> {
+    let recorded = entries[0];
+    let tx_hash: CryptoHash = recorded
+        .transaction
+        .parse()
+        .map_err(|err| eyre::eyre!("{err}"))
+        .context("Invalid transaction hash")?;
+    // Older ledgers don't record the signer, which defaults to the repository:
+    let sender = match recorded.signer {
+        Some(ref signer) => signer.parse().context("Invalid signer")?,
+        None => repository.clone(),
+    };
+    let response = rpc::transaction_status(network, tx_hash, sender, WaitUntil::Final)
+        .await
+        .context("Transaction not found")?;
+    let Some(outcome) = response.final_execution_outcome else {
+        bail!("The transaction has no execution outcome");
+    };
+    let outcome = outcome.into_outcome();
+
+    match outcome.status {
+        FinalExecutionStatus::SuccessValue(_) => {}
+        FinalExecutionStatus::Failure(ref error) => {
+            audit
+                .problems
+                .push(format!("The transaction failed: {error}"));
+        }
+        FinalExecutionStatus::NotStarted | FinalExecutionStatus::Started => {
+            audit.problems.push("The transaction isn't executed".into());
+        }
+    }
+    if outcome.transaction.receiver_id != *repository {
+        audit.problems.push(format!(
+            "The transaction was sent to {}, not to the repository",
+            outcome.transaction.receiver_id
+        ));
+    }
+
+    let block_hash = outcome.transaction_outcome.block_hash;
+    audit.block_hash = Some(block_hash.to_string());
+    if let Some(ref recorded_hash) = recorded.block_hash {
+        if *recorded_hash != block_hash.to_string() {
+            audit.problems.push(format!(
+                "The transaction is in block {block_hash}, not in the recorded block {recorded_hash}"
+            ));
+        }
+    }
+    match rpc::block_height(network, block_hash).await {
+        Ok(height) => {
+            audit.block_height = Some(height);
+            if let Some(audited_height) = block_height.filter(|&audited| height > audited) {
+                audit.problems.push(format!(
+                    "Block {block_hash} is at height {height}, after the audited height {audited_height}"
+                ));
+            }
+            if let Some(recorded_height) = recorded.block_height {
+                if recorded_height != height {
+                    audit.problems.push(format!(
+                        "Block {block_hash} is at height {height}, not at the recorded height {recorded_height}"
+                    ));
+                }
+            }
+        }
+        Err(err) => audit
+            .problems
+            .push(format!("Block {block_hash} not found: {err:#}")),
+    }
+
+    let methods = [Operation::Insert, Operation::Delete].map(|operation| operation.method_name());
+    let mut call_hashes: Vec<String> = outcome
+        .transaction
+        .actions
+        .iter()
+        .filter_map(|action| match action {
+            ActionView::FunctionCall {
+                method_name, args, ..
+            } if methods.contains(&method_name.as_str()) => Some(cache::batch_hash(args)),
+            _ => None,
+        })
+        .collect();
+    if call_hashes.len() != entries.len() {
+        audit.problems.push(format!(
+            "The transaction has {} batch calls, but {} batches are recorded",
+            call_hashes.len(),
+            entries.len()
+        ));
+    }
+    for entry in entries {
+        let Some(ref hash) = entry.hash else {
+            audit.warnings.push(format!(
+                "No hash is recorded for batch {}",
+                entry.filename.display()
+            ));
+            continue;
+        };
+        match call_hashes.iter().position(|call_hash| call_hash == hash) {
+            Some(index) => {
+                call_hashes.swap_remove(index);
+            }
+            None => audit.problems.push(format!(
+                "Batch {} ({hash}) isn't in the transaction",
+                entry.filename.display()
+            )),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,205 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, Context as _, Result};
+use near_api::{AccountId, NetworkConfig};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    publish::{PublishStatsReport, MAX_TX_TGAS},
+    rpc,
+    ui::{format_near, Event, PublishPaused, STORAGE_PRICE_PER_BYTE},
+};
+
+/// How long the balance is reused before checking it again.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Returns the balance of the account not locked for its storage, as of the
+/// block.
+pub async fn available_balance(
+    account: &AccountId,
+    network: &NetworkConfig,
+    block: rpc::Block,
+) -> Result<u128> {
+    let view = rpc::view_account(network, account, block)
+        .await
+        .with_context(|| format!("Failed to view account {account}"))?;
+    Ok(view
+        .amount
+        .saturating_sub(view.storage_usage as u128 * STORAGE_PRICE_PER_BYTE))
+}
+
+/// Returns the most gas, in yoctoNEAR at the given gas price, burnt by a
+/// transaction.
+pub fn max_transaction_cost(gas_price: u128) -> u128 {
+    MAX_TX_TGAS as u128 * 10u128.pow(12) * gas_price
+}
+
+/// Returns the estimated gas and storage costs, in yoctoNEAR, of publishing
+/// batches of the given total size in transactions of up to `max_tx_size`.
+pub fn estimated_cost(bytes: u64, max_tx_size: u64, gas_price: u128) -> (u128, u128) {
+    let transactions = bytes.div_ceil(max_tx_size.max(1)).max(1);
+    (
+        transactions as u128 * max_transaction_cost(gas_price),
+        bytes as u128 * STORAGE_PRICE_PER_BYTE,
+    )
+}
+
+/// Pauses submissions while the balance of the signer can't pay for the
+/// next transaction with `min_balance` to spare, and resumes them once the
+/// account is topped up, instead of failing with `NotEnoughBalance`.
+#[derive(Debug, Default)]
+pub struct BalanceGuard {
+    /// The balance, in yoctoNEAR, kept in the account.
+    min_balance: u128,
+    /// When the balance and the balance needed were last checked.
+    last_check: Mutex<Option<(Instant, u128, u128)>>,
+}
+
+impl BalanceGuard {
+    pub fn new(min_balance: u128) -> Self {
+        Self {
+            min_balance,
+            last_check: Mutex::default(),
+        }
+    }
+
+    /// Waits until the account can pay for the next transaction, reporting
+    /// the pause and the resumption.
+    ///
+    /// If the balance or the gas price can't be fetched, the transaction
+    /// isn't delayed.
+    pub async fn wait(
+        &self,
+        network: &NetworkConfig,
+        account: &AccountId,
+        report: Option<&PublishStatsReport>,
+    ) {
+        let mut paused = false;
+        loop {
+            let Some((balance, needed)) = self.check(network, account).await else {
+                return;
+            };
+            let event = PublishPaused {
+                account: account.to_string(),
+                balance,
+                needed,
+                resumed: paused,
+            };
+            if balance >= needed {
+                if paused {
+                    tracing::info!(?event, "balance topped up, resuming submissions");
+                    if let Some(report) = report {
+                        report.send(Event::PublishPaused(event));
+                    }
+                }
+                return;
+            }
+            if !paused {
+                tracing::warn!(?event, "balance too low, pausing submissions");
+                if let Some(report) = report {
+                    report.send(Event::PublishPaused(event));
+                }
+                paused = true;
+            }
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    }
+
+    /// Returns the available balance of the account and the balance needed
+    /// for the next transaction, checking them again if the last check is
+    /// older than [`CHECK_INTERVAL`].
+    async fn check(&self, network: &NetworkConfig, account: &AccountId) -> Option<(u128, u128)> {
+        if let Some((checked_at, balance, needed)) = *self.last_check.lock().unwrap() {
+            if checked_at.elapsed() < CHECK_INTERVAL {
+                return Some((balance, needed));
+            }
+        }
+        let load = rpc::network_load(network, rpc::Block::Final)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, "failed to fetch the gas price"))
+            .ok()?;
+        let needed = self.min_balance + max_transaction_cost(load.gas_price);
+        let balance = available_balance(account, network, rpc::Block::Final)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, "failed to fetch the balance"))
+            .ok()?;
+        *self.last_check.lock().unwrap() = Some((Instant::now(), balance, needed));
+        Some((balance, needed))
+    }
+}
+
+/// Stops submissions before the tokens burnt by a publish run could exceed
+/// a budget.
+///
+/// Each transaction reserves the cost of the most gas it can burn before it's
+/// sent, and is charged the tokens it burnt once its outcome is known, or the
+/// reserved cost if its outcome isn't awaited.
+#[derive(Debug)]
+pub struct Budget {
+    /// The budget, in yoctoNEAR.
+    limit: u128,
+    state: Mutex<BudgetState>,
+}
+
+#[derive(Debug, Default)]
+struct BudgetState {
+    /// Tokens burnt by the finished transactions.
+    spent: u128,
+    /// Tokens reserved by the transactions in flight.
+    reserved: u128,
+    /// The cost reserved by each transaction, set by the first one.
+    transaction_cost: Option<u128>,
+}
+
+impl Budget {
+    pub fn new(limit: u128) -> Self {
+        Self {
+            limit,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the tokens burnt so far, in yoctoNEAR.
+    pub fn spent(&self) -> u128 {
+        self.state.lock().unwrap().spent
+    }
+
+    /// Reserves the cost of the next transaction, failing if it could exceed
+    /// the budget.
+    pub async fn reserve(&self, network: &NetworkConfig) -> Result<()> {
+        let known_cost = self.state.lock().unwrap().transaction_cost;
+        let cost = match known_cost {
+            Some(cost) => cost,
+            None => {
+                let load = rpc::network_load(network, rpc::Block::Final)
+                    .await
+                    .context("Failed to fetch the gas price to check the budget")?;
+                max_transaction_cost(load.gas_price)
+            }
+        };
+        let mut state = self.state.lock().unwrap();
+        state.transaction_cost = Some(cost);
+        if state.spent + state.reserved + cost > self.limit {
+            bail!(
+                "Stopped before the next transaction could exceed the budget of {} NEAR, \
+                 with {} NEAR spent",
+                format_near(self.limit),
+                format_near(state.spent)
+            );
+        }
+        state.reserved += cost;
+        Ok(())
+    }
+
+    /// Charges a transaction the tokens it burnt, or its reserved cost if
+    /// unknown, e.g. if it may have been sent despite an error.
+    pub fn charge(&self, tokens_burnt: Option<u128>) {
+        let mut state = self.state.lock().unwrap();
+        let cost = state.transaction_cost.unwrap_or_default();
+        state.reserved = state.reserved.saturating_sub(cost);
+        state.spent += tokens_burnt.unwrap_or(cost);
+    }
+}
@@ -0,0 +1,154 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, Context as _, Result};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+/// The magic bytes starting a statement filter file.
+const MAGIC: &[u8; 8] = b"RDFBLOOM";
+
+/// The default rate of statements wrongly found in a filter.
+pub const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// A Bloom filter of statements, e.g. of those already published to a
+/// repository, to leave them out of the next batches.
+///
+/// Statements are compared by their N-Quads serialization, like in a
+/// snapshot. A statement in the filter is always found, while a few others
+/// may be found too, at the false positive rate the filter was sized for.
+#[derive(Clone, Debug)]
+pub struct StatementFilter {
+    /// The number of bit positions set per statement.
+    hashes: u32,
+    /// The number of statements inserted.
+    statement_count: u64,
+    bits: Vec<u64>,
+}
+
+impl StatementFilter {
+    /// Returns an empty filter sized for the number of statements and the
+    /// false positive rate.
+    pub fn new(capacity: usize, false_positive_rate: f64) -> Self {
+        let capacity = capacity.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-capacity * rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let hashes = ((bit_count / capacity) * ln2).round().clamp(1.0, 32.0);
+        Self {
+            hashes: hashes as u32,
+            statement_count: 0,
+            bits: vec![0; (bit_count as usize).div_ceil(64)],
+        }
+    }
+
+    /// Returns a filter of the statement hashes, sized for their number.
+    pub fn from_hashes(hashes: &[StatementHash], false_positive_rate: f64) -> Self {
+        let mut filter = Self::new(hashes.len(), false_positive_rate);
+        for hash in hashes {
+            filter.insert_hash(hash);
+        }
+        filter
+    }
+
+    /// Returns the number of statements inserted.
+    pub fn len(&self) -> u64 {
+        self.statement_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statement_count == 0
+    }
+
+    pub fn insert(&mut self, quad: &oxrdf::Quad) {
+        self.insert_hash(&StatementHash::of(quad));
+    }
+
+    pub fn contains(&self, quad: &oxrdf::Quad) -> bool {
+        let hash = StatementHash::of(quad);
+        self.positions(&hash)
+            .all(|position| self.bits[position / 64] & (1 << (position % 64)) != 0)
+    }
+
+    fn insert_hash(&mut self, hash: &StatementHash) {
+        for position in self.positions(hash).collect::<Vec<_>>() {
+            self.bits[position / 64] |= 1 << (position % 64);
+        }
+        self.statement_count += 1;
+    }
+
+    /// Returns the bit positions of the statement, by double hashing.
+    fn positions(&self, hash: &StatementHash) -> impl Iterator<Item = usize> {
+        let bit_count = self.bits.len() as u64 * 64;
+        let StatementHash(first, second) = *hash;
+        (0..self.hashes as u64)
+            .map(move |index| (first.wrapping_add(index.wrapping_mul(second)) % bit_count) as usize)
+    }
+
+    /// Decodes a filter written by [`StatementFilter::to_bytes`].
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let Some(data) = data.strip_prefix(MAGIC) else {
+            bail!("Not a statement filter");
+        };
+        let Some((header, words)) = data.split_at_checked(12) else {
+            bail!("Truncated statement filter");
+        };
+        let hashes = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let statement_count = u64::from_le_bytes(header[4..].try_into().unwrap());
+        if hashes == 0 || words.is_empty() || words.len() % 8 != 0 {
+            bail!("Invalid statement filter");
+        }
+        let bits = words
+            .chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(Self {
+            hashes,
+            statement_count,
+            bits,
+        })
+    }
+
+    /// Encodes the filter as its magic bytes, the number of hashes and of
+    /// statements, then the bits, all little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(MAGIC.len() + 12 + self.bits.len() * 8);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&self.hashes.to_le_bytes());
+        data.extend_from_slice(&self.statement_count.to_le_bytes());
+        for word in &self.bits {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data
+    }
+
+    /// Reads the filter from a file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read statement filter {}", path.display()))?;
+        Self::from_bytes(&data)
+            .with_context(|| format!("Failed to read statement filter {}", path.display()))
+    }
+
+    /// Writes the filter to a file.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_bytes())
+            .with_context(|| format!("Failed to write statement filter {}", path.display()))
+    }
+}
+
+/// The hash of the N-Quads serialization of a statement, from which its bit
+/// positions in any filter are derived.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct StatementHash(u64, u64);
+
+impl StatementHash {
+    pub fn of(quad: &oxrdf::Quad) -> Self {
+        let digest = Sha256::digest(quad.to_string().as_bytes());
+        let (first, second) = digest.split_at(8);
+        Self(
+            u64::from_le_bytes(first.try_into().unwrap()),
+            // An even step would revisit positions sooner, the number of bits being even:
+            u64::from_le_bytes(second[..8].try_into().unwrap()) | 1,
+        )
+    }
+}
@@ -0,0 +1,101 @@
+// This is free and unencumbered software released into the public domain.
+
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// A local record of the batches published to a repository, so that
+/// publishing the same batch twice can be avoided.
+///
+/// Each line of the cache file holds the SHA-256 of a batch's `rdf_insert`
+/// arguments (covering its dataset and data) and the hash of the transaction
+/// that published it.
+#[derive(Debug)]
+pub struct PublishCache {
+    path: PathBuf,
+    transactions: Mutex<HashMap<String, String>>,
+}
+
+impl PublishCache {
+    /// Returns the path of the cache of a repository,
+    /// `$XDG_CACHE_HOME/asimov-dataset/<repository>.db` or
+    /// `~/.cache/asimov-dataset/<repository>.db`.
+    pub fn default_path(repository: &str) -> Option<PathBuf> {
+        Some(cache_dir()?.join(format!("{repository}.db")))
+    }
+
+    /// Opens the cache at the given path, which doesn't need to exist yet.
+    pub fn open(path: PathBuf) -> std::io::Result<Self> {
+        let mut transactions = HashMap::new();
+        match std::fs::File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if let Some((hash, transaction)) = line.split_once(' ') {
+                        transactions.insert(hash.to_string(), transaction.to_string());
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        Ok(Self {
+            path,
+            transactions: Mutex::new(transactions),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the transaction that published the batch with this hash, if any.
+    pub fn transaction(&self, hash: &str) -> Option<String> {
+        self.transactions.lock().unwrap().get(hash).cloned()
+    }
+
+    /// Records the batches with these hashes as published by the transaction.
+    pub fn insert(&self, hashes: &[&str], transaction: &str) -> std::io::Result<()> {
+        let mut lines = String::new();
+        for hash in hashes {
+            lines.push_str(&format!("{hash} {transaction}\n"));
+        }
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(lines.as_bytes())?;
+
+        let mut transactions = self.transactions.lock().unwrap();
+        for hash in hashes {
+            transactions.insert(hash.to_string(), transaction.to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the directory of the local state of the CLI,
+/// `$XDG_CACHE_HOME/asimov-dataset` or `~/.cache/asimov-dataset`.
+pub fn cache_dir() -> Option<PathBuf> {
+    let cache_dir = std::env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(cache_dir.join("asimov-dataset"))
+}
+
+/// Returns the hex SHA-256 of a batch's `rdf_insert` arguments.
+pub fn batch_hash(args: &[u8]) -> String {
+    Sha256::digest(args)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
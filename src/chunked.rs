@@ -0,0 +1,163 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::channel::{Receiver, Sender};
+use eyre::Result;
+use oxrdfio::{RdfFormat, RdfParser};
+use std::{
+    cell::RefCell,
+    io::{BufRead, Read},
+    rc::Rc,
+};
+
+use crate::syntax;
+
+/// Size of the chunks of a file parsed in parallel, extended to the next
+/// line break.
+pub const CHUNK_SIZE: usize = 4 << 20;
+
+/// A chunk of a file to parse.
+type Chunk<C> = std::io::Result<C>;
+
+/// A chunk of a file, with the line it starts at, counted from 0.
+type LineChunk<C> = (u64, Chunk<C>);
+
+/// A chunk of a file, with its size in bytes and its parsed statements.
+type ParsedChunk = (usize, Vec<Result<oxrdf::Quad>>);
+
+/// Returns whether files in the format can be split at any line break and
+/// the parts parsed on their own.
+pub fn is_line_oriented(format: RdfFormat) -> bool {
+    matches!(format, RdfFormat::NTriples | RdfFormat::NQuads)
+}
+
+/// Reads the statements of a line-oriented document, parsing chunks of it
+/// on up to `threads` threads while keeping the statements in order, and
+/// adding the bytes of each chunk to `count` as it's reached.
+pub fn read_quads<R>(
+    reader: R,
+    format: RdfFormat,
+    threads: usize,
+    count: Rc<RefCell<usize>>,
+) -> Box<dyn Iterator<Item = Result<oxrdf::Quad>>>
+where
+    R: BufRead + Send + 'static,
+{
+    parse_chunks(LineChunks::new(reader, CHUNK_SIZE), format, threads, count)
+}
+
+/// Parses the chunks on up to `threads` threads while keeping the
+/// statements in order, and adds the bytes of each chunk to `count` as it's
+/// reached.
+///
+/// Each chunk must end at a line break, or at the end of the document.
+pub fn parse_chunks<I, C>(
+    chunks: I,
+    format: RdfFormat,
+    threads: usize,
+    count: Rc<RefCell<usize>>,
+) -> Box<dyn Iterator<Item = Result<oxrdf::Quad>>>
+where
+    I: Iterator<Item = Chunk<C>> + Send + 'static,
+    C: AsRef<[u8]> + Send + 'static,
+{
+    let threads = threads.max(1);
+
+    // The chunks are dealt to the threads in turn, each parsing one ahead of
+    // the reader, so the reader collects them in turn too:
+    let (inputs, outputs): (Vec<Sender<LineChunk<C>>>, Vec<Receiver<ParsedChunk>>) = (0..threads)
+        .map(|_| {
+            let (input_tx, input_rx) = crossbeam::channel::bounded(1);
+            let (output_tx, output_rx) = crossbeam::channel::bounded(1);
+            std::thread::spawn(move || parse_worker_loop(input_rx, output_tx, format));
+            (input_tx, output_rx)
+        })
+        .unzip();
+    std::thread::spawn(move || {
+        // The line of each chunk, counted from 0, locates its parse errors:
+        let mut line = 0;
+        for (index, chunk) in chunks.enumerate() {
+            let first_line = line;
+            let stop = match chunk {
+                Ok(ref chunk) => {
+                    line += bytecount(chunk.as_ref(), b'\n');
+                    false
+                }
+                Err(_) => true,
+            };
+            if inputs[index % threads].send((first_line, chunk)).is_err() || stop {
+                break;
+            }
+        }
+    });
+
+    let chunks = (0..).map_while(move |index| outputs[index % threads].recv().ok());
+    Box::new(chunks.flat_map(move |(bytes, quads)| {
+        *count.borrow_mut() += bytes;
+        quads
+    }))
+}
+
+fn parse_worker_loop<C: AsRef<[u8]>>(
+    input_rx: Receiver<LineChunk<C>>,
+    output_tx: Sender<ParsedChunk>,
+    format: RdfFormat,
+) {
+    for (first_line, chunk) in input_rx {
+        let parsed = match chunk {
+            Ok(chunk) => {
+                let chunk = chunk.as_ref();
+                let quads = RdfParser::from_format(format)
+                    .for_slice(chunk)
+                    .map(|quad| quad.map_err(|err| syntax::syntax_error(err, first_line).into()))
+                    .collect();
+                (chunk.len(), quads)
+            }
+            Err(err) => (
+                0,
+                vec![Err(
+                    eyre::Report::new(err).wrap_err("Failed to read input file")
+                )],
+            ),
+        };
+        if output_tx.send(parsed).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads chunks of about `size` bytes, each ending after a line break,
+/// except perhaps the last one.
+struct LineChunks<R> {
+    reader: R,
+    size: usize,
+}
+
+impl<R> LineChunks<R> {
+    fn new(reader: R, size: usize) -> Self {
+        Self { reader, size }
+    }
+}
+
+impl<R: BufRead> Iterator for LineChunks<R> {
+    type Item = std::io::Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::with_capacity(self.size + 1024);
+        let result = (&mut self.reader)
+            .take(self.size as u64)
+            .read_to_end(&mut chunk)
+            .and_then(|_| self.reader.read_until(b'\n', &mut chunk));
+        if let Err(err) = result {
+            return Some(Err(err));
+        }
+        if chunk.is_empty() {
+            return None;
+        }
+        Some(Ok(chunk))
+    }
+}
+
+/// Returns the number of occurrences of the byte in the data.
+fn bytecount(data: &[u8], byte: u8) -> u64 {
+    data.iter().filter(|&&b| b == byte).count() as u64
+}
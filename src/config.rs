@@ -0,0 +1,134 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::AccountId;
+use std::path::{Path, PathBuf};
+
+/// The environment variable naming the config profile loaded at startup.
+pub const PROFILE_VAR: &str = "ASIMOV_DATASET_PROFILE";
+
+/// The config profile loaded when [`PROFILE_VAR`] isn't set.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The environment variable naming the network used when the repository
+/// name doesn't tell it.
+pub const NETWORK_VAR: &str = "NEAR_NETWORK";
+
+/// The environment variable naming the repository published to when it's
+/// left out.
+pub const REPOSITORY_VAR: &str = "ASIMOV_REPOSITORY";
+
+/// A config profile, written by `setup` as an environment file.
+///
+/// The profile is loaded at startup after `.env`, without overriding the
+/// variables already set, so that `NEAR_SIGNER` gives the default of
+/// `--signer`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigProfile {
+    /// Either `mainnet` or `testnet`.
+    pub network: String,
+    pub signer: AccountId,
+    pub repository: AccountId,
+}
+
+impl ConfigProfile {
+    /// Reads the profile from its environment file.
+    pub fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config profile {}", path.display()))?;
+        let mut network = None;
+        let mut signer = None;
+        let mut repository = None;
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                bail!("Invalid line in config profile {}: {line}", path.display());
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                NETWORK_VAR => network = Some(value.to_string()),
+                "NEAR_SIGNER" => signer = Some(value.parse()?),
+                REPOSITORY_VAR => repository = Some(value.parse()?),
+                _ => {}
+            }
+        }
+        let missing = |key| eyre!("Missing {key} in config profile {}", path.display());
+        Ok(Self {
+            network: network.ok_or_else(|| missing(NETWORK_VAR))?,
+            signer: signer.ok_or_else(|| missing("NEAR_SIGNER"))?,
+            repository: repository.ok_or_else(|| missing(REPOSITORY_VAR))?,
+        })
+    }
+
+    /// Writes the profile to its environment file, creating its directory.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let text = format!(
+            "# Written by `asimov-dataset setup`.\n\
+             NEAR_NETWORK={}\n\
+             NEAR_SIGNER={}\n\
+             ASIMOV_REPOSITORY={}\n",
+            self.network, self.signer, self.repository
+        );
+        std::fs::write(path, text)
+            .with_context(|| format!("Failed to write config profile {}", path.display()))
+    }
+}
+
+/// Returns the directory of the config profiles,
+/// `$XDG_CONFIG_HOME/asimov-dataset` or `~/.config/asimov-dataset`.
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(config_dir.join("asimov-dataset"))
+}
+
+/// Returns the config directory of the ASIMOV platform, shared with the
+/// `asimov` CLI and its other modules, `$ASIMOV_HOME/configs` or
+/// `~/.asimov/configs`.
+pub fn platform_config_dir() -> Option<PathBuf> {
+    let home = std::env::var_os("ASIMOV_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".asimov")))?;
+    Some(home.join("configs"))
+}
+
+/// Returns the path of the environment file of the dataset module in the
+/// platform config directory, `dataset.env`.
+///
+/// It holds the same variables as a config profile, e.g. written by the
+/// `asimov` CLI, and is loaded after the profile without overriding it, so
+/// that `asimov dataset` runs with the environment of the rest of the
+/// platform.
+pub fn platform_config_path() -> Option<PathBuf> {
+    Some(platform_config_dir()?.join("dataset.env"))
+}
+
+/// Returns the name of the profile to load, from [`PROFILE_VAR`].
+pub fn profile_name() -> String {
+    std::env::var(PROFILE_VAR)
+        .ok()
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| DEFAULT_PROFILE.into())
+}
+
+/// Returns the path of the environment file of the named profile.
+pub fn profile_path(name: &str) -> Option<PathBuf> {
+    Some(config_dir()?.join(format!("{name}.env")))
+}
+
+/// Checks that the profile name can name its environment file.
+pub fn parse_profile_name(name: &str) -> Result<String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        bail!("Invalid profile name: {name}");
+    }
+    Ok(name.to_string())
+}
@@ -1,18 +1,24 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use crossbeam::atomic::AtomicCell;
 
+/// How often paused workers check whether they were resumed.
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub fn new_cancel_context() -> (Context, Canceller) {
     let val = Arc::new(AtomicCell::new(false));
+    let paused = Arc::new(AtomicCell::new(false));
 
     (
         Context {
             cancelled: val.clone(),
+            paused: paused.clone(),
         },
         Canceller {
             cancelled: val.clone(),
+            paused,
         },
     )
 }
@@ -20,6 +26,7 @@ pub fn new_cancel_context() -> (Context, Canceller) {
 #[derive(Clone)]
 pub struct Context {
     cancelled: Arc<AtomicCell<bool>>,
+    paused: Arc<AtomicCell<bool>>,
 }
 
 impl Context {
@@ -27,11 +34,32 @@ impl Context {
     pub fn is_cancelled(&self) -> bool {
         self.cancelled.load()
     }
+
+    #[inline]
+    pub fn is_paused(&self) -> bool {
+        self.paused.load()
+    }
+
+    /// Blocks the current thread while paused, unless cancelled.
+    pub fn block_while_paused(&self) {
+        while self.is_paused() && !self.is_cancelled() {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+        }
+    }
+
+    /// Waits while paused, unless cancelled.
+    #[cfg(feature = "cli")]
+    pub async fn wait_while_paused(&self) {
+        while self.is_paused() && !self.is_cancelled() {
+            tokio::time::sleep(PAUSE_POLL_INTERVAL).await;
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Canceller {
     cancelled: Arc<AtomicCell<bool>>,
+    paused: Arc<AtomicCell<bool>>,
 }
 
 impl Canceller {
@@ -39,4 +67,11 @@ impl Canceller {
     pub fn cancel(&self) {
         self.cancelled.store(true);
     }
+
+    /// Pauses the workers if running, or resumes them if paused. Returns
+    /// whether they are now paused.
+    #[inline]
+    pub fn toggle_pause(&self) -> bool {
+        !self.paused.fetch_xor(true)
+    }
 }
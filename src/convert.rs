@@ -0,0 +1,124 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{eyre, Context as _, Result};
+use oxrdfio::{RdfFormat, RdfSerializer};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::{decode::BatchSource, prepare};
+
+/// The format statements are converted to: an RDF syntax, or RDF/Borsh.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OutputFormat {
+    Rdf(RdfFormat),
+    /// A single RDF/Borsh batch holding all the statements, whatever its size.
+    Borsh,
+}
+
+impl OutputFormat {
+    /// Returns the format of the file extension, e.g. `nq` or `rdfb`.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rdfb" => Some(Self::Borsh),
+            extension => RdfFormat::from_extension(extension).map(Self::Rdf),
+        }
+    }
+
+    /// Returns the format of the file, by its extension.
+    pub fn of_file(file: &Path) -> Option<Self> {
+        Self::from_extension(file.extension()?.to_str()?)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rdf(format) => format.file_extension(),
+            Self::Borsh => "rdfb",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_extension(s).ok_or_else(|| {
+            format!(
+                "unknown format `{s}`, expected one of: nt, nq, ttl, trig, n3, rdf, jsonld, rdfb"
+            )
+        })
+    }
+}
+
+/// Reads the statements of the files in order, whether in an RDF syntax or
+/// prepared RDF/Borsh batches.
+pub fn read_files(files: Vec<PathBuf>) -> impl Iterator<Item = Result<oxrdf::Quad>> {
+    // Batches with a shared term dictionary decode when read in order:
+    let mut batches = BatchSource::for_files(
+        files
+            .iter()
+            .filter(|file| file.extension().is_some_and(|ext| ext == "rdfb"))
+            .cloned(),
+    )
+    .into_iter();
+    files.into_iter().flat_map(move |file| {
+        let quads = match file.extension().is_some_and(|ext| ext == "rdfb") {
+            true => batches.next().expect("a source per batch").read_quads(),
+            false => prepare::read_input(&file),
+        };
+        let quads: Box<dyn Iterator<Item = Result<oxrdf::Quad>>> = match quads {
+            Ok(quads) => Box::new(quads.map(move |quad| {
+                quad.with_context(|| format!("Failed to read {}", file.display()))
+            })),
+            Err(err) => Box::new(std::iter::once(
+                Err(err).with_context(|| format!("Failed to read {}", file.display())),
+            )),
+        };
+        quads
+    })
+}
+
+/// Writes the statements in the format, returning how many were written.
+///
+/// RDF syntaxes are written as the statements are read, while an RDF/Borsh
+/// batch is serialized once all of them are read.
+pub fn convert(
+    quads: impl Iterator<Item = Result<oxrdf::Quad>>,
+    format: OutputFormat,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut count = 0;
+    match format {
+        OutputFormat::Rdf(format) => {
+            let mut serializer = RdfSerializer::from_format(format).for_writer(&mut writer);
+            for quad in quads {
+                let quad = quad?;
+                serializer.serialize_quad(&quad).map_err(|err| {
+                    match quad.graph_name.is_default_graph() {
+                        true => eyre!(err),
+                        false => eyre!(
+                            "Unable to write {quad} in {}, which has no named graphs",
+                            format.name()
+                        ),
+                    }
+                })?;
+                count += 1;
+            }
+            serializer.finish()?;
+        }
+        OutputFormat::Borsh => {
+            let quads = quads.collect::<Result<Vec<_>>>()?;
+            count = quads.len();
+            writer.write_all(&prepare::serialize_quads(quads)?)?;
+        }
+    }
+    writer.flush()?;
+    Ok(count)
+}
@@ -0,0 +1,140 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{eyre, Context as _, Result};
+use oxrdf::Quad;
+use postgres::{Client, NoTls, SimpleQueryMessage, SimpleQueryRow};
+use std::{collections::VecDeque, path::PathBuf, sync::Arc};
+
+use crate::{prepare::StatementSource, r2rml::Mapping};
+
+/// Number of rows fetched from the database at once.
+const FETCH_SIZE: usize = 10_000;
+
+const CURSOR: &str = "asimov_dataset_rows";
+
+/// Reads statements from a PostgreSQL database, mapping the rows of the
+/// queries of an R2RML mapping to RDF.
+#[derive(Clone, Debug)]
+pub struct PostgresSource {
+    url: String,
+    mapping: Arc<Mapping>,
+}
+
+impl PostgresSource {
+    pub fn new(url: impl Into<String>, mapping: Mapping) -> Self {
+        Self {
+            url: url.into(),
+            mapping: Arc::new(mapping),
+        }
+    }
+}
+
+impl StatementSource for PostgresSource {
+    /// Returns the connection URL without its password.
+    fn name(&self) -> PathBuf {
+        let url = match self.url.split_once("://") {
+            Some((scheme, rest)) => match rest.split_once('@') {
+                Some((credentials, host)) => {
+                    let user = credentials.split(':').next().unwrap_or_default();
+                    format!("{scheme}://{user}@{host}")
+                }
+                None => self.url.clone(),
+            },
+            None => self.url.clone(),
+        };
+        PathBuf::from(url)
+    }
+
+    fn read_quads(&self) -> Result<Box<dyn Iterator<Item = Result<Quad>>>> {
+        let client = Client::connect(&self.url, NoTls)
+            .with_context(|| format!("Failed to connect to {}", self.name().display()))?;
+        Ok(Box::new(Rows {
+            client,
+            mapping: self.mapping.clone(),
+            triples_map: 0,
+            open: false,
+            quads: VecDeque::new(),
+            finished: false,
+        }))
+    }
+}
+
+/// Streams the statements mapped from the rows of each triples map in turn,
+/// fetching the rows through a cursor.
+struct Rows {
+    client: Client,
+    mapping: Arc<Mapping>,
+    /// The index of the triples map being read.
+    triples_map: usize,
+    /// Whether the cursor of the triples map is open.
+    open: bool,
+    quads: VecDeque<Quad>,
+    finished: bool,
+}
+
+impl Rows {
+    fn fetch(&mut self) -> Result<()> {
+        let Some(triples_map) = self.mapping.triples_maps.get(self.triples_map) else {
+            self.finished = true;
+            return Ok(());
+        };
+        if !self.open {
+            self.client
+                .batch_execute(&format!(
+                    "BEGIN READ ONLY; DECLARE {CURSOR} NO SCROLL CURSOR FOR {}",
+                    triples_map.query
+                ))
+                .with_context(|| format!("Failed to run query `{}`", triples_map.query))?;
+            self.open = true;
+        }
+
+        let messages = self
+            .client
+            .simple_query(&format!("FETCH {FETCH_SIZE} FROM {CURSOR}"))
+            .with_context(|| format!("Failed to fetch rows of query `{}`", triples_map.query))?;
+        let mut rows = 0;
+        let mut quads = Vec::new();
+        for message in messages {
+            if let SimpleQueryMessage::Row(row) = message {
+                triples_map.row_quads(&row, &mut quads)?;
+                rows += 1;
+            }
+        }
+        self.quads.extend(quads);
+
+        if rows < FETCH_SIZE {
+            self.client
+                .batch_execute(&format!("CLOSE {CURSOR}; COMMIT"))
+                .context("Failed to close the database cursor")?;
+            self.open = false;
+            self.triples_map += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Result<Quad>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(quad) = self.quads.pop_front() {
+                return Some(Ok(quad));
+            }
+            if self.finished {
+                return None;
+            }
+            if let Err(err) = self.fetch() {
+                self.finished = true;
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+impl crate::r2rml::Row for SimpleQueryRow {
+    fn value(&self, column: &str) -> Result<Option<&str>> {
+        self.try_get(column)
+            .map_err(|_| eyre!("The query returned no column `{column}`"))
+    }
+}
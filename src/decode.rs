@@ -0,0 +1,230 @@
+// This is free and unencumbered software released into the public domain.
+
+use borsh::BorshDeserialize;
+use eyre::{bail, eyre, Context as _, Result};
+use oxrdf::{BlankNode, GraphName, Literal, NamedNode, Quad, Subject, Term};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::prepare::{StatementSource, SHARED_DICTIONARY_VERSION};
+
+/// RDF/Borsh version of batches carrying their own term dictionary.
+pub const DATASET_VERSION: u8 = b'1';
+
+/// Decodes prepared RDF/Borsh batches back into quads.
+///
+/// Batches with a shared term dictionary refer to the terms of the batches
+/// before them, so they must be decoded in order by the same decoder.
+#[derive(Debug, Default)]
+pub struct BatchDecoder {
+    /// The terms of the shared dictionary so far, the term with ID 1 first.
+    terms: Vec<Term>,
+}
+
+impl BatchDecoder {
+    pub fn decode(&mut self, data: &[u8]) -> Result<Vec<Quad>> {
+        if data.len() < 10 || &data[..4] != b"RDFB" {
+            bail!("Not an RDF/Borsh batch");
+        }
+        let mut reader = lz4_flex::frame::FrameDecoder::new(&data[10..]);
+        match data[4] {
+            DATASET_VERSION => {
+                let terms = read_terms(&mut reader)?;
+                let count = u32::deserialize_reader(&mut reader)?;
+                (0..count)
+                    .map(|_| {
+                        let [context, subject, predicate, object] =
+                            <[u16; 4]>::deserialize_reader(&mut reader)?;
+                        quad(&terms, [subject, predicate, object, context].map(u32::from))
+                    })
+                    .collect()
+            }
+            SHARED_DICTIONARY_VERSION => {
+                let first_id = u32::deserialize_reader(&mut reader)? as usize;
+                if first_id != self.terms.len() + 1 {
+                    bail!(
+                        "Batch continues a term dictionary of {} terms, but {} terms were decoded; \
+                         batches with a shared term dictionary must be decoded in order",
+                        first_id.saturating_sub(1),
+                        self.terms.len()
+                    );
+                }
+                self.terms.extend(read_terms(&mut reader)?);
+                let count = u32::deserialize_reader(&mut reader)?;
+                (0..count)
+                    .map(|_| {
+                        let [context, subject, predicate, object] =
+                            <[u32; 4]>::deserialize_reader(&mut reader)?;
+                        quad(&self.terms, [subject, predicate, object, context])
+                    })
+                    .collect()
+            }
+            version => bail!("Unsupported RDF/Borsh version {:?}", version as char),
+        }
+    }
+}
+
+/// Checks the structure of a batch without resolving the terms of a shared
+/// dictionary, returning its number of statements.
+///
+/// Catches truncated or corrupted batches, which would make the repository
+/// contract panic.
+pub fn verify(data: &[u8]) -> Result<usize> {
+    if data.len() < 10 || &data[..4] != b"RDFB" {
+        bail!("Not an RDF/Borsh batch");
+    }
+    let header_count = u32::from_le_bytes(data[6..10].try_into().unwrap());
+    let mut reader = lz4_flex::frame::FrameDecoder::new(&data[10..]);
+    let count = match data[4] {
+        DATASET_VERSION => {
+            let last_id = read_terms(&mut reader)?.len() as u32;
+            let count = u32::deserialize_reader(&mut reader)?;
+            for _ in 0..count {
+                let ids = <[u16; 4]>::deserialize_reader(&mut reader)?;
+                check_ids(ids.map(u32::from), last_id)?;
+            }
+            count
+        }
+        SHARED_DICTIONARY_VERSION => {
+            let first_id = u32::deserialize_reader(&mut reader)?;
+            let last_id = first_id.saturating_sub(1) + read_terms(&mut reader)?.len() as u32;
+            let count = u32::deserialize_reader(&mut reader)?;
+            for _ in 0..count {
+                check_ids(<[u32; 4]>::deserialize_reader(&mut reader)?, last_id)?;
+            }
+            count
+        }
+        version => bail!("Unsupported RDF/Borsh version {:?}", version as char),
+    };
+    if count != header_count {
+        bail!("Batch holds {count} statements, but its header counts {header_count}");
+    }
+    if reader.read(&mut [0])? != 0 || !reader.into_inner().is_empty() {
+        bail!("Batch has data after its statements");
+    }
+    Ok(count as usize)
+}
+
+/// Checks that the term IDs of a quad, in graph, subject, predicate, object
+/// order, refer to terms up to `last_id`, where graph 0 is the default graph.
+fn check_ids([graph, subject, predicate, object]: [u32; 4], last_id: u32) -> Result<()> {
+    let unknown = [subject, predicate, object]
+        .into_iter()
+        .chain((graph != 0).then_some(graph))
+        .find(|&id| id == 0 || id > last_id);
+    match unknown {
+        Some(id) => bail!("Unknown term ID {id}"),
+        None => Ok(()),
+    }
+}
+
+/// Returns whether the batch, or at least its first 5 bytes, refers to a term
+/// dictionary shared with the batches before it.
+pub fn uses_shared_dictionary(data: &[u8]) -> bool {
+    data.starts_with(b"RDFB") && data.get(4) == Some(&SHARED_DICTIONARY_VERSION)
+}
+
+/// Reads the statements of a prepared batch, to prepare them again.
+#[derive(Debug)]
+pub struct BatchSource {
+    file: PathBuf,
+    decoder: Arc<Mutex<BatchDecoder>>,
+}
+
+impl BatchSource {
+    /// Returns the sources of the batches, sharing a decoder so that batches
+    /// with a shared term dictionary decode when read in the given order.
+    pub fn for_files(files: impl IntoIterator<Item = PathBuf>) -> Vec<Arc<dyn StatementSource>> {
+        let decoder = Arc::new(Mutex::default());
+        files
+            .into_iter()
+            .map(|file| {
+                let decoder = decoder.clone();
+                Arc::new(Self { file, decoder }) as Arc<dyn StatementSource>
+            })
+            .collect()
+    }
+}
+
+impl StatementSource for BatchSource {
+    fn name(&self) -> PathBuf {
+        self.file.clone()
+    }
+
+    fn read_quads(&self) -> Result<Box<dyn Iterator<Item = Result<Quad>>>> {
+        let data = std::fs::read(&self.file)
+            .with_context(|| format!("Failed to read {}", self.file.display()))?;
+        let quads = self
+            .decoder
+            .lock()
+            .unwrap()
+            .decode(&data)
+            .with_context(|| format!("Failed to decode {}", self.file.display()))?;
+        Ok(Box::new(quads.into_iter().map(Ok)))
+    }
+}
+
+/// Writes the quads in N-Quads.
+pub fn write_nquads(quads: &[Quad], mut writer: impl Write) -> std::io::Result<()> {
+    for quad in quads {
+        writeln!(writer, "{quad} .")?;
+    }
+    Ok(())
+}
+
+fn read_terms(reader: &mut impl Read) -> Result<Vec<Term>> {
+    let count = u32::deserialize_reader(reader)?;
+    (0..count).map(|_| read_term(reader)).collect()
+}
+
+/// Reads a term serialized by `rdf_borsh::BorshTerm`.
+fn read_term(reader: &mut impl Read) -> Result<Term> {
+    let tag = u8::deserialize_reader(reader)?;
+    let value = String::deserialize_reader(reader)?;
+    Ok(match tag {
+        0x01 => NamedNode::new_unchecked(value).into(),
+        0x02 => BlankNode::new_unchecked(value).into(),
+        0x03 => Literal::new_simple_literal(value).into(),
+        0x04 => {
+            let datatype = String::deserialize_reader(reader)?;
+            Literal::new_typed_literal(value, NamedNode::new_unchecked(datatype)).into()
+        }
+        0x05 => {
+            let language = String::deserialize_reader(reader)?;
+            Literal::new_language_tagged_literal_unchecked(value, language).into()
+        }
+        _ => bail!("Unknown RDF/Borsh term type {tag:#04x}"),
+    })
+}
+
+/// Returns the quad of the term IDs, in subject, predicate, object, graph
+/// order, where graph 0 is the default graph.
+fn quad(terms: &[Term], [subject, predicate, object, graph]: [u32; 4]) -> Result<Quad> {
+    let term = |id: u32| {
+        (id as usize)
+            .checked_sub(1)
+            .and_then(|index| terms.get(index))
+            .cloned()
+            .ok_or_else(|| eyre!("Unknown term ID {id}"))
+    };
+    let subject = match term(subject)? {
+        Term::NamedNode(node) => Subject::NamedNode(node),
+        Term::BlankNode(node) => Subject::BlankNode(node),
+        _ => bail!("Subject of a quad is a literal"),
+    };
+    let Term::NamedNode(predicate) = term(predicate)? else {
+        bail!("Predicate of a quad isn't an IRI");
+    };
+    let graph_name = match graph {
+        0 => GraphName::DefaultGraph,
+        id => match term(id)? {
+            Term::NamedNode(node) => GraphName::NamedNode(node),
+            Term::BlankNode(node) => GraphName::BlankNode(node),
+            _ => bail!("Graph of a quad is a literal"),
+        },
+    };
+    Ok(Quad::new(subject, predicate, term(object)?, graph_name))
+}
@@ -0,0 +1,62 @@
+// This is free and unencumbered software released into the public domain.
+
+use color_eyre::Section;
+use eyre::{eyre, Result};
+use std::path::Path;
+
+/// The estimated size of prepared batches relative to the size of their
+/// input files.
+///
+/// LZ4-compressed batches of typical N-Triples take around a sixth of the
+/// input, so this leaves room for less compressible data and for compressed
+/// inputs such as Parquet.
+const OUTPUT_SIZE_RATIO: f64 = 0.5;
+
+/// Returns the estimated size of the batches prepared from input files of
+/// the given total size.
+pub fn estimated_output_size(input_bytes: u64) -> u64 {
+    (input_bytes as f64 * OUTPUT_SIZE_RATIO).ceil() as u64
+}
+
+/// Returns the space available to unprivileged users on the filesystem of
+/// the path, or `None` if unknown on this platform.
+#[cfg(unix)]
+pub fn available_space(path: &Path) -> std::io::Result<Option<u64>> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt as _};
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: the path is NUL-terminated, and statvfs fills in the stat on success.
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: statvfs succeeded.
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)] // the field types vary by platform
+    Ok(Some(stat.f_bavail as u64 * stat.f_frsize as u64))
+}
+
+/// Returns the space available to unprivileged users on the filesystem of
+/// the path, or `None` if unknown on this platform.
+#[cfg(not(unix))]
+pub fn available_space(_path: &Path) -> std::io::Result<Option<u64>> {
+    Ok(None)
+}
+
+/// Fails if the filesystem of the directory lacks the space for the batches
+/// prepared from input files of the given total size.
+pub fn check_free_space(dir: &Path, input_bytes: u64) -> Result<()> {
+    let needed = estimated_output_size(input_bytes);
+    let available = available_space(dir)
+        .map_err(|err| eyre!("Failed to check the free space in {}: {err}", dir.display()))?;
+    match available {
+        Some(available) if available < needed => Err(eyre!(
+            "Not enough free space in {}: preparing needs about {} MiB, but only {} MiB are available",
+            dir.display(),
+            needed.div_ceil(1 << 20),
+            available / (1 << 20)
+        ))
+        .with_suggestion(|| "Free up space, or prepare into a directory on another filesystem"),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,145 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use near_crypto::SecretKey;
+use std::{io::IsTerminal, time::Instant};
+
+use crate::{
+    decode::DATASET_VERSION,
+    preflight::{check_writable, Check},
+    prepare::SHARED_DICTIONARY_VERSION,
+    rpc,
+    ui::{progress_mode, ProgressMode},
+};
+
+/// The networks whose RPC endpoints are checked.
+const NETWORKS: [&str; 2] = ["mainnet", "testnet"];
+
+/// Runs every check of the environment, going on after the failed ones.
+///
+/// Unlike a preflight, no repository nor account is checked, only what any
+/// run relies on, so that the report can be pasted into a bug report.
+pub async fn diagnose() -> Vec<Check> {
+    let mut checks = vec![
+        Check::new(
+            "Version",
+            Ok(format!("asimov-dataset {}", env!("CARGO_PKG_VERSION"))),
+        ),
+        Check::new(
+            "RDF/Borsh encoder",
+            Ok(format!(
+                "rdf-borsh {}, format versions {} and {} (shared dictionary)",
+                env!("RDF_BORSH_VERSION"),
+                DATASET_VERSION as char,
+                SHARED_DICTIONARY_VERSION as char,
+            )),
+        ),
+        Check::new("Keychain", check_keychain().await),
+        Check::new("NEAR_PRIVATE_KEY", check_private_keys()),
+    ];
+
+    for name in NETWORKS {
+        let network = match rpc::named_network_config(name) {
+            Ok(network) => network,
+            Err(err) => {
+                checks.push(Check::new(format!("RPC {name}"), Err(err)));
+                continue;
+            }
+        };
+        // Each endpoint on its own, as calls fail over to the next one:
+        for endpoint in &network.rpc_endpoints {
+            let mut single = network.clone();
+            single.rpc_endpoints = vec![endpoint.clone()];
+            let url = rpc::endpoint_url(endpoint);
+            let start = Instant::now();
+            let result = rpc::final_block_height(&single).await.map(|height| {
+                format!(
+                    "{url} answered in {} ms, final block {height}",
+                    start.elapsed().as_millis()
+                )
+            });
+            checks.push(Check::new(
+                format!("RPC {name}"),
+                result.map_err(|err| eyre!("{url}: {err:#}")),
+            ));
+        }
+    }
+
+    let temp_dir = std::env::temp_dir().join("asimov-dataset");
+    checks.push(Check::new(
+        format!("Directory {}", temp_dir.display()),
+        check_writable(&temp_dir).map(|_| "Writable".to_string()),
+    ));
+
+    checks.push(Check::new("Terminal", Ok(describe_terminal())));
+    checks
+}
+
+/// Checks that the system keychain, where the keys of accounts are looked
+/// up first, can be read.
+async fn check_keychain() -> Result<String> {
+    let entry = tokio::task::spawn_blocking(|| {
+        keyring::Entry::new("asimov-dataset-doctor", "probe")?.get_password()
+    })
+    .await?;
+    match entry {
+        Ok(_) | Err(keyring::Error::NoEntry) => Ok("The system keychain is available".into()),
+        Err(err) => Err(err).context("The system keychain is unavailable"),
+    }
+}
+
+/// Checks that the `NEAR_PRIVATE_KEY` environment variable, if set, holds
+/// private keys separated by commas, reporting their public keys.
+fn check_private_keys() -> Result<String> {
+    let keys = match std::env::var("NEAR_PRIVATE_KEY") {
+        Ok(keys) => keys,
+        Err(std::env::VarError::NotPresent) => return Ok("Not set".into()),
+        Err(std::env::VarError::NotUnicode(_)) => bail!("Not valid Unicode"),
+    };
+    let keys: Vec<&str> = keys.split(',').map(str::trim).collect();
+    let mut public_keys = Vec::with_capacity(keys.len());
+    for (index, key) in keys.iter().enumerate() {
+        // The error would quote the secret key:
+        let Ok(key) = key.parse::<SecretKey>() else {
+            bail!(
+                "Key {} of {} is not a private key, such as \"ed25519:...\"",
+                index + 1,
+                keys.len()
+            );
+        };
+        public_keys.push(key.public_key().to_string());
+    }
+    Ok(match public_keys.len() {
+        1 => format!("1 key, {}", public_keys[0]),
+        count => format!("{count} keys, {}", public_keys.join(", ")),
+    })
+}
+
+/// Describes the standard streams and how progress is shown on them.
+fn describe_terminal() -> String {
+    let stream = |is_terminal: bool| match is_terminal {
+        true => "terminal",
+        false => "not a terminal",
+    };
+    let term = std::env::var("TERM").unwrap_or_else(|_| "unset".into());
+    let stderr = console::Term::stderr();
+    let size = match stderr.size_checked() {
+        Some((rows, columns)) => format!("{columns}x{rows}"),
+        None => "unknown size".into(),
+    };
+    let colors = match console::colors_enabled_stderr() {
+        true => "colors",
+        false => "no colors",
+    };
+    let progress = match progress_mode() {
+        ProgressMode::Auto if std::io::stderr().is_terminal() => "progress bars",
+        ProgressMode::Auto | ProgressMode::Plain => "plain progress lines",
+        ProgressMode::Bars => "progress bars",
+    };
+    format!(
+        "stdin {}, stdout {}, stderr {} ({size}, {colors}), TERM={term}, {progress}",
+        stream(std::io::stdin().is_terminal()),
+        stream(std::io::stdout().is_terminal()),
+        stream(std::io::stderr().is_terminal()),
+    )
+}
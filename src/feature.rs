@@ -3,6 +3,14 @@
 /// The set of features that are enabled in this build of the program.
 #[allow(unused)]
 pub static FEATURES: &[&str] = &[
+    #[cfg(feature = "cli")]
+    "cli",
+    #[cfg(feature = "ffi")]
+    "ffi",
+    #[cfg(feature = "parquet")]
+    "parquet",
+    #[cfg(feature = "postgres")]
+    "postgres",
     #[cfg(feature = "unstable")]
     "unstable",
 ];
@@ -0,0 +1,211 @@
+// This is free and unencumbered software released into the public domain.
+
+//! A C ABI to prepare and publish datasets from other languages.
+//!
+//! Build it as a shared library with:
+//!
+//! ```sh
+//! cargo rustc --lib --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! The functions return 0 on success, or -1 on failure, after which
+//! `asimov_dataset_last_error` returns the error message. They block until
+//! done, passing each progress event as JSON, e.g.
+//! `{"event":"prepare","progress":{...}}`, to the progress callback, if
+//! any, on the calling thread.
+
+use crossbeam::channel::Sender;
+use eyre::{eyre, Result};
+use near_api::AccountId;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    path::PathBuf,
+};
+
+use crate::{
+    context::new_cancel_context,
+    keys,
+    prepare::{self, PrepareStatsReport},
+    publish::{self, PublishStatsReport},
+    rpc,
+    ui::Event,
+};
+
+/// Receives a progress event as JSON, along with the caller's `user_data`.
+pub type ProgressCallback =
+    Option<unsafe extern "C" fn(event: *const c_char, user_data: *mut c_void)>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Returns the message of the last error on the calling thread, or null if
+/// none. The message is valid until the next call on the thread.
+#[no_mangle]
+pub extern "C" fn asimov_dataset_last_error() -> *const c_char {
+    LAST_ERROR.with_borrow(|error| {
+        error
+            .as_ref()
+            .map_or(std::ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Prepares the RDF files into RDF/Borsh batches in `output_dir`.
+///
+/// # Safety
+///
+/// `files` must point to `file_count` NUL-terminated paths, and `output_dir`
+/// must be a NUL-terminated path.
+#[no_mangle]
+pub unsafe extern "C" fn asimov_dataset_prepare(
+    files: *const *const c_char,
+    file_count: usize,
+    output_dir: *const c_char,
+    progress: ProgressCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let result = (|| {
+        let files = paths(files, file_count)?;
+        let output_dir =
+            PathBuf::from(string(output_dir)?.ok_or_else(|| eyre!("No output directory"))?);
+        run(progress, user_data, move |tx| {
+            let (files_tx, _files_rx) = crossbeam::channel::unbounded();
+            let report = PrepareStatsReport::new(tx);
+            let params =
+                prepare::Params::new(files.into_iter(), files_tx, Some(report), output_dir);
+            prepare::prepare_datasets_sync(new_cancel_context().0, params)
+        })
+    })();
+    status(result)
+}
+
+/// Prepares the RDF files into batches in `output_dir` and publishes them to
+/// the repository, signed by the repository's account with its keys in the
+/// system keychain or the `NEAR_PRIVATE_KEY` environment variable.
+///
+/// `network` and `dataset` may be null, for the network of the repository's
+/// top-level account and the default dataset.
+///
+/// # Safety
+///
+/// `repository` and `output_dir` must be NUL-terminated strings, `network`
+/// and `dataset` NUL-terminated strings or null, and `files` must point to
+/// `file_count` NUL-terminated paths.
+#[no_mangle]
+pub unsafe extern "C" fn asimov_dataset_publish(
+    repository: *const c_char,
+    network: *const c_char,
+    dataset: *const c_char,
+    files: *const *const c_char,
+    file_count: usize,
+    output_dir: *const c_char,
+    progress: ProgressCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    let result = (|| {
+        let repository: AccountId = string(repository)?
+            .ok_or_else(|| eyre!("No repository"))?
+            .parse()?;
+        let network = string(network)?;
+        let dataset = string(dataset)?;
+        let files = paths(files, file_count)?;
+        let output_dir =
+            PathBuf::from(string(output_dir)?.ok_or_else(|| eyre!("No output directory"))?);
+        run(progress, user_data, move |tx| {
+            let runtime = tokio::runtime::Runtime::new()?;
+            runtime.block_on(async move {
+                let network = rpc::network_config(network.as_deref(), &repository)?;
+                let signer = keys::get_signer(&repository, &network).await?;
+                let (ctx, _) = new_cancel_context();
+
+                let (files_tx, files_rx) = crossbeam::channel::unbounded();
+                let report = PrepareStatsReport::new(tx.clone());
+                let params =
+                    prepare::Params::new(files.into_iter(), files_tx, Some(report), output_dir);
+                prepare::prepare_datasets(ctx.clone(), params).await?;
+
+                let params = publish::Params::new(
+                    repository.clone(),
+                    repository,
+                    dataset,
+                    signer,
+                    network,
+                    files_rx.into_iter(),
+                    Some(PublishStatsReport::new(tx)),
+                );
+                publish::publish_datasets(ctx, params).await
+            })
+        })
+    })();
+    status(result)
+}
+
+/// Runs the job on a thread of its own, passing the events it sends to the
+/// progress callback on the calling thread until it's done.
+fn run(
+    progress: ProgressCallback,
+    user_data: *mut c_void,
+    job: impl FnOnce(Sender<Event>) -> Result<()> + Send + 'static,
+) -> Result<()> {
+    let (tx, rx) = crossbeam::channel::unbounded();
+    let worker = std::thread::spawn(move || job(tx));
+    for event in rx {
+        let Some(callback) = progress else {
+            continue;
+        };
+        if let Ok(json) = serde_json::to_string(&event).map(CString::new) {
+            let json = json.expect("JSON escapes NUL characters");
+            // SAFETY: the caller passed a callback taking the event and its user data.
+            unsafe { callback(json.as_ptr(), user_data) };
+        }
+    }
+    worker
+        .join()
+        .unwrap_or_else(|_| Err(eyre!("Worker thread panicked")))
+}
+
+/// Returns 0 on success, or else records the error and returns -1.
+fn status(result: Result<()>) -> c_int {
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let message = CString::new(format!("{err:#}").replace('\0', ""))
+                .expect("NUL characters are removed");
+            LAST_ERROR.with_borrow_mut(|error| *error = Some(message));
+            -1
+        }
+    }
+}
+
+/// Returns the string, or `None` if the pointer is null.
+///
+/// # Safety
+///
+/// The pointer must be null or point to a NUL-terminated string.
+unsafe fn string(ptr: *const c_char) -> Result<Option<String>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    Ok(Some(CStr::from_ptr(ptr).to_str()?.to_string()))
+}
+
+/// # Safety
+///
+/// `files` must point to `count` NUL-terminated strings.
+unsafe fn paths(files: *const *const c_char, count: usize) -> Result<Vec<PathBuf>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if files.is_null() {
+        return Err(eyre!("No files"));
+    }
+    std::slice::from_raw_parts(files, count)
+        .iter()
+        .map(|&file| {
+            string(file)?
+                .map(PathBuf::from)
+                .ok_or_else(|| eyre!("Null file path"))
+        })
+        .collect()
+}
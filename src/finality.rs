@@ -0,0 +1,134 @@
+// This is free and unencumbered software released into the public domain.
+
+use serde::Serialize;
+
+/// How long to wait for a submitted transaction before considering it sent.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WaitUntil {
+    /// Wait until the transaction is included in a block.
+    Included,
+    /// Wait until the transaction's receipts are executed in possibly non-final blocks.
+    #[default]
+    Optimistic,
+    /// Wait until the transaction is in a final block and its receipts are executed.
+    Executed,
+    /// Wait until the execution of all the transaction's receipts is final.
+    Final,
+}
+
+impl WaitUntil {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Included => "included",
+            Self::Optimistic => "optimistic",
+            Self::Executed => "executed",
+            Self::Final => "final",
+        }
+    }
+
+    /// Whether the RPC node returns the execution outcome at this level.
+    pub fn has_outcome(&self) -> bool {
+        !matches!(self, Self::Included)
+    }
+}
+
+impl std::fmt::Display for WaitUntil {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for WaitUntil {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "included" => Ok(Self::Included),
+            "optimistic" => Ok(Self::Optimistic),
+            "executed" => Ok(Self::Executed),
+            "final" => Ok(Self::Final),
+            _ => Err(format!(
+                "unknown finality `{s}`, expected one of: included, optimistic, executed, final"
+            )),
+        }
+    }
+}
+
+/// A stage a submitted transaction advances through, reported for each of
+/// its batches as it reaches it.
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    /// The transaction was accepted by the RPC node.
+    #[default]
+    Submitted,
+    /// The transaction is included in a block.
+    Included,
+    /// The transaction's receipts are executed.
+    Executed,
+    /// The execution of the transaction's receipts is final.
+    Final,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Submitted => "submitted",
+            Self::Included => "included",
+            Self::Executed => "executed",
+            Self::Final => "final",
+        }
+    }
+
+    /// Returns the stage reached by a transaction awaited until the finality.
+    pub fn of(finality: WaitUntil) -> Self {
+        match finality {
+            WaitUntil::Included => Self::Included,
+            WaitUntil::Optimistic | WaitUntil::Executed => Self::Executed,
+            WaitUntil::Final => Self::Final,
+        }
+    }
+
+    /// Returns the finality to await for a transaction to reach the stage,
+    /// or `None` for the submission.
+    pub fn wait_until(&self) -> Option<WaitUntil> {
+        match self {
+            Self::Submitted => None,
+            Self::Included => Some(WaitUntil::Included),
+            Self::Executed => Some(WaitUntil::Optimistic),
+            Self::Final => Some(WaitUntil::Final),
+        }
+    }
+
+    /// Returns the stages between the submission and the stage reached at
+    /// the finality, both excluded.
+    pub fn between(finality: WaitUntil) -> impl Iterator<Item = Self> {
+        let last = Self::of(finality);
+        [Self::Included, Self::Executed]
+            .into_iter()
+            .filter(move |stage| *stage < last)
+    }
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "submitted" => Ok(Self::Submitted),
+            "included" => Ok(Self::Included),
+            "executed" => Ok(Self::Executed),
+            "final" => Ok(Self::Final),
+            _ => Err(format!(
+                "unknown stage `{s}`, expected one of: submitted, included, executed, final"
+            )),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, Context as _, Result};
+use serde::Serialize;
+
+use crate::ui::{PrepareState, PublishState};
+
+/// The final summary of a prepare or publish run, reported to completion hooks.
+#[derive(Debug, Default, Serialize)]
+pub struct RunSummary {
+    pub command: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub prepared_batches: usize,
+    pub published_batches: usize,
+    pub statements: usize,
+    pub transactions: Vec<String>,
+}
+
+impl RunSummary {
+    pub fn prepare(state: &PrepareState, result: &Result<()>) -> Self {
+        Self {
+            command: "prepare".into(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+            prepared_batches: state.prepared_files.len(),
+            statements: state.prepared_statements,
+            ..Default::default()
+        }
+    }
+
+    pub fn publish(state: &PublishState, result: &Result<()>) -> Self {
+        Self {
+            command: "publish".into(),
+            success: result.is_ok(),
+            error: result.as_ref().err().map(|err| format!("{err:#}")),
+            prepared_batches: state
+                .prepare
+                .as_ref()
+                .map(|state| state.prepared_files.len())
+                .unwrap_or_default(),
+            published_batches: state.published_files.len(),
+            statements: state.published_statements,
+            transactions: state.transactions.clone(),
+        }
+    }
+}
+
+/// Reports the summary to the completion hook.
+///
+/// An `http://` or `https://` hook receives the summary as a JSON POST request.
+/// Any other hook is run as a shell command with the summary in `ASIMOV_DATASET_*`
+/// environment variables.
+pub async fn on_complete(hook: &str, summary: &RunSummary) -> Result<()> {
+    if hook.starts_with("http://") || hook.starts_with("https://") {
+        reqwest::Client::new()
+            .post(hook)
+            .json(summary)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to POST run summary to {hook}"))?;
+        return Ok(());
+    }
+
+    let mut command = if cfg!(windows) {
+        let mut command = tokio::process::Command::new("cmd");
+        command.arg("/C");
+        command
+    } else {
+        let mut command = tokio::process::Command::new("sh");
+        command.arg("-c");
+        command
+    };
+    let status = command
+        .arg(hook)
+        .env("ASIMOV_DATASET_COMMAND", &summary.command)
+        .env(
+            "ASIMOV_DATASET_STATUS",
+            if summary.success {
+                "success"
+            } else {
+                "failure"
+            },
+        )
+        .env(
+            "ASIMOV_DATASET_ERROR",
+            summary.error.as_deref().unwrap_or_default(),
+        )
+        .env(
+            "ASIMOV_DATASET_PREPARED_BATCHES",
+            summary.prepared_batches.to_string(),
+        )
+        .env(
+            "ASIMOV_DATASET_PUBLISHED_BATCHES",
+            summary.published_batches.to_string(),
+        )
+        .env("ASIMOV_DATASET_STATEMENTS", summary.statements.to_string())
+        .env(
+            "ASIMOV_DATASET_TRANSACTIONS",
+            summary.transactions.join(" "),
+        )
+        .env("ASIMOV_DATASET_SUMMARY", serde_json::to_string(summary)?)
+        .status()
+        .await
+        .with_context(|| format!("Failed to run completion hook `{hook}`"))?;
+
+    if !status.success() {
+        bail!("Completion hook `{hook}` exited with {status}");
+    }
+    Ok(())
+}
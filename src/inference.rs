@@ -0,0 +1,463 @@
+// This is free and unencumbered software released into the public domain.
+
+use oxrdf::{
+    vocab::{rdf, rdfs},
+    GraphName, NamedNode, NamedNodeRef, Quad, Subject, Term,
+};
+use std::collections::{HashMap, HashSet};
+
+const OWL: &str = "http://www.w3.org/2002/07/owl#";
+
+/// The entailment rules applied when materializing inferred statements.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Profile {
+    /// The RDFS rules for domains, ranges, subclasses and subproperties.
+    Rdfs,
+    /// The RDFS rules, plus the OWL RL rules for equivalent classes and
+    /// properties, inverse, symmetric and transitive properties, and the
+    /// symmetry and transitivity of `owl:sameAs`.
+    OwlRl,
+}
+
+impl Profile {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Rdfs => "rdfs",
+            Self::OwlRl => "owl-rl",
+        }
+    }
+}
+
+impl std::fmt::Display for Profile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rdfs" => Ok(Self::Rdfs),
+            "owl-rl" => Ok(Self::OwlRl),
+            _ => Err(format!(
+                "unknown profile `{s}`, expected one of: rdfs, owl-rl"
+            )),
+        }
+    }
+}
+
+type Id = u32;
+
+/// The vocabulary terms the rules match on.
+struct Vocabulary {
+    rdf_type: Id,
+    domain: Id,
+    range: Id,
+    sub_class_of: Id,
+    sub_property_of: Id,
+    equivalent_class: Id,
+    equivalent_property: Id,
+    inverse_of: Id,
+    same_as: Id,
+    symmetric_property: Id,
+    transitive_property: Id,
+}
+
+/// Forward-chaining reasoner maintaining the closure of the statements
+/// inserted so far.
+///
+/// Statements are reasoned on regardless of their graph, and inferred
+/// statements are in the default graph. All the statements are kept in
+/// memory, interned.
+pub struct Reasoner {
+    profile: Profile,
+    terms: Vec<Term>,
+    ids: HashMap<Term, Id>,
+    vocabulary: Vocabulary,
+    triples: HashSet<(Id, Id, Id)>,
+    /// Subject and object pairs, by predicate.
+    by_predicate: HashMap<Id, Vec<(Id, Id)>>,
+    /// Objects, by subject and predicate.
+    objects: HashMap<(Id, Id), Vec<Id>>,
+    /// Subjects, by predicate and object.
+    subjects: HashMap<(Id, Id), Vec<Id>>,
+}
+
+impl Reasoner {
+    pub fn new(profile: Profile) -> Self {
+        let mut terms = Vec::new();
+        let mut ids = HashMap::new();
+        let mut id = |node: NamedNodeRef| intern(&mut terms, &mut ids, node.into_owned().into());
+        let owl = |term: &str| NamedNode::new_unchecked(format!("{OWL}{term}"));
+        let vocabulary = Vocabulary {
+            rdf_type: id(rdf::TYPE),
+            domain: id(rdfs::DOMAIN),
+            range: id(rdfs::RANGE),
+            sub_class_of: id(rdfs::SUB_CLASS_OF),
+            sub_property_of: id(rdfs::SUB_PROPERTY_OF),
+            equivalent_class: id(owl("equivalentClass").as_ref()),
+            equivalent_property: id(owl("equivalentProperty").as_ref()),
+            inverse_of: id(owl("inverseOf").as_ref()),
+            same_as: id(owl("sameAs").as_ref()),
+            symmetric_property: id(owl("SymmetricProperty").as_ref()),
+            transitive_property: id(owl("TransitiveProperty").as_ref()),
+        };
+        Self {
+            profile,
+            terms,
+            ids,
+            vocabulary,
+            triples: HashSet::new(),
+            by_predicate: HashMap::new(),
+            objects: HashMap::new(),
+            subjects: HashMap::new(),
+        }
+    }
+
+    /// Inserts a statement, returning the statements newly inferred from it.
+    pub fn insert(&mut self, quad: &Quad) -> Vec<Quad> {
+        let subject = self.id(quad.subject.clone().into());
+        let predicate = self.id(quad.predicate.clone().into());
+        let object = self.id(quad.object.clone());
+
+        let mut inferred = Vec::new();
+        let mut queue = Vec::new();
+        if self.add(subject, predicate, object) {
+            queue.push((subject, predicate, object));
+        }
+        while let Some(triple) = queue.pop() {
+            for (s, p, o) in self.consequences(triple) {
+                if self.is_resource(s) && self.add(s, p, o) {
+                    inferred.push((s, p, o));
+                    queue.push((s, p, o));
+                }
+            }
+        }
+
+        inferred
+            .into_iter()
+            .filter_map(|(s, p, o)| {
+                let subject = match self.terms[s as usize].clone() {
+                    Term::NamedNode(node) => Subject::NamedNode(node),
+                    Term::BlankNode(node) => Subject::BlankNode(node),
+                    Term::Triple(triple) => Subject::Triple(triple),
+                    Term::Literal(_) => return None,
+                };
+                let Term::NamedNode(predicate) = self.terms[p as usize].clone() else {
+                    return None;
+                };
+                let object = self.terms[o as usize].clone();
+                Some(Quad::new(
+                    subject,
+                    predicate,
+                    object,
+                    GraphName::DefaultGraph,
+                ))
+            })
+            .collect()
+    }
+
+    /// Returns the statements entailed by a new statement together with the known ones.
+    fn consequences(&self, (s, p, o): (Id, Id, Id)) -> Vec<(Id, Id, Id)> {
+        let v = &self.vocabulary;
+        let mut triples = Vec::new();
+
+        // Rules where the new statement is an instance statement:
+        for &class in self.objects_of(p, v.domain) {
+            triples.push((s, v.rdf_type, class));
+        }
+        for &class in self.objects_of(p, v.range) {
+            triples.push((o, v.rdf_type, class));
+        }
+        for &property in self.objects_of(p, v.sub_property_of) {
+            triples.push((s, property, o));
+        }
+        if p == v.rdf_type {
+            for &class in self.objects_of(o, v.sub_class_of) {
+                triples.push((s, v.rdf_type, class));
+            }
+        }
+
+        // Rules where the new statement is a schema statement:
+        if p == v.domain {
+            for &(subject, _) in self.pairs_of(s) {
+                triples.push((subject, v.rdf_type, o));
+            }
+        } else if p == v.range {
+            for &(_, object) in self.pairs_of(s) {
+                triples.push((object, v.rdf_type, o));
+            }
+        } else if p == v.sub_property_of {
+            for &(subject, object) in self.pairs_of(s) {
+                triples.push((subject, o, object));
+            }
+            for &property in self.objects_of(o, v.sub_property_of) {
+                triples.push((s, v.sub_property_of, property));
+            }
+            for &property in self.subjects_of(v.sub_property_of, s) {
+                triples.push((property, v.sub_property_of, o));
+            }
+        } else if p == v.sub_class_of {
+            for &instance in self.subjects_of(v.rdf_type, s) {
+                triples.push((instance, v.rdf_type, o));
+            }
+            for &class in self.objects_of(o, v.sub_class_of) {
+                triples.push((s, v.sub_class_of, class));
+            }
+            for &class in self.subjects_of(v.sub_class_of, s) {
+                triples.push((class, v.sub_class_of, o));
+            }
+        }
+
+        if self.profile == Profile::Rdfs {
+            return triples;
+        }
+
+        if self.has(p, v.rdf_type, v.symmetric_property) {
+            triples.push((o, p, s));
+        }
+        if self.has(p, v.rdf_type, v.transitive_property) {
+            for &object in self.objects_of(o, p) {
+                triples.push((s, p, object));
+            }
+            for &subject in self.subjects_of(p, s) {
+                triples.push((subject, p, o));
+            }
+        }
+        for &property in self.objects_of(p, v.inverse_of) {
+            triples.push((o, property, s));
+        }
+        for &property in self.subjects_of(v.inverse_of, p) {
+            triples.push((o, property, s));
+        }
+
+        if p == v.rdf_type && o == v.symmetric_property {
+            for &(subject, object) in self.pairs_of(s) {
+                triples.push((object, s, subject));
+            }
+        } else if p == v.rdf_type && o == v.transitive_property {
+            for &(subject, object) in self.pairs_of(s) {
+                for &next in self.objects_of(object, s) {
+                    triples.push((subject, s, next));
+                }
+            }
+        } else if p == v.inverse_of {
+            for &(subject, object) in self.pairs_of(s) {
+                triples.push((object, o, subject));
+            }
+            for &(subject, object) in self.pairs_of(o) {
+                triples.push((object, s, subject));
+            }
+        } else if p == v.equivalent_class {
+            triples.push((s, v.sub_class_of, o));
+            triples.push((o, v.sub_class_of, s));
+        } else if p == v.equivalent_property {
+            triples.push((s, v.sub_property_of, o));
+            triples.push((o, v.sub_property_of, s));
+        } else if p == v.same_as {
+            triples.push((o, v.same_as, s));
+            for &object in self.objects_of(o, v.same_as) {
+                triples.push((s, v.same_as, object));
+            }
+            for &subject in self.subjects_of(v.same_as, s) {
+                triples.push((subject, v.same_as, o));
+            }
+        }
+
+        triples
+    }
+
+    fn id(&mut self, term: Term) -> Id {
+        intern(&mut self.terms, &mut self.ids, term)
+    }
+
+    /// Whether the term can be the subject of a statement.
+    fn is_resource(&self, id: Id) -> bool {
+        !matches!(self.terms[id as usize], Term::Literal(_))
+    }
+
+    fn has(&self, s: Id, p: Id, o: Id) -> bool {
+        self.triples.contains(&(s, p, o))
+    }
+
+    fn add(&mut self, s: Id, p: Id, o: Id) -> bool {
+        if !self.triples.insert((s, p, o)) {
+            return false;
+        }
+        self.by_predicate.entry(p).or_default().push((s, o));
+        self.objects.entry((s, p)).or_default().push(o);
+        self.subjects.entry((p, o)).or_default().push(s);
+        true
+    }
+
+    fn pairs_of(&self, p: Id) -> &[(Id, Id)] {
+        self.by_predicate
+            .get(&p)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn objects_of(&self, s: Id, p: Id) -> &[Id] {
+        self.objects
+            .get(&(s, p))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    fn subjects_of(&self, p: Id, o: Id) -> &[Id] {
+        self.subjects
+            .get(&(p, o))
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+}
+
+fn intern(terms: &mut Vec<Term>, ids: &mut HashMap<Term, Id>, term: Term) -> Id {
+    *ids.entry(term).or_insert_with_key(|term| {
+        terms.push(term.clone());
+        (terms.len() - 1) as Id
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxrdf::Literal;
+
+    fn iri(name: &str) -> NamedNode {
+        let (namespace, local) = match name.split_once(':') {
+            Some(("rdf", local)) => ("http://www.w3.org/1999/02/22-rdf-syntax-ns#", local),
+            Some(("rdfs", local)) => ("http://www.w3.org/2000/01/rdf-schema#", local),
+            Some(("owl", local)) => (OWL, local),
+            _ => ("http://example.org/", name),
+        };
+        NamedNode::new_unchecked(format!("{namespace}{local}"))
+    }
+
+    fn quad(s: &str, p: &str, o: &str) -> Quad {
+        Quad::new(iri(s), iri(p), iri(o), GraphName::DefaultGraph)
+    }
+
+    /// Inserts the statements in order, returning all the inferred ones.
+    fn infer(profile: Profile, quads: &[Quad]) -> Vec<Quad> {
+        let mut reasoner = Reasoner::new(profile);
+        quads
+            .iter()
+            .flat_map(|quad| reasoner.insert(quad))
+            .collect()
+    }
+
+    #[test]
+    fn infers_domains_ranges_and_subclasses() {
+        let schema = [
+            quad("p", "rdfs:domain", "A"),
+            quad("p", "rdfs:range", "B"),
+            quad("A", "rdfs:subClassOf", "C"),
+            quad("C", "rdfs:subClassOf", "D"),
+        ];
+        let instance = quad("x", "p", "y");
+        let expected = [
+            quad("x", "rdf:type", "A"),
+            quad("y", "rdf:type", "B"),
+            quad("x", "rdf:type", "C"),
+            quad("x", "rdf:type", "D"),
+            quad("A", "rdfs:subClassOf", "D"),
+        ];
+
+        // The closure doesn't depend on the order of the statements:
+        for quads in [
+            [&schema[..], std::slice::from_ref(&instance)].concat(),
+            [std::slice::from_ref(&instance), &schema[..]].concat(),
+        ] {
+            let inferred = infer(Profile::Rdfs, &quads);
+            for quad in &expected {
+                assert!(inferred.contains(quad), "missing {quad}");
+            }
+            assert_eq!(inferred.len(), expected.len(), "{inferred:?}");
+        }
+    }
+
+    #[test]
+    fn infers_subproperties() {
+        let inferred = infer(
+            Profile::Rdfs,
+            &[
+                quad("p", "rdfs:subPropertyOf", "q"),
+                quad("q", "rdfs:subPropertyOf", "r"),
+                quad("x", "p", "y"),
+            ],
+        );
+        assert!(inferred.contains(&quad("p", "rdfs:subPropertyOf", "r")));
+        assert!(inferred.contains(&quad("x", "q", "y")));
+        assert!(inferred.contains(&quad("x", "r", "y")));
+    }
+
+    #[test]
+    fn infers_no_statements_about_literals() {
+        let literal = Quad::new(
+            iri("x"),
+            iri("p"),
+            Literal::new_simple_literal("y"),
+            GraphName::DefaultGraph,
+        );
+        let inferred = infer(Profile::Rdfs, &[quad("p", "rdfs:range", "B"), literal]);
+        assert!(inferred.is_empty(), "{inferred:?}");
+    }
+
+    #[test]
+    fn infers_owl_rl_properties() {
+        let statements = [
+            quad("knows", "rdf:type", "owl:SymmetricProperty"),
+            quad("ancestor", "rdf:type", "owl:TransitiveProperty"),
+            quad("parent", "owl:inverseOf", "child"),
+            quad("a", "knows", "b"),
+            quad("a", "ancestor", "b"),
+            quad("b", "ancestor", "c"),
+            quad("a", "parent", "b"),
+            quad("a", "owl:sameAs", "b"),
+            quad("b", "owl:sameAs", "c"),
+        ];
+        let inferred = infer(Profile::OwlRl, &statements);
+        for quad in [
+            quad("b", "knows", "a"),
+            quad("a", "ancestor", "c"),
+            quad("b", "child", "a"),
+            quad("b", "owl:sameAs", "a"),
+            quad("a", "owl:sameAs", "c"),
+            quad("c", "owl:sameAs", "a"),
+        ] {
+            assert!(inferred.contains(&quad), "missing {quad}");
+        }
+
+        // Only the RDFS rules apply to the RDFS profile:
+        assert!(infer(Profile::Rdfs, &statements).is_empty());
+    }
+
+    #[test]
+    fn infers_owl_rl_equivalences() {
+        let inferred = infer(
+            Profile::OwlRl,
+            &[
+                quad("A", "owl:equivalentClass", "B"),
+                quad("p", "owl:equivalentProperty", "q"),
+                quad("x", "rdf:type", "B"),
+                quad("x", "q", "y"),
+            ],
+        );
+        assert!(inferred.contains(&quad("x", "rdf:type", "A")));
+        assert!(inferred.contains(&quad("x", "p", "y")));
+    }
+
+    #[test]
+    fn parses_profiles() {
+        for profile in [Profile::Rdfs, Profile::OwlRl] {
+            assert_eq!(profile.as_str().parse::<Profile>(), Ok(profile));
+        }
+        assert_eq!(
+            "owl".parse::<Profile>(),
+            Err("unknown profile `owl`, expected one of: rdfs, owl-rl".to_string())
+        );
+    }
+}
@@ -0,0 +1,78 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::channel::{Receiver, Sender};
+use std::{
+    io::Write,
+    os::unix::{
+        fs::FileTypeExt,
+        net::{UnixListener, UnixStream},
+    },
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::ui::Event;
+
+/// How long a client may block the delivery of an event before being dropped.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Streams every event passing through the returned receiver to the clients
+/// connected to a Unix domain socket at the given path, as one JSON object
+/// per line.
+///
+/// A stale socket left at the path by an earlier run is replaced, and the
+/// socket is removed once the last event has passed through.
+pub fn tap(events: Receiver<Event>, path: &Path) -> std::io::Result<Receiver<Event>> {
+    if std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        // Only a socket nobody listens on anymore is stale:
+        if UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                "another process listens on the socket",
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+
+    let clients: Arc<Mutex<Vec<UnixStream>>> = Arc::default();
+    std::thread::spawn({
+        let clients = clients.clone();
+        move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_ok() {
+                    clients.lock().unwrap().push(stream);
+                }
+            }
+        }
+    });
+
+    let (tx, rx): (Sender<Event>, Receiver<Event>) = crossbeam::channel::unbounded();
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        for event in events {
+            let mut clients = clients.lock().unwrap();
+            if !clients.is_empty() {
+                match serde_json::to_vec(&event) {
+                    Ok(mut line) => {
+                        line.push(b'\n');
+                        clients.retain_mut(|client| client.write_all(&line).is_ok());
+                    }
+                    Err(err) => tracing::debug!(?err, "failed to serialize progress event"),
+                }
+            }
+            drop(clients);
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+        // Unlink the socket before the receiver sees the end of the events:
+        std::fs::remove_file(&path).ok();
+        drop(tx);
+    });
+    Ok(rx)
+}
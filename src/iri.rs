@@ -0,0 +1,286 @@
+// This is free and unencumbered software released into the public domain.
+
+use oxrdf::{GraphName, NamedNode, Quad, Subject, Term};
+use serde::Serialize;
+use std::{borrow::Cow, path::PathBuf};
+
+/// Schemes whose IRIs must have a host, e.g. `http://example.org/`.
+const HOST_SCHEMES: &[&str] = &["ftp", "http", "https", "ws", "wss"];
+
+/// Why an IRI was rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IriViolation {
+    /// The IRI is relative.
+    MissingScheme,
+    /// The IRI of a scheme like `http` has no host.
+    MissingHost,
+    /// The port isn't a number up to 65535.
+    InvalidPort,
+    /// A `%` isn't followed by two hexadecimal digits.
+    InvalidPercentEncoding,
+}
+
+impl IriViolation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MissingScheme => "missing-scheme",
+            Self::MissingHost => "missing-host",
+            Self::InvalidPort => "invalid-port",
+            Self::InvalidPercentEncoding => "invalid-percent-encoding",
+        }
+    }
+}
+
+impl std::fmt::Display for IriViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A statement left out for having an invalid IRI.
+#[derive(Debug, Serialize)]
+pub struct IriViolationRecord {
+    pub file: PathBuf,
+    /// The index of the statement among all the statements read.
+    pub statement: usize,
+    pub iri: String,
+    pub violation: IriViolation,
+}
+
+/// Normalizes the IRIs of the statement in place, or returns the first
+/// invalid one, leaving the IRIs of quoted triples as they are.
+pub fn normalize_quad(quad: &mut Quad) -> Result<(), (String, IriViolation)> {
+    if let Subject::NamedNode(ref mut node) = quad.subject {
+        normalize_node(node)?;
+    }
+    normalize_node(&mut quad.predicate)?;
+    if let Term::NamedNode(ref mut node) = quad.object {
+        normalize_node(node)?;
+    }
+    if let GraphName::NamedNode(ref mut node) = quad.graph_name {
+        normalize_node(node)?;
+    }
+    Ok(())
+}
+
+fn normalize_node(node: &mut NamedNode) -> Result<(), (String, IriViolation)> {
+    match normalize(node.as_str()) {
+        Ok(Cow::Borrowed(_)) => Ok(()),
+        Ok(Cow::Owned(iri)) => {
+            *node = NamedNode::new_unchecked(iri);
+            Ok(())
+        }
+        Err(violation) => Err((node.as_str().to_string(), violation)),
+    }
+}
+
+/// Returns the IRI with its scheme and host in lowercase, unreserved
+/// characters percent-decoded, and other percent-encodings in uppercase,
+/// e.g. `HTTP://Example.org/%7euser%2f` becomes `http://example.org/~user%2F`.
+pub fn normalize(iri: &str) -> Result<Cow<'_, str>, IriViolation> {
+    let (scheme, rest) = iri.split_once(':').ok_or(IriViolation::MissingScheme)?;
+    // A colon after a path segment, e.g. `a/b:c`, isn't the end of a scheme:
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !valid_scheme {
+        return Err(IriViolation::MissingScheme);
+    }
+    let scheme = scheme.to_ascii_lowercase();
+    let needs_host = HOST_SCHEMES.contains(&scheme.as_str());
+
+    let mut normalized = String::with_capacity(iri.len());
+    normalized.push_str(&scheme);
+    normalized.push(':');
+    let path = match rest.strip_prefix("//") {
+        Some(rest) => {
+            let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+            let (authority, path) = rest.split_at(end);
+            let (userinfo, host_port) = match authority.rsplit_once('@') {
+                Some((userinfo, host_port)) => (Some(userinfo), host_port),
+                None => (None, authority),
+            };
+            let (host, port) = split_port(host_port);
+            if host.is_empty() && needs_host {
+                return Err(IriViolation::MissingHost);
+            }
+            normalized.push_str("//");
+            if let Some(userinfo) = userinfo {
+                normalized.push_str(&normalize_percent(userinfo)?);
+                normalized.push('@');
+            }
+            normalized.push_str(&normalize_percent(&host.to_lowercase())?);
+            if let Some(port) = port {
+                if !port.is_empty() && port.parse::<u16>().is_err() {
+                    return Err(IriViolation::InvalidPort);
+                }
+                normalized.push(':');
+                normalized.push_str(port);
+            }
+            path
+        }
+        None if needs_host => return Err(IriViolation::MissingHost),
+        None => rest,
+    };
+    normalized.push_str(&normalize_percent(path)?);
+
+    Ok(match normalized == iri {
+        true => Cow::Borrowed(iri),
+        false => Cow::Owned(normalized),
+    })
+}
+
+/// Splits the host and the port, if any, of an authority.
+fn split_port(host_port: &str) -> (&str, Option<&str>) {
+    // The colons of an IPv6 address are within brackets, e.g. `[::1]:8080`:
+    let host_end = match host_port.starts_with('[') {
+        true => host_port.find(']').map_or(host_port.len(), |end| end + 1),
+        false => 0,
+    };
+    match host_port[host_end..].find(':') {
+        Some(colon) => (
+            &host_port[..host_end + colon],
+            Some(&host_port[host_end + colon + 1..]),
+        ),
+        None => (host_port, None),
+    }
+}
+
+/// Decodes the percent-encoded unreserved characters, and puts the digits of
+/// the other percent-encodings in uppercase.
+fn normalize_percent(value: &str) -> Result<Cow<'_, str>, IriViolation> {
+    if !value.contains('%') {
+        return Ok(Cow::Borrowed(value));
+    }
+    let mut normalized = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('%') {
+        normalized.push_str(&rest[..start]);
+        let byte = rest
+            .get(start + 1..start + 3)
+            .filter(|hex| hex.bytes().all(|digit| digit.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or(IriViolation::InvalidPercentEncoding)?;
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                normalized.push(byte as char)
+            }
+            _ => normalized.push_str(&format!("%{byte:02X}")),
+        }
+        rest = &rest[start + 3..];
+    }
+    normalized.push_str(rest);
+    Ok(Cow::Owned(normalized))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalized(iri: &str) -> Result<String, IriViolation> {
+        normalize(iri).map(Cow::into_owned)
+    }
+
+    #[test]
+    fn normalizes_schemes_hosts_and_percent_encodings() {
+        assert_eq!(
+            normalized("HTTP://Example.org/%7euser%2f").as_deref(),
+            Ok("http://example.org/~user%2F")
+        );
+        assert_eq!(
+            normalized("https://User@Example.ORG:8080/caf%c3%a9?q=%41#Top").as_deref(),
+            Ok("https://User@example.org:8080/caf%C3%A9?q=A#Top")
+        );
+        assert_eq!(
+            normalized("URN:ISBN:0451450523").as_deref(),
+            Ok("urn:ISBN:0451450523")
+        );
+    }
+
+    #[test]
+    fn borrows_normalized_iris() {
+        for iri in [
+            "http://example.org/a/../b",
+            "http://[::1]:8080/",
+            "http://example.org:/",
+            "file:///tmp/data.nt",
+            "mailto:Someone@Example.org",
+        ] {
+            assert!(matches!(normalize(iri), Ok(Cow::Borrowed(_))), "{iri}");
+        }
+    }
+
+    #[test]
+    fn rejects_relative_references() {
+        for iri in [
+            "example.org/a",
+            "/a/b",
+            "#fragment",
+            "a/b:c",
+            "1http://example.org/",
+        ] {
+            assert_eq!(normalize(iri), Err(IriViolation::MissingScheme), "{iri}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_iris() {
+        assert_eq!(normalize("http:///a"), Err(IriViolation::MissingHost));
+        assert_eq!(
+            normalize("https:example.org"),
+            Err(IriViolation::MissingHost)
+        );
+        assert_eq!(
+            normalize("http://example.org:port/"),
+            Err(IriViolation::InvalidPort)
+        );
+        assert_eq!(
+            normalize("http://[::1]:65536/"),
+            Err(IriViolation::InvalidPort)
+        );
+        assert_eq!(
+            normalize("http://example.org/%zz"),
+            Err(IriViolation::InvalidPercentEncoding)
+        );
+        assert_eq!(
+            normalize("http://example.org/%4"),
+            Err(IriViolation::InvalidPercentEncoding)
+        );
+    }
+
+    #[test]
+    fn normalizes_quads_or_returns_the_invalid_iri() {
+        let mut quad = Quad::new(
+            NamedNode::new_unchecked("HTTP://Example.org/s"),
+            NamedNode::new_unchecked("http://example.org/p"),
+            NamedNode::new_unchecked("http://example.org/%7eo"),
+            GraphName::DefaultGraph,
+        );
+        assert_eq!(normalize_quad(&mut quad), Ok(()));
+        assert_eq!(
+            quad.subject,
+            Subject::from(NamedNode::new_unchecked("http://example.org/s"))
+        );
+        assert_eq!(
+            quad.object,
+            Term::from(NamedNode::new_unchecked("http://example.org/~o"))
+        );
+
+        quad.predicate = NamedNode::new_unchecked("p");
+        assert_eq!(
+            normalize_quad(&mut quad),
+            Err(("p".to_string(), IriViolation::MissingScheme))
+        );
+    }
+
+    #[test]
+    fn names_violations_the_same_in_reports() {
+        let violation = IriViolation::InvalidPercentEncoding;
+        assert_eq!(
+            serde_json::to_string(&violation).unwrap(),
+            format!("\"{violation}\"")
+        );
+    }
+}
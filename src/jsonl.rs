@@ -0,0 +1,38 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{Context as _, Result};
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+/// Writes the records reported by the read workers, e.g. invalid IRIs or
+/// parse errors, into a JSON Lines file.
+#[derive(Debug)]
+pub struct JsonLinesLog {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl JsonLinesLog {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+        })
+    }
+
+    pub fn record<T: Serialize>(&self, record: &T) -> Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        serde_json::to_writer(&mut *writer, record)?;
+        writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        Ok(self.writer.lock().unwrap().flush()?)
+    }
+}
@@ -0,0 +1,230 @@
+// This is free and unencumbered software released into the public domain.
+
+use color_eyre::Section;
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{
+    near_primitives::{types::Nonce, views::AccessKeyPermissionView},
+    signer::{keystore::KeystoreSigner, AccountKeyPair, SignerTrait},
+    types::CryptoHash,
+    AccountId, NetworkConfig, Signer,
+};
+use near_crypto::{PublicKey, SecretKey};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use crate::rpc;
+
+/// Adds to the signer's pool the other full access keys of the account whose
+/// secret keys are in the system keychain, returning how many were added.
+///
+/// The signer rotates through the keys of its pool, so that transactions
+/// submitted without waiting for each other are spread across them, each key
+/// having its own nonce sequence.
+pub async fn add_keychain_keys(
+    signer: &Signer,
+    account: &AccountId,
+    network: &NetworkConfig,
+) -> Result<usize> {
+    let keys = rpc::view_access_keys(network, account)
+        .await
+        .with_context(|| format!("Failed to list the access keys of {account}"))?;
+    let current = signer.get_public_key().await?;
+
+    let mut added = 0;
+    for key in keys.keys {
+        if key.public_key == current
+            || key.access_key.permission != AccessKeyPermissionView::FullAccess
+        {
+            continue;
+        }
+        let candidate = KeystoreSigner::new_with_pubkey(key.public_key.clone());
+        if candidate
+            .get_secret_key(account, &key.public_key)
+            .await
+            .is_ok()
+        {
+            signer.add_signer_to_pool(candidate).await?;
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// The nonces last used with each access key.
+///
+/// Each transaction uses the next nonce after both the last one used and the
+/// one of the access key on chain, so that transactions in flight don't reuse
+/// nonces, and nonces used by other clients are skipped.
+#[derive(Debug, Default)]
+pub struct Nonces {
+    last: Mutex<HashMap<PublicKey, Nonce>>,
+}
+
+impl Nonces {
+    /// Returns the next nonce of the access key and a recent block hash.
+    pub async fn next(
+        &self,
+        account: &AccountId,
+        public_key: &PublicKey,
+        network: &NetworkConfig,
+    ) -> Result<(Nonce, CryptoHash)> {
+        let (access_key, block_hash) = rpc::view_access_key(network, account, public_key)
+            .await
+            .with_context(|| format!("Failed to fetch the access key {public_key} of {account}"))?;
+        let mut last = self.last.lock().unwrap();
+        let nonce = last.entry(public_key.clone()).or_default();
+        *nonce = (*nonce).max(access_key.nonce) + 1;
+        Ok((*nonce, block_hash.into()))
+    }
+
+    /// Records the nonce of the access key reported by an `InvalidNonce`
+    /// error, so that the next transaction uses a higher one.
+    pub fn observe(&self, public_key: &PublicKey, ak_nonce: Nonce) {
+        let mut last = self.last.lock().unwrap();
+        let nonce = last.entry(public_key.clone()).or_default();
+        *nonce = (*nonce).max(ak_nonce);
+    }
+}
+
+/// Returns a signer with the first full access key of the account whose
+/// secret key is in the system keychain.
+async fn keychain_signer(account: &AccountId, network: &NetworkConfig) -> Result<Arc<Signer>> {
+    let keys = rpc::view_access_keys(network, account)
+        .await
+        .with_context(|| format!("Failed to list the access keys of {account}"))?;
+    for key in keys.keys {
+        if key.access_key.permission != AccessKeyPermissionView::FullAccess {
+            continue;
+        }
+        let candidate = KeystoreSigner::new_with_pubkey(key.public_key.clone());
+        if candidate
+            .get_secret_key(account, &key.public_key)
+            .await
+            .is_ok()
+        {
+            return Signer::new(candidate).context("Failed to create keychain signer");
+        }
+    }
+    bail!("No full access key of {account} is in the system keychain")
+}
+
+/// Returns a signer for the account, with its keys in the system keychain,
+/// or else the keys of the `NEAR_PRIVATE_KEY` environment variable.
+pub async fn get_signer(account: &AccountId, network: &NetworkConfig) -> Result<Arc<Signer>> {
+    let keystore_result = keychain_signer(account, network)
+        .await
+        .with_context(|| format!("Failed to get signer from keychain for \"{}\"", account));
+
+    let keystore_err = match keystore_result {
+        Ok(keystore) => {
+            // Spread transactions across all the access keys in the keychain:
+            match add_keychain_keys(&keystore, account, network).await {
+                Ok(0) => {}
+                Ok(added) => tracing::debug!(account = %account, "using {} access keys", added + 1),
+                Err(err) => tracing::warn!(?err, "failed to look up other access keys"),
+            }
+            return Ok(keystore);
+        }
+        Err(err) => err,
+    };
+
+    let secret_key_result = std::env::var("NEAR_PRIVATE_KEY")
+        .map_err(|err| match err {
+            std::env::VarError::NotPresent => {
+                eyre!("Environment variable NEAR_PRIVATE_KEY is not present")
+            }
+            std::env::VarError::NotUnicode(_os_string) => {
+                eyre!("Environment variable NEAR_PRIVATE_KEY has invalid data",)
+            }
+        })
+        .and_then(|keys| {
+            // Several keys, separated by commas, form a pool of access keys:
+            keys.split(',')
+                .map(|key| {
+                    key.trim()
+                        .parse()
+                        .context("Invalid NEAR private key format")
+                })
+                .collect::<Result<Vec<_>>>()
+        });
+    let secret_key_result = match secret_key_result {
+        Ok(secret_keys) => key_pool_signer(secret_keys).await,
+        Err(err) => Err(err),
+    };
+
+    let secret_key_err = match secret_key_result {
+        Ok(secret_key) => return Ok(secret_key),
+        Err(err) => err,
+    };
+
+    Err(eyre!(
+        "Unable to find credentials for NEAR account \"{}\"",
+        account
+    )
+    .with_note(|| {
+        format!(
+            "\nThe CLI tried two methods to find your credentials:\n\
+             1. Searching the system keychain for account \"{}\"\n\
+             2. Looking for a private key in the NEAR_PRIVATE_KEY environment variable\n",
+            account
+        )
+    })
+    .with_section(|| format!("Keychain error: {:#}", keystore_err))
+    .with_section(|| format!("Private key error: {:#}", secret_key_err))
+    .with_suggestion(|| {
+        "\nYou can:\n\
+             • Import your account into the keychain:\n\t $ near account import-account\n\
+             • Set the NEAR_PRIVATE_KEY environment variable with your private key (\"ed25519:...\"), or several separated by commas\n\
+             • Use the --signer option to specify a different account that has access to the repository contract"
+    }))
+}
+
+/// Saves the secret key of the account in the system keychain, where
+/// [`get_signer`] finds it, as `near account import-account` does.
+pub async fn save_to_keychain(
+    account: &AccountId,
+    network: &NetworkConfig,
+    secret_key: SecretKey,
+) -> Result<()> {
+    let service = format!("near-{}-{}", network.network_name, account);
+    let user = format!("{account}:{}", secret_key.public_key());
+    let key_pair = serde_json::to_string(&AccountKeyPair {
+        public_key: secret_key.public_key(),
+        private_key: secret_key,
+    })?;
+    tokio::task::spawn_blocking(move || {
+        keyring::Entry::new(&service, &user)?.set_password(&key_pair)
+    })
+    .await?
+    .with_context(|| format!("Failed to save the key of {account} in the system keychain"))
+}
+
+/// Returns whether the public key is a full access key of the account.
+pub async fn is_full_access_key(
+    account: &AccountId,
+    public_key: &PublicKey,
+    network: &NetworkConfig,
+) -> Result<bool> {
+    let keys = rpc::view_access_keys(network, account)
+        .await
+        .with_context(|| format!("Failed to list the access keys of {account}"))?;
+    Ok(keys.keys.iter().any(|key| {
+        key.public_key == *public_key
+            && key.access_key.permission == AccessKeyPermissionView::FullAccess
+    }))
+}
+
+/// Returns a signer rotating through the access keys of the secret keys.
+async fn key_pool_signer(secret_keys: Vec<near_crypto::SecretKey>) -> Result<Arc<Signer>> {
+    let mut secret_keys = secret_keys.into_iter().map(Signer::from_secret_key);
+    let Some(first) = secret_keys.next() else {
+        bail!("No private key");
+    };
+    let signer = Signer::new(first).context("Failed to create signer from private key")?;
+    for secret_key in secret_keys {
+        signer.add_signer_to_pool(secret_key).await?;
+    }
+    Ok(signer)
+}
@@ -0,0 +1,69 @@
+// This is free and unencumbered software released into the public domain.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::OpenOptions,
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+/// A record of one batch submitted in a transaction, stored as a line of the
+/// NDJSON receipt ledger.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Unix timestamp (seconds) when the transaction outcome was received.
+    pub timestamp: u64,
+    pub network: String,
+    pub repository: String,
+    pub dataset: String,
+    pub transaction: String,
+    /// Account that signed the transaction, missing in older ledgers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_height: Option<u64>,
+    pub filename: PathBuf,
+    /// Hex SHA-256 of the batch's call arguments, as in the publish cache,
+    /// missing in older ledgers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    pub statement_count: usize,
+    pub bytes: usize,
+    /// Gas burnt by the whole transaction, including its receipts.
+    pub gas_burnt: u64,
+    /// Tokens (in yoctoNEAR) burnt by the whole transaction, including its receipts.
+    pub tokens_burnt: String,
+    /// Either `success` or `failure`, or the finality waited for if the
+    /// execution outcome is not known yet.
+    pub status: String,
+}
+
+/// Appends the entries to the ledger at the given path, creating it if needed.
+pub fn append(path: &Path, entries: &[LedgerEntry]) -> std::io::Result<()> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut lines, entry)?;
+        lines.push(b'\n');
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&lines)
+}
+
+/// Reads all the entries of the ledger at the given path.
+pub fn read(path: &Path) -> std::io::Result<Vec<LedgerEntry>> {
+    let file = std::fs::File::open(path)?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries.push(serde_json::from_str(&line)?);
+    }
+    Ok(entries)
+}
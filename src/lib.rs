@@ -1,6 +1,70 @@
 // This is free and unencumbered software released into the public domain.
 
+#[cfg(feature = "cli")]
+pub mod arweave;
+#[cfg(feature = "cli")]
+pub mod audit;
+#[cfg(feature = "cli")]
+pub mod balance;
+pub mod bloom;
+pub mod cache;
+pub mod chunked;
+#[cfg(feature = "cli")]
+pub mod config;
 pub mod context;
+pub mod convert;
+#[cfg(feature = "postgres")]
+pub mod database;
+pub mod decode;
+pub mod disk;
+#[cfg(feature = "cli")]
+pub mod doctor;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod finality;
+#[cfg(feature = "cli")]
+pub mod hook;
+pub mod inference;
+#[cfg(unix)]
+pub mod ipc;
+pub mod iri;
+pub mod jsonl;
+#[cfg(feature = "cli")]
+pub mod keys;
+pub mod ledger;
+pub mod literal;
+pub mod lock;
+pub mod metrics;
+pub mod mmap;
+#[cfg(feature = "cli")]
+pub mod offline;
+#[cfg(feature = "cli")]
+pub mod pacing;
+pub mod patch;
+pub mod plugin;
+#[cfg(feature = "cli")]
+pub mod preflight;
 pub mod prepare;
+pub mod provenance;
+#[cfg(feature = "cli")]
 pub mod publish;
+#[cfg(feature = "cli")]
+pub mod publisher;
+pub mod r2rml;
+#[cfg(feature = "cli")]
+pub mod rpc;
+pub mod schedule;
+#[cfg(feature = "cli")]
+pub mod server;
+#[cfg(feature = "cli")]
+pub mod setup;
+pub mod shard;
+pub mod snapshot;
+pub mod statistics;
+#[cfg(feature = "cli")]
+pub mod store;
+pub mod syntax;
+#[cfg(feature = "parquet")]
+pub mod triple_table;
 pub mod ui;
+pub mod update;
@@ -0,0 +1,459 @@
+// This is free and unencumbered software released into the public domain.
+
+use oxrdf::{Literal, LiteralRef, Quad, QuadRef, Term, TermRef};
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc, UnicodeNormalization as _};
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+/// What to do with statements having a literal that isn't valid for its
+/// XSD datatype, e.g. `"12x"^^xsd:integer`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LiteralValidation {
+    /// Log a warning and keep the statement.
+    Warn,
+    /// Log a warning and leave out the statement.
+    Skip,
+    /// Stop preparing.
+    Fail,
+}
+
+impl LiteralValidation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Warn => "warn",
+            Self::Skip => "skip",
+            Self::Fail => "fail",
+        }
+    }
+}
+
+impl std::fmt::Display for LiteralValidation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for LiteralValidation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "warn" => Ok(Self::Warn),
+            "skip" => Ok(Self::Skip),
+            "fail" => Ok(Self::Fail),
+            _ => Err(format!(
+                "unknown literal validation `{s}`, expected one of: warn, skip, fail"
+            )),
+        }
+    }
+}
+
+/// Returns the literal object of the statement if it isn't valid for its
+/// XSD datatype.
+pub fn invalid_literal(quad: QuadRef<'_>) -> Option<LiteralRef<'_>> {
+    match quad.object {
+        TermRef::Literal(literal) if is_valid(literal) == Some(false) => Some(literal),
+        _ => None,
+    }
+}
+
+/// Puts the value and language tag of the literal object of the statement
+/// in Unicode Normalization Form C, leaving the literals of quoted triples
+/// as they are.
+pub fn normalize_nfc(quad: &mut Quad) {
+    let Term::Literal(ref literal) = quad.object else {
+        return;
+    };
+    let value = nfc(literal.value());
+    let language = literal.language().map(nfc);
+    let unchanged = matches!(value, Cow::Borrowed(_)) && !matches!(language, Some(Cow::Owned(_)));
+    if unchanged {
+        return;
+    }
+    let literal = match language {
+        Some(language) => Literal::new_language_tagged_literal_unchecked(
+            value.into_owned(),
+            language.into_owned(),
+        ),
+        None => Literal::new_typed_literal(value.into_owned(), literal.datatype()),
+    };
+    quad.object = literal.into();
+}
+
+fn nfc(value: &str) -> Cow<'_, str> {
+    match is_nfc(value) {
+        true => Cow::Borrowed(value),
+        false => Cow::Owned(value.nfc().collect()),
+    }
+}
+
+/// Returns whether the lexical form of the literal is valid for its XSD
+/// datatype, or `None` if the datatype isn't checked.
+pub fn is_valid(literal: LiteralRef<'_>) -> Option<bool> {
+    let value = literal.value();
+    let datatype = literal.datatype().as_str().strip_prefix(XSD)?;
+    let bounded = |min: i128, max: i128| {
+        is_integer(value)
+            && value
+                .parse::<i128>()
+                .is_ok_and(|n| (min..=max).contains(&n))
+    };
+    Some(match datatype {
+        "boolean" => matches!(value, "true" | "false" | "1" | "0"),
+        "decimal" => is_decimal(value),
+        "integer" => is_integer(value),
+        "long" => bounded(i64::MIN.into(), i64::MAX.into()),
+        "int" => bounded(i32::MIN.into(), i32::MAX.into()),
+        "short" => bounded(i16::MIN.into(), i16::MAX.into()),
+        "byte" => bounded(i8::MIN.into(), i8::MAX.into()),
+        "unsignedLong" => bounded(0, u64::MAX.into()),
+        "unsignedInt" => bounded(0, u32::MAX.into()),
+        "unsignedShort" => bounded(0, u16::MAX.into()),
+        "unsignedByte" => bounded(0, u8::MAX.into()),
+        "nonNegativeInteger" => is_integer(value) && !is_negative(value),
+        "positiveInteger" => is_integer(value) && !is_negative(value) && !is_zero(value),
+        "nonPositiveInteger" => is_integer(value) && (is_negative(value) || is_zero(value)),
+        "negativeInteger" => is_integer(value) && is_negative(value) && !is_zero(value),
+        "double" | "float" => is_double(value),
+        "date" => with_timezone(value, |value| date(value).is_some_and(str::is_empty)),
+        "dateTime" => with_timezone(value, is_date_time),
+        "dateTimeStamp" => has_timezone(value) && with_timezone(value, is_date_time),
+        "time" => with_timezone(value, is_time),
+        "gYear" => with_timezone(value, |value| year(value).is_some_and(str::is_empty)),
+        "gYearMonth" => with_timezone(value, |value| {
+            year(value)
+                .and_then(|rest| rest.strip_prefix('-'))
+                .and_then(|rest| two_digits(rest, 1, 12))
+                .is_some_and(|(_, rest)| rest.is_empty())
+        }),
+        "duration" => is_duration(value),
+        _ => return None,
+    })
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit())
+}
+
+fn unsigned(value: &str) -> &str {
+    value.strip_prefix(['+', '-']).unwrap_or(value)
+}
+
+fn is_integer(value: &str) -> bool {
+    is_digits(unsigned(value))
+}
+
+fn is_negative(value: &str) -> bool {
+    value.starts_with('-') && !is_zero(value)
+}
+
+fn is_zero(value: &str) -> bool {
+    unsigned(value).bytes().all(|byte| byte == b'0')
+}
+
+fn is_decimal(value: &str) -> bool {
+    match unsigned(value).split_once('.') {
+        Some((whole, fraction)) => {
+            (whole.is_empty() || is_digits(whole))
+                && (fraction.is_empty() || is_digits(fraction))
+                && !(whole.is_empty() && fraction.is_empty())
+        }
+        None => is_digits(unsigned(value)),
+    }
+}
+
+fn is_double(value: &str) -> bool {
+    if matches!(value, "INF" | "+INF" | "-INF" | "NaN") {
+        return true;
+    }
+    match value.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => is_decimal(mantissa) && is_integer(exponent),
+        None => is_decimal(value),
+    }
+}
+
+/// Returns whether the value ends with a timezone, `Z` or `±hh:mm`.
+fn has_timezone(value: &str) -> bool {
+    value.ends_with('Z') || timezone_start(value).is_some()
+}
+
+/// Returns the position of a `±hh:mm` timezone at the end of the value.
+fn timezone_start(value: &str) -> Option<usize> {
+    let start = value.len().checked_sub(6)?;
+    let (hours, minutes) = value
+        .get(start..)?
+        .strip_prefix(['+', '-'])?
+        .split_once(':')?;
+    let valid = is_digits(hours)
+        && is_digits(minutes)
+        && (hours.parse::<u8>().ok()? < 14 || (hours == "14" && minutes == "00"))
+        && minutes.parse::<u8>().ok()? < 60;
+    valid.then_some(start)
+}
+
+/// Returns whether the value, without its optional timezone, is valid.
+fn with_timezone(value: &str, valid: impl Fn(&str) -> bool) -> bool {
+    if let Some(value) = value.strip_suffix('Z') {
+        return valid(value);
+    }
+    match timezone_start(value) {
+        // A date's own `-` separators aren't a timezone:
+        Some(start) if valid(&value[..start]) => true,
+        _ => valid(value),
+    }
+}
+
+/// Parses a year of at least 4 digits, with no leading zeros beyond 4
+/// digits, returning the rest of the value.
+fn year(value: &str) -> Option<&str> {
+    let value = value.strip_prefix('-').unwrap_or(value);
+    let digits = value.bytes().take_while(u8::is_ascii_digit).count();
+    let year = &value[..digits];
+    let valid = digits >= 4 && !(digits > 4 && year.starts_with('0'));
+    valid.then(|| &value[digits..])
+}
+
+/// Parses two digits between `min` and `max`, returning them and the rest
+/// of the value.
+fn two_digits(value: &str, min: u8, max: u8) -> Option<(u8, &str)> {
+    let digits = value.get(..2)?;
+    if !is_digits(digits) {
+        return None;
+    }
+    let number: u8 = digits.parse().ok()?;
+    (min..=max).contains(&number).then(|| (number, &value[2..]))
+}
+
+/// Parses a date, `YYYY-MM-DD`, returning the rest of the value.
+fn date(value: &str) -> Option<&str> {
+    let year_digits = value.strip_prefix('-').unwrap_or(value);
+    let rest = year(value)?;
+    let year: i64 = year_digits[..year_digits.len() - rest.len()].parse().ok()?;
+    let (month, rest) = two_digits(rest.strip_prefix('-')?, 1, 12)?;
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    let (_, rest) = two_digits(rest.strip_prefix('-')?, 1, days)?;
+    Some(rest)
+}
+
+fn is_date_time(value: &str) -> bool {
+    date(value)
+        .and_then(|rest| rest.strip_prefix('T'))
+        .is_some_and(is_time)
+}
+
+/// Returns whether the value is a time, `hh:mm:ss` with optional fractional
+/// seconds, where `24:00:00` is the end of the day.
+fn is_time(value: &str) -> bool {
+    let Some((hours, rest)) = two_digits(value, 0, 24) else {
+        return false;
+    };
+    let Some((minutes, rest)) = rest
+        .strip_prefix(':')
+        .and_then(|rest| two_digits(rest, 0, 59))
+    else {
+        return false;
+    };
+    let Some((seconds, rest)) = rest
+        .strip_prefix(':')
+        .and_then(|rest| two_digits(rest, 0, 59))
+    else {
+        return false;
+    };
+    let fraction = match rest.strip_prefix('.') {
+        Some(fraction) if is_digits(fraction) => fraction,
+        Some(_) => return false,
+        None if rest.is_empty() => "",
+        None => return false,
+    };
+    hours < 24 || (minutes == 0 && seconds == 0 && is_zero(fraction))
+}
+
+/// Returns whether the value is a duration, e.g. `P1Y2M3DT4H5M6.7S`.
+fn is_duration(value: &str) -> bool {
+    let value = value.strip_prefix('-').unwrap_or(value);
+    let Some(value) = value.strip_prefix('P') else {
+        return false;
+    };
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let date_valid = components(date, &['Y', 'M', 'D'], false);
+    let time_valid = time.map(|time| components(time, &['H', 'M', 'S'], true));
+    match (date_valid, time_valid) {
+        (Some(_), Some(Some(time_count))) => time_count > 0,
+        (Some(date_count), None) => date_count > 0,
+        _ => false,
+    }
+}
+
+/// Returns the number of `<number><designator>` components of the value in
+/// the order of the designators, or `None` if invalid. Only the last
+/// component may have a fraction, if `fraction`.
+fn components(mut value: &str, designators: &[char], fraction: bool) -> Option<usize> {
+    let mut count = 0;
+    for (index, &designator) in designators.iter().enumerate() {
+        let Some(end) = value.find(designator) else {
+            continue;
+        };
+        let number = &value[..end];
+        let last = index + 1 == designators.len();
+        let valid = if fraction && last {
+            is_decimal(number) && !number.starts_with(['+', '-'])
+        } else {
+            is_digits(number)
+        };
+        if !valid {
+            return None;
+        }
+        value = &value[end + 1..];
+        count += 1;
+    }
+    value.is_empty().then_some(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use oxrdf::{GraphName, NamedNode};
+
+    fn typed(value: &str, datatype: &str) -> Literal {
+        Literal::new_typed_literal(value, NamedNode::new_unchecked(format!("{XSD}{datatype}")))
+    }
+
+    fn valid(value: &str, datatype: &str) -> Option<bool> {
+        is_valid(typed(value, datatype).as_ref())
+    }
+
+    fn quad(object: impl Into<Term>) -> Quad {
+        Quad::new(
+            NamedNode::new_unchecked("http://example.org/s"),
+            NamedNode::new_unchecked("http://example.org/p"),
+            object,
+            GraphName::DefaultGraph,
+        )
+    }
+
+    #[test]
+    fn checks_numbers() {
+        assert_eq!(valid("12", "integer"), Some(true));
+        assert_eq!(valid("+12", "integer"), Some(true));
+        assert_eq!(valid("-0", "integer"), Some(true));
+        assert_eq!(valid("12x", "integer"), Some(false));
+        assert_eq!(valid("", "integer"), Some(false));
+        assert_eq!(valid("-128", "byte"), Some(true));
+        assert_eq!(valid("128", "byte"), Some(false));
+        assert_eq!(valid("9223372036854775808", "long"), Some(false));
+        assert_eq!(valid("-1", "unsignedByte"), Some(false));
+        assert_eq!(valid("0", "positiveInteger"), Some(false));
+        assert_eq!(valid("-0", "negativeInteger"), Some(false));
+        assert_eq!(valid("-0", "nonPositiveInteger"), Some(true));
+        assert_eq!(valid(".5", "decimal"), Some(true));
+        assert_eq!(valid("5.", "decimal"), Some(true));
+        assert_eq!(valid(".", "decimal"), Some(false));
+        assert_eq!(valid("1e3", "decimal"), Some(false));
+        assert_eq!(valid("1.5E-3", "double"), Some(true));
+        assert_eq!(valid("-INF", "float"), Some(true));
+        assert_eq!(valid("inf", "double"), Some(false));
+        assert_eq!(valid("1", "boolean"), Some(true));
+        assert_eq!(valid("TRUE", "boolean"), Some(false));
+    }
+
+    #[test]
+    fn checks_dates_and_times() {
+        assert_eq!(valid("2024-02-29", "date"), Some(true));
+        assert_eq!(valid("2023-02-29", "date"), Some(false));
+        assert_eq!(valid("2024-13-01", "date"), Some(false));
+        assert_eq!(valid("2024-02-29Z", "date"), Some(true));
+        assert_eq!(valid("2024-02-29+01:00", "date"), Some(true));
+        assert_eq!(valid("2024-02-29+15:00", "date"), Some(false));
+        assert_eq!(valid("2024-01-01T12:30:00.5Z", "dateTime"), Some(true));
+        assert_eq!(valid("2024-01-01T24:00:00", "dateTime"), Some(true));
+        assert_eq!(valid("2024-01-01T24:00:01", "dateTime"), Some(false));
+        assert_eq!(valid("2024-01-01", "dateTime"), Some(false));
+        assert_eq!(valid("2024-01-01T12:00:00", "dateTimeStamp"), Some(false));
+        assert_eq!(valid("2024-01-01T12:00:00Z", "dateTimeStamp"), Some(true));
+        assert_eq!(valid("12:30", "time"), Some(false));
+        assert_eq!(valid("2024", "gYear"), Some(true));
+        assert_eq!(valid("02024", "gYear"), Some(false));
+        assert_eq!(valid("2024-12", "gYearMonth"), Some(true));
+        assert_eq!(valid("2024-13", "gYearMonth"), Some(false));
+    }
+
+    #[test]
+    fn checks_durations() {
+        assert_eq!(valid("P1Y2M3DT4H5M6.7S", "duration"), Some(true));
+        assert_eq!(valid("-P1D", "duration"), Some(true));
+        assert_eq!(valid("PT1.5S", "duration"), Some(true));
+        assert_eq!(valid("PT1.5M", "duration"), Some(false));
+        assert_eq!(valid("P1S", "duration"), Some(false));
+        assert_eq!(valid("P", "duration"), Some(false));
+        assert_eq!(valid("PT", "duration"), Some(false));
+    }
+
+    #[test]
+    fn leaves_other_datatypes_unchecked() {
+        assert_eq!(valid("\"quoted\"\n\\", "string"), None);
+        let custom =
+            Literal::new_typed_literal("12x", NamedNode::new_unchecked("http://example.org/t"));
+        assert_eq!(is_valid(custom.as_ref()), None);
+        let tagged = Literal::new_language_tagged_literal_unchecked("12x", "en");
+        assert_eq!(is_valid(tagged.as_ref()), None);
+    }
+
+    #[test]
+    fn finds_invalid_literal_objects() {
+        let invalid = quad(typed("12x", "integer"));
+        assert_eq!(
+            invalid_literal(invalid.as_ref()),
+            Some(typed("12x", "integer").as_ref())
+        );
+        assert_eq!(invalid_literal(quad(typed("12", "integer")).as_ref()), None);
+        let node = quad(NamedNode::new_unchecked("http://example.org/o"));
+        assert_eq!(invalid_literal(node.as_ref()), None);
+    }
+
+    #[test]
+    fn normalizes_values_and_language_tags() {
+        let mut tagged = quad(Literal::new_language_tagged_literal_unchecked(
+            "cafe\u{301}",
+            "en",
+        ));
+        normalize_nfc(&mut tagged);
+        assert_eq!(
+            tagged.object,
+            Term::from(Literal::new_language_tagged_literal_unchecked(
+                "caf\u{e9}",
+                "en"
+            ))
+        );
+
+        let mut typed_quad = quad(typed("e\u{301}", "string"));
+        normalize_nfc(&mut typed_quad);
+        assert_eq!(typed_quad.object, Term::from(typed("\u{e9}", "string")));
+
+        let escaped = quad(Literal::new_simple_literal("\"quoted\"\n\\"));
+        let mut normalized = escaped.clone();
+        normalize_nfc(&mut normalized);
+        assert_eq!(normalized, escaped);
+    }
+
+    #[test]
+    fn parses_validation_modes() {
+        for mode in [
+            LiteralValidation::Warn,
+            LiteralValidation::Skip,
+            LiteralValidation::Fail,
+        ] {
+            assert_eq!(mode.as_str().parse::<LiteralValidation>(), Ok(mode));
+        }
+        assert!("ignore".parse::<LiteralValidation>().is_err());
+    }
+}
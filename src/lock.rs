@@ -0,0 +1,92 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, Context as _, Result};
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read as _, Write as _},
+    path::{Path, PathBuf},
+};
+
+use crate::cache::cache_dir;
+
+/// An advisory lock on publishing to a repository, so that two runs don't
+/// submit transactions to it at once, racing for the nonces of the signer
+/// and publishing the same batches twice.
+///
+/// The lock is held until dropped, and released by the OS if the process
+/// dies. Locking is only supported on Unix, elsewhere failing to acquire.
+#[derive(Debug)]
+pub struct PublishLock {
+    path: PathBuf,
+    _file: File,
+}
+
+impl PublishLock {
+    /// Returns the path of the lock of a repository,
+    /// `$XDG_CACHE_HOME/asimov-dataset/<repository>.lock` or
+    /// `~/.cache/asimov-dataset/<repository>.lock`.
+    pub fn default_path(repository: &str) -> Option<PathBuf> {
+        Some(cache_dir()?.join(format!("{repository}.lock")))
+    }
+
+    /// Takes the lock at the given path, failing if another process holds it.
+    pub fn acquire(path: PathBuf) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock {}", path.display()))?;
+        let locked =
+            try_lock(&file).with_context(|| format!("Failed to lock {}", path.display()))?;
+        if !locked {
+            let mut pid = String::new();
+            let _ = file.read_to_string(&mut pid);
+            match pid.trim() {
+                "" => bail!("Another run is publishing to this repository"),
+                pid => bail!("Another run (process {pid}) is publishing to this repository"),
+            }
+        }
+        // Record the holder, for the error of the next run:
+        file.set_len(0)
+            .and_then(|_| write!(file, "{}", std::process::id()))
+            .with_context(|| format!("Failed to write lock {}", path.display()))?;
+        Ok(Self { path, _file: file })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Takes an exclusive lock on the file without waiting, returning whether it
+/// was taken.
+#[cfg(unix)]
+fn try_lock(file: &File) -> std::io::Result<bool> {
+    use std::os::unix::io::AsRawFd as _;
+
+    // SAFETY: the descriptor is open for as long as the file is borrowed.
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let err = std::io::Error::last_os_error();
+    match err.kind() {
+        std::io::ErrorKind::WouldBlock => Ok(false),
+        _ => Err(err),
+    }
+}
+
+/// Fails, as files aren't locked outside of Unix, rather than letting runs
+/// publish at once.
+#[cfg(not(unix))]
+fn try_lock(_file: &File) -> std::io::Result<bool> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "file locks are only supported on Unix",
+    ))
+}
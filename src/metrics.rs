@@ -0,0 +1,193 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::{
+    atomic::AtomicCell,
+    channel::{Receiver, Sender},
+};
+use std::{
+    fmt::Write as _,
+    io::{Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use crate::ui::Event;
+
+/// Counters and gauges of a prepare or publish run, exposed in the Prometheus
+/// text format.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    statements_read: AtomicCell<u64>,
+    bytes_read: AtomicCell<u64>,
+    batches_prepared: AtomicCell<u64>,
+    statements_prepared: AtomicCell<u64>,
+    batches_published: AtomicCell<u64>,
+    statements_published: AtomicCell<u64>,
+    bytes_uploaded: AtomicCell<u64>,
+    batches_failed: AtomicCell<u64>,
+    rpc_errors: AtomicCell<u64>,
+    current_file: Mutex<Option<PathBuf>>,
+}
+
+impl Metrics {
+    /// Updates the metrics from a progress event.
+    pub fn record(&self, event: &Event) {
+        match event {
+            Event::Reader(progress) => {
+                self.statements_read
+                    .fetch_add(progress.statement_count as u64);
+                self.bytes_read.fetch_add(progress.bytes as u64);
+                *self.current_file.lock().unwrap() =
+                    (!progress.finished).then(|| progress.filename.clone());
+            }
+            Event::Prepare(progress) => {
+                self.batches_prepared.fetch_add(1);
+                self.statements_prepared
+                    .fetch_add(progress.statement_count as u64);
+            }
+            Event::Publish(progress) if progress.published() => {
+                self.batches_published.fetch_add(1);
+                self.statements_published
+                    .fetch_add(progress.statement_count as u64);
+                self.bytes_uploaded.fetch_add(progress.bytes as u64);
+            }
+            Event::PublishFailure(failure) => {
+                self.batches_failed
+                    .fetch_add(failure.filenames.len() as u64);
+            }
+            Event::Publish(_) | Event::PublishSkipped(_) | Event::PublishPaused(_) => {}
+        }
+    }
+
+    /// Counts a failed RPC request.
+    pub fn record_rpc_error(&self) {
+        self.rpc_errors.fetch_add(1);
+    }
+
+    /// Renders the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (name, kind, help, value) in [
+            (
+                "asimov_dataset_statements_read_total",
+                "counter",
+                "Statements read from input files.",
+                self.statements_read.load(),
+            ),
+            (
+                "asimov_dataset_bytes_read_total",
+                "counter",
+                "Bytes read from input files.",
+                self.bytes_read.load(),
+            ),
+            (
+                "asimov_dataset_batches_prepared_total",
+                "counter",
+                "RDF/Borsh batches prepared.",
+                self.batches_prepared.load(),
+            ),
+            (
+                "asimov_dataset_statements_prepared_total",
+                "counter",
+                "Statements in prepared batches.",
+                self.statements_prepared.load(),
+            ),
+            (
+                "asimov_dataset_batches_published_total",
+                "counter",
+                "RDF/Borsh batches published.",
+                self.batches_published.load(),
+            ),
+            (
+                "asimov_dataset_statements_published_total",
+                "counter",
+                "Statements in published batches.",
+                self.statements_published.load(),
+            ),
+            (
+                "asimov_dataset_bytes_uploaded_total",
+                "counter",
+                "Bytes of batch data uploaded.",
+                self.bytes_uploaded.load(),
+            ),
+            (
+                "asimov_dataset_batches_failed_total",
+                "counter",
+                "RDF/Borsh batches that failed to publish.",
+                self.batches_failed.load(),
+            ),
+            (
+                "asimov_dataset_rpc_errors_total",
+                "counter",
+                "Failed RPC requests.",
+                self.rpc_errors.load(),
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            let _ = writeln!(out, "{name} {value}");
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP asimov_dataset_current_file The input file currently being read."
+        );
+        let _ = writeln!(out, "# TYPE asimov_dataset_current_file gauge");
+        if let Some(ref file) = *self.current_file.lock().unwrap() {
+            let file = file.display().to_string();
+            let file = file.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(out, "asimov_dataset_current_file{{file=\"{file}\"}} 1");
+        }
+
+        out
+    }
+}
+
+/// Starts serving the metrics over HTTP on a background thread.
+pub fn serve(addr: SocketAddr, metrics: Arc<Metrics>) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            if let Err(err) = respond(stream, &metrics) {
+                tracing::debug!(?err, "failed to serve metrics");
+            }
+        }
+    });
+    Ok(())
+}
+
+fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    // The request itself is irrelevant, every path serves the metrics:
+    let mut request = [0u8; 1024];
+    let _ = stream.read(&mut request)?;
+
+    let body = metrics.render();
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Records every event passing through the returned receiver.
+pub fn tap(events: Receiver<Event>, metrics: Arc<Metrics>) -> Receiver<Event> {
+    let (tx, rx): (Sender<Event>, Receiver<Event>) = crossbeam::channel::unbounded();
+    std::thread::spawn(move || {
+        for event in events {
+            metrics.record(&event);
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
@@ -0,0 +1,66 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{Context as _, Result};
+use memmap2::Mmap;
+use oxrdfio::RdfFormat;
+use std::{cell::RefCell, fs::File, path::Path, rc::Rc, sync::Arc};
+
+use crate::chunked::{self, CHUNK_SIZE};
+
+/// A chunk of a file mapped into memory.
+struct MappedChunk {
+    map: Arc<Mmap>,
+    start: usize,
+    end: usize,
+}
+
+impl AsRef<[u8]> for MappedChunk {
+    fn as_ref(&self) -> &[u8] {
+        &self.map[self.start..self.end]
+    }
+}
+
+/// Reads the statements of a line-oriented file mapped into memory, parsing
+/// its chunks on up to `threads` threads while keeping the statements in
+/// order, and adding the bytes of each chunk to `count` as it's reached.
+///
+/// The file must not be truncated while it's read, which would crash the
+/// process with SIGBUS.
+pub fn read_quads(
+    file: &Path,
+    format: RdfFormat,
+    threads: usize,
+    count: Rc<RefCell<usize>>,
+) -> Result<Box<dyn Iterator<Item = Result<oxrdf::Quad>>>> {
+    let file = File::open(file).context("Failed to open input file")?;
+    // SAFETY: the mapping is only read, and the file is expected to be left
+    // unchanged while it's read, as documented above.
+    let map = unsafe { Mmap::map(&file) }.context("Failed to map input file")?;
+    #[cfg(unix)]
+    map.advise(memmap2::Advice::Sequential).ok();
+    let map = Arc::new(map);
+
+    let chunks = chunk_ranges(&map, CHUNK_SIZE)
+        .into_iter()
+        .map(move |(start, end)| {
+            let map = map.clone();
+            Ok(MappedChunk { map, start, end })
+        });
+    Ok(chunked::parse_chunks(chunks, format, threads, count))
+}
+
+/// Returns the byte ranges of chunks of the data of about `size` bytes,
+/// each ending after a line break, except perhaps the last one.
+fn chunk_ranges(data: &[u8], size: usize) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    while start < data.len() {
+        let end = data
+            .get(start + size..)
+            .and_then(|rest| rest.iter().position(|&byte| byte == b'\n'))
+            .map_or(data.len(), |offset| start + size + offset + 1);
+        ranges.push((start, end));
+        start = end;
+    }
+    ranges
+}
@@ -0,0 +1,160 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{
+    errors::SignerError,
+    near_primitives::{
+        borsh,
+        serialize::{from_base64, to_base64},
+        transaction::{SignedTransaction, Transaction},
+    },
+    signer::SignerTrait,
+    AccountId, Signer,
+};
+use near_crypto::{PublicKey, SecretKey, Signature};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// A signer knowing only the public key of an access key, whose secret key
+/// stays on an offline machine. It provides the key for building unsigned
+/// transactions, and fails to sign them.
+struct PublicKeySigner {
+    public_key: PublicKey,
+}
+
+#[async_trait::async_trait]
+impl SignerTrait for PublicKeySigner {
+    async fn get_secret_key(
+        &self,
+        _signer_id: &AccountId,
+        _public_key: &PublicKey,
+    ) -> Result<SecretKey, SignerError> {
+        Err(SignerError::SecretKeyIsNotAvailable)
+    }
+
+    fn get_public_key(&self) -> Result<PublicKey, SignerError> {
+        Ok(self.public_key.clone())
+    }
+}
+
+/// Returns a signer for an access key of which only the public key, e.g.
+/// `ed25519:...`, is known.
+pub fn public_key_signer(public_key: &str) -> Result<Arc<Signer>> {
+    let public_key = public_key
+        .parse()
+        .map_err(|err| eyre!("Invalid public key `{public_key}`: {err}"))?;
+    Ok(Signer::new(PublicKeySigner { public_key })?)
+}
+
+/// A transaction written for signing on an offline machine, with its nonce
+/// and block hash fixed, as one JSON file.
+///
+/// The signer signs `hash` with the secret key of `public_key` and adds the
+/// resulting signature, e.g. `ed25519:...`, as `signature`. The block hash
+/// expires after about a day, so the transaction must be signed and broadcast
+/// within that time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OfflineTransaction {
+    pub signer_id: String,
+    pub public_key: String,
+    pub nonce: u64,
+    pub receiver_id: String,
+    pub block_hash: String,
+    /// The hash of the transaction to sign.
+    pub hash: String,
+    /// The Borsh serialization of the transaction, in base64.
+    pub transaction: String,
+    /// The batches whose data the transaction sends, in order.
+    pub batches: Vec<OfflineBatch>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OfflineBatch {
+    pub filename: PathBuf,
+    pub dataset: String,
+    pub statement_count: usize,
+    /// The hash of the call arguments, identifying the batch in the publish cache.
+    pub hash: String,
+}
+
+impl OfflineTransaction {
+    pub fn new(transaction: &Transaction, batches: Vec<OfflineBatch>) -> Result<Self> {
+        let (hash, _) = transaction.get_hash_and_size();
+        Ok(Self {
+            signer_id: transaction.signer_id().to_string(),
+            public_key: transaction.public_key().to_string(),
+            nonce: transaction.nonce(),
+            receiver_id: transaction.receiver_id().to_string(),
+            block_hash: transaction.block_hash().to_string(),
+            hash: hash.to_string(),
+            transaction: to_base64(&borsh::to_vec(transaction)?),
+            batches,
+            signature: None,
+        })
+    }
+
+    /// Returns the file name of the transaction, ordering the transactions
+    /// of a directory by nonce.
+    pub fn file_name(&self) -> String {
+        format!("{:020}.json", self.nonce)
+    }
+
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        let path = dir.join(self.file_name());
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(&path, json)
+            .with_context(|| format!("Failed to write transaction {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Returns the transaction signed with its signature, checking that the
+    /// signature is valid.
+    pub fn signed(&self) -> Result<SignedTransaction> {
+        let Some(ref signature) = self.signature else {
+            bail!("The transaction isn't signed");
+        };
+        let signature: Signature = signature
+            .parse()
+            .map_err(|err| eyre!("Invalid signature `{signature}`: {err}"))?;
+        let bytes = from_base64(&self.transaction).context("Invalid transaction encoding")?;
+        let transaction: Transaction =
+            borsh::from_slice(&bytes).context("Invalid transaction encoding")?;
+        let (hash, _) = transaction.get_hash_and_size();
+        if hash.to_string() != self.hash {
+            bail!("The transaction doesn't match its hash {}", self.hash);
+        }
+        if !signature.verify(hash.as_ref(), transaction.public_key()) {
+            bail!(
+                "The signature isn't a signature of the transaction hash with {}",
+                transaction.public_key()
+            );
+        }
+        Ok(SignedTransaction::new(signature, transaction))
+    }
+}
+
+/// Reads the transactions written into a directory, ordered by nonce.
+pub fn read_transactions(dir: &Path) -> Result<Vec<(PathBuf, OfflineTransaction)>> {
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    paths.retain(|path| path.extension().is_some_and(|ext| ext == "json"));
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let transaction = std::fs::read(&path)
+                .map_err(eyre::Report::from)
+                .and_then(|json| Ok(serde_json::from_slice(&json)?))
+                .with_context(|| format!("Failed to read transaction {}", path.display()))?;
+            Ok((path, transaction))
+        })
+        .collect()
+}
@@ -0,0 +1,136 @@
+// This is free and unencumbered software released into the public domain.
+
+use near_api::NetworkConfig;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::rpc::{self, NetworkLoad};
+
+/// How long the network load is reused before checking it again.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The longest delay before a transaction while the network is busy.
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// Slows down or pauses submissions while the network is congested.
+///
+/// Submissions pause while the congestion of the most congested shard is
+/// above `max_congestion`, or the gas price is above `max_gas_price`, and
+/// resume once both are back below. While the congestion is between half of
+/// `max_congestion` and `max_congestion`, each transaction is delayed in
+/// proportion, up to 10 seconds.
+#[derive(Debug, Default)]
+pub struct Pacing {
+    max_congestion: Option<f64>,
+    max_gas_price: Option<u128>,
+    last_check: Mutex<Option<(Instant, NetworkLoad)>>,
+}
+
+impl Pacing {
+    pub fn new(max_congestion: Option<f64>, max_gas_price: Option<u128>) -> Self {
+        Self {
+            max_congestion,
+            max_gas_price,
+            last_check: Mutex::default(),
+        }
+    }
+
+    /// Waits until the network is ready for the next transaction.
+    ///
+    /// If the network load can't be fetched, the transaction isn't delayed.
+    pub async fn wait(&self, network: &NetworkConfig) {
+        let mut paused = false;
+        loop {
+            let Some(load) = self.load(network).await else {
+                return;
+            };
+            match self.delay(&load) {
+                Some(delay) => {
+                    if paused {
+                        tracing::info!(?load, "network cleared, resuming submissions");
+                    }
+                    if !delay.is_zero() {
+                        tracing::debug!(?load, ?delay, "network busy, slowing submissions");
+                        tokio::time::sleep(delay).await;
+                    }
+                    return;
+                }
+                None => {
+                    if !paused {
+                        tracing::warn!(?load, "network congested, pausing submissions");
+                        paused = true;
+                    }
+                    tokio::time::sleep(CHECK_INTERVAL).await;
+                }
+            }
+        }
+    }
+
+    /// Returns the delay before the next transaction under the given load, or
+    /// `None` if submissions should pause.
+    fn delay(&self, load: &NetworkLoad) -> Option<Duration> {
+        if self
+            .max_gas_price
+            .is_some_and(|max_gas_price| load.gas_price > max_gas_price)
+        {
+            return None;
+        }
+        let Some(max_congestion) = self.max_congestion else {
+            return Some(Duration::ZERO);
+        };
+        if load.congestion > max_congestion {
+            return None;
+        }
+        // A zero level only lets submissions through without any congestion:
+        if max_congestion == 0.0 {
+            return Some(Duration::ZERO);
+        }
+        let busy = (load.congestion - max_congestion / 2.0) / (max_congestion / 2.0);
+        Some(MAX_DELAY.mul_f64(busy.clamp(0.0, 1.0)))
+    }
+
+    /// Returns the network load, checking it again if the last check is older
+    /// than [`CHECK_INTERVAL`].
+    async fn load(&self, network: &NetworkConfig) -> Option<NetworkLoad> {
+        if let Some((checked_at, load)) = *self.last_check.lock().unwrap() {
+            if checked_at.elapsed() < CHECK_INTERVAL {
+                return Some(load);
+            }
+        }
+        let load = rpc::network_load(network, rpc::Block::Final)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, "failed to fetch the network load"))
+            .ok()?;
+        *self.last_check.lock().unwrap() = Some((Instant::now(), load));
+        Some(load)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delay(max_congestion: f64, congestion: f64) -> Option<Duration> {
+        let load = NetworkLoad {
+            gas_price: 100_000_000,
+            congestion,
+        };
+        Pacing::new(Some(max_congestion), None).delay(&load)
+    }
+
+    #[test]
+    fn slows_down_above_half_the_max_congestion() {
+        assert_eq!(delay(0.5, 0.125), Some(Duration::ZERO));
+        assert_eq!(delay(0.5, 0.375), Some(MAX_DELAY / 2));
+        assert_eq!(delay(0.5, 0.5), Some(MAX_DELAY));
+        assert_eq!(delay(0.5, 0.625), None);
+    }
+
+    #[test]
+    fn pauses_on_any_congestion_at_zero_max_congestion() {
+        assert_eq!(delay(0.0, 0.0), Some(Duration::ZERO));
+        assert_eq!(delay(0.0, 0.1), None);
+    }
+}
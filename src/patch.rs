@@ -0,0 +1,479 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use oxrdfio::{RdfFormat, RdfParser};
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::Path,
+};
+
+use crate::snapshot::Delta;
+
+/// Returns whether the file is an RDF Patch (`.rdfp`) or LD Patch (`.ldpatch`) file.
+pub fn is_patch(file: &Path) -> bool {
+    file.extension()
+        .is_some_and(|ext| ext == "rdfp" || ext == "ldpatch")
+}
+
+/// Reads the net changes of a patch file, as the statements it adds and deletes.
+///
+/// The operations are applied in order, so a statement added then deleted by
+/// the patch is only deleted, and conversely.
+pub fn read_patch(file: &Path) -> Result<Delta> {
+    let text = std::fs::read_to_string(file)?;
+    let mut changes = Changes::default();
+    match file.extension() {
+        Some(ext) if ext == "ldpatch" => read_ld_patch(&text, &mut changes)?,
+        _ => read_rdf_patch(&text, &mut changes)?,
+    }
+    Ok(changes.into_delta())
+}
+
+/// The net changes of a sequence of operations.
+#[derive(Default)]
+pub(crate) struct Changes {
+    added: BTreeSet<String>,
+    removed: BTreeSet<String>,
+}
+
+impl Changes {
+    pub(crate) fn add(&mut self, statement: String) {
+        self.removed.remove(&statement);
+        self.added.insert(statement);
+    }
+
+    pub(crate) fn delete(&mut self, statement: String) {
+        self.added.remove(&statement);
+        self.removed.insert(statement);
+    }
+
+    pub(crate) fn into_delta(self) -> Delta {
+        Delta {
+            added: self.added.into_iter().collect(),
+            removed: self.removed.into_iter().collect(),
+        }
+    }
+}
+
+/// Reads an [RDF Patch](https://afs.github.io/rdf-patch/), where each line is
+/// a header (`H`), a transaction boundary (`TX`, `TC` or `TA`), a prefix
+/// definition (`PA` or `PD`), or an added (`A`) or deleted (`D`) statement.
+///
+/// The changes of aborted transactions are ignored.
+fn read_rdf_patch(text: &str, changes: &mut Changes) -> Result<()> {
+    let mut prefixes: HashMap<String, String> = HashMap::new();
+    // The operations of the current transaction, if one is open:
+    let mut transaction: Option<Vec<(bool, String)>> = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (code, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let result = match code {
+            "H" => Ok(()),
+            "TX" => {
+                transaction = Some(Vec::new());
+                Ok(())
+            }
+            "TC" => {
+                for (add, statement) in transaction.take().unwrap_or_default() {
+                    match add {
+                        true => changes.add(statement),
+                        false => changes.delete(statement),
+                    }
+                }
+                Ok(())
+            }
+            "TA" => {
+                transaction = None;
+                Ok(())
+            }
+            "PA" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+                [prefix, iri, ..] => {
+                    let prefix = prefix.trim_matches('"').trim_end_matches(':');
+                    let iri = iri.trim_matches(|c| c == '"' || c == '<' || c == '>');
+                    prefixes.insert(prefix.to_string(), iri.to_string());
+                    Ok(())
+                }
+                _ => Err(eyre!("Expected a prefix and an IRI")),
+            },
+            "PD" => {
+                let prefix = rest.trim_end_matches('.').trim();
+                prefixes.remove(prefix.trim_matches('"').trim_end_matches(':'));
+                Ok(())
+            }
+            "A" | "D" => parse_statement(rest, &prefixes).map(|statement| {
+                let add = code == "A";
+                match transaction {
+                    Some(ref mut operations) => operations.push((add, statement)),
+                    None if add => changes.add(statement),
+                    None => changes.delete(statement),
+                }
+            }),
+            _ => Err(eyre!("Unknown operation `{code}`")),
+        };
+        result.with_context(|| format!("Invalid RDF Patch line {}", index + 1))?;
+    }
+    if transaction.is_some() {
+        bail!("The last transaction isn't committed");
+    }
+    Ok(())
+}
+
+/// Parses the terms of an RDF Patch statement into a normalized N-Quads statement.
+fn parse_statement(text: &str, prefixes: &HashMap<String, String>) -> Result<String> {
+    let terms = parse_terms(text, prefixes)?;
+    if !(3..=4).contains(&terms.len()) {
+        bail!("Expected 3 or 4 terms, found {}", terms.len());
+    }
+    let nquads = format!("{} .", terms.join(" "));
+    let mut quads = RdfParser::from_format(RdfFormat::NQuads).for_slice(nquads.as_bytes());
+    match quads.next() {
+        Some(quad) => Ok(quad?.to_string()),
+        None => Err(eyre!("Expected a statement")),
+    }
+}
+
+/// Splits the terms of an RDF Patch statement, expanding prefixed names into
+/// IRIs and ignoring the final `.`.
+fn parse_terms(text: &str, prefixes: &HashMap<String, String>) -> Result<Vec<String>> {
+    let mut terms = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let end = match c {
+            '<' => {
+                let end = text[start..]
+                    .find('>')
+                    .map(|end| start + end + 1)
+                    .ok_or_else(|| eyre!("Unterminated IRI"))?;
+                let iri = &text[start..end];
+                // Blank nodes may also be written as `<_:label>`:
+                terms.push(match iri.strip_prefix("<_:") {
+                    Some(label) => format!("_:{}", label.trim_end_matches('>')),
+                    None => iri.to_string(),
+                });
+                end
+            }
+            '"' => {
+                let mut end = None;
+                let mut escaped = false;
+                for (i, c) in text[start + 1..].char_indices() {
+                    match c {
+                        _ if escaped => escaped = false,
+                        '\\' => escaped = true,
+                        '"' => {
+                            end = Some(start + 1 + i + 1);
+                            break;
+                        }
+                        _ => {}
+                    }
+                }
+                let mut end = end.ok_or_else(|| eyre!("Unterminated literal"))?;
+                let rest = &text[end..];
+                let literal = &text[start..end];
+                if rest.starts_with('@') {
+                    let length = rest.find(char::is_whitespace).unwrap_or(rest.len());
+                    end += rest[..length].trim_end_matches('.').len();
+                    terms.push(text[start..end].to_string());
+                } else if let Some(datatype) = rest.strip_prefix("^^") {
+                    let length = datatype.find(char::is_whitespace).unwrap_or(datatype.len());
+                    let length = datatype[..length].trim_end_matches('.').len();
+                    let datatype = expand_name(&datatype[..length], prefixes)?;
+                    terms.push(format!("{literal}^^{datatype}"));
+                    end += 2 + length;
+                } else {
+                    terms.push(literal.to_string());
+                }
+                end
+            }
+            _ => {
+                let length = text[start..]
+                    .find(char::is_whitespace)
+                    .unwrap_or(text.len() - start);
+                let token = &text[start..start + length];
+                if token == "." {
+                    break;
+                }
+                let token = token.trim_end_matches('.');
+                terms.push(match token.strip_prefix("_:") {
+                    Some(_) => token.to_string(),
+                    None => expand_name(token, prefixes)?,
+                });
+                start + token.len()
+            }
+        };
+        while chars.peek().is_some_and(|&(i, _)| i < end) {
+            chars.next();
+        }
+    }
+    Ok(terms)
+}
+
+/// Expands a prefixed name into an IRI, leaving IRIs unchanged.
+fn expand_name(name: &str, prefixes: &HashMap<String, String>) -> Result<String> {
+    if name.starts_with('<') {
+        return Ok(name.to_string());
+    }
+    let (prefix, local) = name
+        .split_once(':')
+        .ok_or_else(|| eyre!("Invalid term `{name}`"))?;
+    let iri = prefixes
+        .get(prefix)
+        .ok_or_else(|| eyre!("Undefined prefix `{prefix}`"))?;
+    Ok(format!("<{iri}{local}>"))
+}
+
+/// Reads an [LD Patch](https://www.w3.org/TR/ldpatch/), made of prefix
+/// declarations and `Add`/`AddNew` and `Delete`/`DeleteExisting` operations
+/// (or their `A` and `D` abbreviations) on the default graph.
+///
+/// Variables and the `Bind`, `Cut` and `UpdateList` operations aren't
+/// supported.
+fn read_ld_patch(text: &str, changes: &mut Changes) -> Result<()> {
+    let mut prologue = String::new();
+    let mut rest = text;
+    loop {
+        rest = skip_space(rest);
+        if rest.is_empty() {
+            return Ok(());
+        }
+        let length = rest
+            .find(|c: char| c.is_whitespace() || c == '{')
+            .unwrap_or(rest.len());
+        let (keyword, after) = rest.split_at(length);
+        match keyword {
+            "@prefix" | "@base" => {
+                let end = after
+                    .find('>')
+                    .and_then(|iri_end| after[iri_end..].find('.').map(|end| iri_end + end + 1))
+                    .ok_or_else(|| eyre!("Invalid `{keyword}` declaration"))?;
+                prologue.push_str(keyword);
+                prologue.push_str(&after[..end]);
+                prologue.push('\n');
+                rest = &after[end..];
+            }
+            "Add" | "A" | "AddNew" | "Delete" | "D" | "DeleteExisting" => {
+                let (graph, after) = read_graph(skip_space(after))
+                    .with_context(|| format!("Invalid `{keyword}` operation"))?;
+                let document = format!("{prologue}{} .\n", graph.trim().trim_end_matches('.'));
+                let add = matches!(keyword, "Add" | "A" | "AddNew");
+                for quad in RdfParser::from_format(RdfFormat::Turtle).for_slice(document.as_bytes())
+                {
+                    let statement = quad
+                        .with_context(|| format!("Invalid `{keyword}` operation"))?
+                        .to_string();
+                    match add {
+                        true => changes.add(statement),
+                        false => changes.delete(statement),
+                    }
+                }
+                rest = skip_space(after)
+                    .strip_prefix('.')
+                    .ok_or_else(|| eyre!("Expected `.` after the `{keyword}` operation"))?;
+            }
+            "Bind" | "B" | "Cut" | "C" | "UpdateList" | "UL" => {
+                bail!("The LD Patch operation `{keyword}` isn't supported")
+            }
+            _ => bail!("Unknown LD Patch operation `{keyword}`"),
+        }
+    }
+}
+
+/// Skips whitespace and comments.
+pub(crate) fn skip_space(mut text: &str) -> &str {
+    loop {
+        text = text.trim_start();
+        match text.strip_prefix('#') {
+            Some(comment) => text = comment.split_once('\n').map_or("", |(_, rest)| rest),
+            None => return text,
+        }
+    }
+}
+
+/// Reads a `{ ... }` graph, which may contain nested graphs, returning its
+/// content and the text following it.
+pub(crate) fn read_graph(text: &str) -> Result<(&str, &str)> {
+    let content = text
+        .strip_prefix('{')
+        .ok_or_else(|| eyre!("Expected `{{`"))?;
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    let mut in_iri = false;
+    let mut depth = 0;
+    for (i, c) in content.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, _) if in_iri => in_iri = c != '>',
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '<') => in_iri = true,
+            (None, '{') => depth += 1,
+            (None, '}') if depth > 0 => depth -= 1,
+            (None, '}') => return Ok((&content[..i], &content[i + 1..])),
+            _ => {}
+        }
+    }
+    Err(eyre!("Expected `}}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EX: &str = "PA ex: <http://example.org/> .\n";
+
+    fn rdf_patch(text: &str) -> Result<Delta> {
+        let mut changes = Changes::default();
+        read_rdf_patch(text, &mut changes)?;
+        Ok(changes.into_delta())
+    }
+
+    fn ld_patch(text: &str) -> Result<Delta> {
+        let mut changes = Changes::default();
+        read_ld_patch(text, &mut changes)?;
+        Ok(changes.into_delta())
+    }
+
+    fn rdf_patch_error(text: &str) -> String {
+        rdf_patch(text).unwrap_err().root_cause().to_string()
+    }
+
+    fn ld_patch_error(text: &str) -> String {
+        ld_patch(text).unwrap_err().root_cause().to_string()
+    }
+
+    #[test]
+    fn reads_rdf_patch_changes() {
+        let delta = rdf_patch(
+            "H id <urn:uuid:0a1b> .
+             PA ex: <http://example.org/> .
+             # A comment
+             A ex:s ex:p \"v\" .
+             A <http://example.org/s> ex:p \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> .
+             A <_:b0> ex:p \"chat\"@fr .
+             D ex:s ex:q ex:o ex:g .
+             TX .
+             A ex:s ex:p ex:aborted .
+             TA .
+             TX .
+             D ex:s ex:p \"v\" .
+             TC .",
+        )
+        .unwrap();
+        assert_eq!(
+            delta.added,
+            [
+                "<http://example.org/s> <http://example.org/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer>",
+                "_:b0 <http://example.org/p> \"chat\"@fr",
+            ]
+        );
+        assert_eq!(
+            delta.removed,
+            [
+                "<http://example.org/s> <http://example.org/p> \"v\"",
+                "<http://example.org/s> <http://example.org/q> <http://example.org/o> <http://example.org/g>",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_rdf_patch_prefix_deletions() {
+        let error = rdf_patch_error(&format!("{EX}PD ex: .\nA ex:s ex:p ex:o ."));
+        assert_eq!(error, "Undefined prefix `ex`");
+    }
+
+    #[test]
+    fn rejects_malformed_rdf_patches() {
+        assert_eq!(
+            rdf_patch_error("X ex:s ex:p ex:o ."),
+            "Unknown operation `X`"
+        );
+        assert_eq!(
+            rdf_patch_error(&format!("{EX}A ex:s ex:p .")),
+            "Expected 3 or 4 terms, found 2"
+        );
+        assert_eq!(
+            rdf_patch_error(&format!("{EX}A ex:s ex:p \"v .")),
+            "Unterminated literal"
+        );
+        assert_eq!(
+            rdf_patch_error(&format!("{EX}A ex:s ex:p <http://example.org/o .")),
+            "Unterminated IRI"
+        );
+        assert_eq!(rdf_patch_error("PA ex:"), "Expected a prefix and an IRI");
+        assert_eq!(
+            rdf_patch_error(&format!("{EX}TX .\nA ex:s ex:p ex:o .")),
+            "The last transaction isn't committed"
+        );
+
+        // The line of the error is reported:
+        let error = rdf_patch(&format!("{EX}A ex:s ex:p")).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid RDF Patch line 2");
+    }
+
+    #[test]
+    fn reads_ld_patch_changes() {
+        let delta = ld_patch(
+            "@prefix ex: <http://example.org/> .
+             # A comment
+             Add { ex:s ex:p \"v\" ; ex:q ex:o } .
+             D { ex:s ex:p \"old\" } .
+             AddNew { ex:s ex:p \"}\" } .",
+        )
+        .unwrap();
+        assert_eq!(
+            delta.added,
+            [
+                "<http://example.org/s> <http://example.org/p> \"v\"",
+                "<http://example.org/s> <http://example.org/p> \"}\"",
+                "<http://example.org/s> <http://example.org/q> <http://example.org/o>",
+            ]
+        );
+        assert_eq!(
+            delta.removed,
+            ["<http://example.org/s> <http://example.org/p> \"old\""]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_ld_patches() {
+        assert_eq!(
+            ld_patch_error("Bind ?x <http://example.org/s> ."),
+            "The LD Patch operation `Bind` isn't supported"
+        );
+        assert_eq!(
+            ld_patch_error("Replace { } ."),
+            "Unknown LD Patch operation `Replace`"
+        );
+        assert_eq!(
+            ld_patch_error("Add { <http://example.org/s> <http://example.org/p> 1 }"),
+            "Expected `.` after the `Add` operation"
+        );
+        assert_eq!(
+            ld_patch_error("Add { <http://example.org/s> <http://example.org/p> 1 ."),
+            "Expected `}`"
+        );
+        assert_eq!(
+            ld_patch_error("@prefix ex: ."),
+            "Invalid `@prefix` declaration"
+        );
+        assert!(ld_patch("Add { ex:s ex:p ex:o } .").is_err());
+    }
+
+    #[test]
+    fn reads_nested_graphs() {
+        let (graph, rest) = read_graph(r#"{ "a}" <urn:x}> { } } rest"#).unwrap();
+        assert_eq!(graph, r#" "a}" <urn:x}> { } "#);
+        assert_eq!(rest, " rest");
+        assert_eq!(skip_space("  # comment\n  # another\n x"), "x");
+    }
+}
@@ -0,0 +1,128 @@
+// This is free and unencumbered software released into the public domain.
+
+//! Reader plugins, external programs reading input formats that aren't
+//! supported, such as HDT or TriX.
+//!
+//! A reader plugin for the `<ext>` extension is an executable named
+//! `asimov-dataset-reader-<ext>` in the `PATH`. It's run with the path of the
+//! input file as its only argument, and writes the statements of the file as
+//! N-Quads to its standard output. Its standard error is passed through, and
+//! exiting with a nonzero status fails the input.
+
+use eyre::{bail, Context as _, Result};
+use std::{
+    cell::RefCell,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::{Child, ChildStdout, Command, Stdio},
+    rc::Rc,
+};
+
+use crate::syntax;
+
+/// The prefix of the names of reader plugins, followed by the extension.
+pub const READER_PREFIX: &str = "asimov-dataset-reader-";
+
+/// Returns the reader plugin for the extension of the file, if one is in the
+/// `PATH`.
+pub fn find_reader(file: &Path) -> Option<PathBuf> {
+    let extension = file.extension()?.to_str()?;
+    let name = format!("{READER_PREFIX}{}", extension.to_ascii_lowercase());
+    std::env::split_paths(&std::env::var_os("PATH")?).find_map(|dir| {
+        let program = dir.join(&name);
+        if is_executable(&program) {
+            return Some(program);
+        }
+        let program = program.with_extension(std::env::consts::EXE_EXTENSION);
+        is_executable(&program).then_some(program)
+    })
+}
+
+#[cfg(unix)]
+fn is_executable(program: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    std::fs::metadata(program)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(program: &Path) -> bool {
+    program.is_file()
+}
+
+/// Streams the statements of the file read by the reader plugin, adding the
+/// size of the file to `count` once all its statements are read.
+pub fn read_quads(
+    program: &Path,
+    file: &Path,
+    count: Rc<RefCell<usize>>,
+) -> Result<Box<dyn Iterator<Item = Result<oxrdf::Quad>>>> {
+    let size = std::fs::metadata(file)
+        .with_context(|| format!("Failed to read {}", file.display()))?
+        .len() as usize;
+    let mut child = Command::new(program)
+        .arg(file)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .with_context(|| format!("Failed to run reader plugin {}", program.display()))?;
+    let stdout = child.stdout.take().expect("piped stdout");
+    let mut reader = Reader {
+        program: program.to_path_buf(),
+        child: Some(child),
+    };
+
+    let quads = oxrdfio::RdfParser::from_format(oxrdfio::RdfFormat::NQuads)
+        .for_reader(BufReader::<ChildStdout>::new(stdout))
+        .map(|quad| quad.map_err(|err| syntax::parse_error(err, 0)));
+    let mut finished = false;
+    Ok(Box::new(quads.chain(std::iter::from_fn(move || {
+        if finished {
+            return None;
+        }
+        finished = true;
+        match reader.wait() {
+            Ok(()) => {
+                *count.borrow_mut() += size;
+                None
+            }
+            Err(err) => Some(Err(err)),
+        }
+    }))))
+}
+
+/// A running reader plugin, killed if dropped before its output is read.
+struct Reader {
+    program: PathBuf,
+    child: Option<Child>,
+}
+
+impl Reader {
+    /// Waits for the plugin to exit, failing if it didn't succeed.
+    fn wait(&mut self) -> Result<()> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let status = child
+            .wait()
+            .with_context(|| format!("Failed to wait for {}", self.program.display()))?;
+        if !status.success() {
+            bail!(
+                "Reader plugin {} failed with {status}",
+                self.program.display()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
@@ -0,0 +1,166 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{near_primitives::hash::hash, AccountId, NetworkConfig};
+use std::path::{Path, PathBuf};
+
+use crate::{
+    balance, keys,
+    publish::{self, MAX_TX_SIZE},
+    rpc,
+    ui::format_near,
+};
+
+/// A check of the preflight checklist, with a detail of its outcome.
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub result: Result<String>,
+}
+
+impl Check {
+    pub(crate) fn new(name: impl Into<String>, result: Result<String>) -> Self {
+        Self {
+            name: name.into(),
+            result,
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// What to check before publishing to a repository.
+#[derive(Clone, Debug)]
+pub struct Preflight {
+    pub repository: AccountId,
+    pub network: NetworkConfig,
+    pub signer: AccountId,
+    /// Estimated size of the batches to publish, or `None` for a single full batch.
+    pub batch_bytes: Option<u64>,
+    /// Directories where batches would be written.
+    pub dirs: Vec<PathBuf>,
+    /// The block whose state the contract and balances are checked against.
+    pub block: rpc::Block,
+}
+
+impl Preflight {
+    /// Runs every check, going on after the failed ones.
+    pub async fn run(&self) -> Vec<Check> {
+        let mut checks = Vec::new();
+
+        let load = rpc::network_load(&self.network, self.block).await;
+        checks.push(Check::new(
+            "RPC",
+            match load {
+                Ok(ref load) => Ok(format!(
+                    "{} is reachable, gas price {} yoctoNEAR",
+                    self.network.network_name, load.gas_price
+                )),
+                Err(ref err) => Err(eyre!("{err:#}")),
+            },
+        ));
+
+        let signer = keys::get_signer(&self.signer, &self.network).await;
+        checks.push(Check::new(
+            "Credentials",
+            signer.map(|_| format!("Found the keys of {}", self.signer)),
+        ));
+
+        checks.push(Check::new("Contract", self.check_contract().await));
+
+        match load {
+            Ok(load) => checks.extend(self.check_balances(load.gas_price).await),
+            Err(_) => checks.push(Check::new(
+                "Balance",
+                Err(eyre!("Unknown gas price, the RPC is unreachable")),
+            )),
+        }
+
+        for dir in &self.dirs {
+            checks.push(Check::new(
+                format!("Directory {}", dir.display()),
+                check_writable(dir).map(|_| "Writable".to_string()),
+            ));
+        }
+        checks
+    }
+
+    /// Checks that a contract with an `rdf_insert` method is deployed at the
+    /// repository.
+    async fn check_contract(&self) -> Result<String> {
+        let Some(code_hash) =
+            publish::repository_code_hash(&self.repository, &self.network, self.block).await?
+        else {
+            bail!("No contract is deployed at {}", self.repository);
+        };
+        if code_hash == hash(publish::LOG_VAULT_WASM) {
+            return Ok(format!("The bundled contract is deployed ({code_hash})"));
+        }
+        // Invalid arguments fail after the method is found:
+        let args = publish::insert_args("", &[])?;
+        match rpc::simulate_function_call(
+            &self.network,
+            &self.repository,
+            "rdf_insert",
+            &args,
+            self.block,
+        )
+        .await
+        {
+            Err(err) if err.to_string().contains("MethodNotFound") => {
+                bail!(
+                    "The contract at {} has no `rdf_insert` method",
+                    self.repository
+                )
+            }
+            _ => Ok(format!("A compatible contract is deployed ({code_hash})")),
+        }
+    }
+
+    /// Checks that the signer can pay for the gas of the estimated batches,
+    /// and the repository for their storage.
+    async fn check_balances(&self, gas_price: u128) -> Vec<Check> {
+        let max_tx_size = rpc::max_transaction_size(&self.network, self.block)
+            .await
+            .unwrap_or(MAX_TX_SIZE) as u64;
+        let bytes = self.batch_bytes.unwrap_or(max_tx_size);
+        let (gas_cost, storage_cost) = balance::estimated_cost(bytes, max_tx_size, gas_price);
+
+        let mut needs = vec![(self.signer.clone(), gas_cost)];
+        match needs[0].0 == self.repository {
+            true => needs[0].1 += storage_cost,
+            false => needs.push((self.repository.clone(), storage_cost)),
+        }
+        let mut checks = Vec::new();
+        for (account, needed) in needs {
+            let result = balance::available_balance(&account, &self.network, self.block)
+                .await
+                .and_then(|available| {
+                    let detail = format!(
+                        "{} NEAR available, about {} NEAR needed",
+                        format_near(available),
+                        format_near(needed)
+                    );
+                    match available >= needed {
+                        true => Ok(detail),
+                        false => Err(eyre!(detail)),
+                    }
+                });
+            checks.push(Check::new(format!("Balance of {account}"), result));
+        }
+        checks
+    }
+}
+
+/// Checks that files can be created in the directory, creating it if missing.
+pub(crate) fn check_writable(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let probe = dir.join(format!(".asimov-dataset-preflight.{}", std::process::id()));
+    std::fs::write(&probe, b"")
+        .with_context(|| format!("Failed to write into {}", dir.display()))?;
+    std::fs::remove_file(&probe)
+        .with_context(|| format!("Failed to remove {}", probe.display()))?;
+    Ok(())
+}
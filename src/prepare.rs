@@ -1,367 +1,1939 @@
 // This is free and unencumbered software released into the public domain.
 
-use crossbeam::channel::{Receiver, Sender};
-use eyre::{Context as _, OptionExt, Result};
-use rdf_rs::model::Statement;
+use borsh::BorshSerialize;
+use crossbeam::{
+    atomic::AtomicCell,
+    channel::{Receiver, RecvTimeoutError, Sender},
+};
+use eyre::{bail, eyre, Context as _, Result};
+use rdf_borsh::BorshTerm;
+use rdf_rs::model::{Statement, Term};
 use rdf_writer::Writer;
 use std::{
     cell::RefCell,
-    collections::VecDeque,
+    collections::{BTreeSet, HashMap, VecDeque},
     fs::File,
-    io::{BufReader, Write},
-    path::PathBuf,
+    io::{BufReader, Cursor, Read, Write},
+    path::{Path, PathBuf},
     rc::Rc,
+    sync::Arc,
+    time::Duration,
 };
+#[cfg(feature = "cli")]
 use tokio::task::JoinSet;
 use tracing::info;
 
-use crate::context::Context;
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+use std::time::Instant;
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+use wasm_clock::Instant;
+
+use crate::{
+    bloom::StatementFilter,
+    context::Context,
+    inference::{Profile, Reasoner},
+    iri::{self, IriViolationRecord},
+    jsonl::JsonLinesLog,
+    literal::{self, LiteralValidation},
+    provenance::Provenance,
+    syntax::{self, ParseErrorRecord, SyntaxError},
+};
+
+/// Bytes of a transaction left for the `rdf_insert` header of a batch.
+const HEADER_SIZE: usize = 1024;
 
 /// Max bytes for serialized result, leaving some room for rdf_insert header.
-const MAX_FILE_SIZE: usize = 1_572_864 - 1024;
+const MAX_FILE_SIZE: usize = 1_572_864 - HEADER_SIZE;
 
 /// Controls how close we want the serialized result to be to MAX_FILE_SIZE.
 const ACCEPTABLE_RATIO: f64 = 0.95;
 
+/// Number of statements read at a time and handed to the prepare workers.
+const READ_BATCH_SIZE: usize = 100_000;
+
+/// Estimated memory taken by a buffered statement besides its terms' strings.
+const STATEMENT_OVERHEAD: usize = 128;
+
 #[derive(Clone, Debug)]
 pub struct PrepareStatsReport {
-    pub tx: Sender<crate::ui::Event>,
+    reporter: crate::ui::EventReporter,
+}
+
+impl PrepareStatsReport {
+    /// Returns a report sending the progress events to the channel.
+    pub fn new(tx: Sender<crate::ui::Event>) -> Self {
+        Self {
+            reporter: tx.into(),
+        }
+    }
+
+    /// Returns a report passing the progress events to the callback, on the
+    /// thread reporting each event.
+    pub fn from_fn(callback: impl Fn(crate::ui::Event) + Send + Sync + 'static) -> Self {
+        Self {
+            reporter: crate::ui::EventReporter::Callback(Arc::new(callback)),
+        }
+    }
+
+    /// Reports the progress event.
+    pub fn send(&self, event: crate::ui::Event) {
+        self.reporter.send(event)
+    }
+}
+
+/// Timing instrumentation of the prepare pipeline stages.
+#[derive(Debug, Default)]
+pub struct PrepareTimings {
+    pub read: StageTimings,
+    pub prepare: StageTimings,
+    pub write: StageTimings,
+    /// Number of serializations attempted while searching for batch sizes.
+    pub serialize_attempts: AtomicCell<usize>,
+    /// Number of batches accepted by the prepare workers.
+    pub prepared_batches: AtomicCell<usize>,
+}
+
+/// Accumulated timings of a single pipeline stage, summed over its workers.
+#[derive(Debug, Default)]
+pub struct StageTimings {
+    busy_nanos: AtomicCell<u64>,
+    stall_nanos: AtomicCell<u64>,
+    statements: AtomicCell<usize>,
+}
+
+impl StageTimings {
+    /// Time spent doing work.
+    pub fn busy(&self) -> Duration {
+        Duration::from_nanos(self.busy_nanos.load())
+    }
+
+    /// Time spent blocked on the stage's input or output channel.
+    pub fn stalled(&self) -> Duration {
+        Duration::from_nanos(self.stall_nanos.load())
+    }
+
+    /// Number of statements processed by the stage.
+    pub fn statements(&self) -> usize {
+        self.statements.load()
+    }
+
+    fn add_busy(&self, start: Instant, statements: usize) {
+        self.busy_nanos.fetch_add(start.elapsed().as_nanos() as u64);
+        self.add_statements(statements);
+    }
+
+    fn add_stall(&self, start: Instant) {
+        self.stall_nanos
+            .fetch_add(start.elapsed().as_nanos() as u64);
+    }
+
+    fn add_statements(&self, statements: usize) {
+        self.statements.fetch_add(statements);
+    }
+}
+
+/// Smallest target size of adapted batches, in bytes.
+const MIN_BATCH_SIZE: usize = 16 * 1024;
+
+/// Fraction of the gas attached to a batch's call that adapted batches aim
+/// to burn, leaving a margin for batches denser than the average.
+const TARGET_GAS_RATIO: f64 = 0.7;
+
+/// Weight of the latest batch in the moving average of gas burnt per byte.
+const GAS_SMOOTHING: f64 = 0.3;
+
+/// The target size of prepared batches, shared between the prepare workers
+/// and the publisher adapting it to the gas burnt by published batches.
+///
+/// The target starts at the max size of a batch. Each
+/// published batch updates a moving average of the gas burnt per byte, and the
+/// target becomes the size expected to burn [`TARGET_GAS_RATIO`] of the gas
+/// attached to a batch, so that it shrinks while batches approach the gas
+/// limit and grows back while they stay well below it.
+#[derive(Debug)]
+pub struct BatchSizer {
+    max_size: usize,
+    max_statements: Option<usize>,
+    target: AtomicCell<usize>,
+    gas_per_byte: std::sync::Mutex<Option<f64>>,
+}
+
+impl Default for BatchSizer {
+    fn default() -> Self {
+        Self::new(MAX_FILE_SIZE)
+    }
+}
+
+impl BatchSizer {
+    /// Returns a sizer of batches of at most `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        let max_size = max_size.max(MIN_BATCH_SIZE);
+        Self {
+            max_size,
+            max_statements: None,
+            target: AtomicCell::new(max_size),
+            gas_per_byte: Default::default(),
+        }
+    }
+
+    /// Returns a sizer of batches fitting with their `rdf_insert` header in
+    /// transactions of at most `max_transaction_size` bytes.
+    pub fn for_transaction_size(max_transaction_size: usize) -> Self {
+        Self::new(max_transaction_size.saturating_sub(HEADER_SIZE))
+    }
+
+    /// Limits batches to `max_statements` statements, e.g. for repository
+    /// contracts limiting the statements of each `rdf_insert` call whatever
+    /// their size.
+    pub fn with_max_statements(mut self, max_statements: Option<usize>) -> Self {
+        self.max_statements = max_statements.map(|max| max.max(1));
+        self
+    }
+
+    /// The size no batch may exceed, in bytes.
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// The number of statements no batch may exceed, if limited.
+    pub fn max_statements(&self) -> Option<usize> {
+        self.max_statements
+    }
+
+    /// The size prepared batches should be close to, in bytes.
+    pub fn target(&self) -> usize {
+        self.target.load()
+    }
+
+    /// Records the gas burnt by a published batch of `bytes` bytes, out of
+    /// the `gas_attached` gas attached to its call.
+    pub fn observe(&self, bytes: usize, gas_burnt: u64, gas_attached: u64) {
+        if bytes == 0 || gas_burnt == 0 {
+            return;
+        }
+        let sample = gas_burnt as f64 / bytes as f64;
+        let mut gas_per_byte = self.gas_per_byte.lock().unwrap();
+        let average = match *gas_per_byte {
+            Some(average) => average + GAS_SMOOTHING * (sample - average),
+            None => sample,
+        };
+        *gas_per_byte = Some(average);
+
+        let target = (TARGET_GAS_RATIO * gas_attached as f64 / average) as usize;
+        let target = target.clamp(MIN_BATCH_SIZE, self.max_size);
+        let previous = self.target.swap(target);
+        if target.abs_diff(previous) * 20 > previous {
+            info!(
+                previous,
+                target,
+                gas_per_byte = average,
+                "Adjusted batch size to gas usage"
+            );
+        }
+    }
+}
+
+/// A prepared RDF/Borsh batch file ready to be published.
+#[derive(Clone, Debug)]
+pub struct PreparedBatch {
+    pub path: PathBuf,
+    pub statement_count: usize,
+    /// The named graph of the statements, if output is split by graph.
+    pub graph: Option<String>,
+    /// The dataset to publish the batch to, overriding the publish default.
+    pub dataset: Option<String>,
+}
+
+impl PreparedBatch {
+    pub fn new(path: PathBuf, statement_count: usize) -> Self {
+        Self {
+            path,
+            statement_count,
+            graph: None,
+            dataset: None,
+        }
+    }
+}
+
+/// The order in which input files are read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum InputOrder {
+    /// Largest files first, to start the longest work as early as possible.
+    SizeDesc,
+    /// Smallest files first.
+    SizeAsc,
+    /// By path.
+    Name,
+    /// In the order given.
+    #[default]
+    AsGiven,
+}
+
+impl InputOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::SizeDesc => "size-desc",
+            Self::SizeAsc => "size-asc",
+            Self::Name => "name",
+            Self::AsGiven => "as-given",
+        }
+    }
+
+    /// Sorts the files in this order. Files of equal size keep their order.
+    pub fn sort(&self, files: &mut [PathBuf]) {
+        let size = |file: &PathBuf| std::fs::metadata(file).map_or(0, |metadata| metadata.len());
+        match self {
+            Self::SizeDesc => files.sort_by_cached_key(|file| std::cmp::Reverse(size(file))),
+            Self::SizeAsc => files.sort_by_cached_key(size),
+            Self::Name => files.sort(),
+            Self::AsGiven => {}
+        }
+    }
+}
+
+impl std::fmt::Display for InputOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for InputOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size-desc" => Ok(Self::SizeDesc),
+            "size-asc" => Ok(Self::SizeAsc),
+            "name" => Ok(Self::Name),
+            "as-given" => Ok(Self::AsGiven),
+            _ => Err(format!(
+                "unknown order `{s}`, expected one of: size-desc, size-asc, name, as-given"
+            )),
+        }
+    }
+}
+
+/// What the dataset name of each batch is derived from.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DatasetFrom {
+    /// The file name of the input, e.g. `people.nt`.
+    Filename,
+    /// The file name of the input without its extension, e.g. `people`.
+    Stem,
+    /// The named graph of the statements, as when splitting by graph.
+    Graph,
+}
+
+impl DatasetFrom {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Filename => "filename",
+            Self::Stem => "stem",
+            Self::Graph => "graph",
+        }
+    }
+
+    /// Returns the dataset name of the statements of the input, or `None`
+    /// if derived from their graph instead.
+    pub fn input_dataset(&self, input: &Path) -> Option<String> {
+        let name = match self {
+            Self::Filename => input.file_name()?,
+            Self::Stem => input.file_stem()?,
+            Self::Graph => return None,
+        };
+        Some(name.to_string_lossy().into_owned())
+    }
+}
+
+impl std::fmt::Display for DatasetFrom {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for DatasetFrom {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "filename" => Ok(Self::Filename),
+            "stem" => Ok(Self::Stem),
+            "graph" => Ok(Self::Graph),
+            _ => Err(format!(
+                "unknown dataset source `{s}`, expected one of: filename, stem, graph"
+            )),
+        }
+    }
+}
+
+/// A source of statements other than input files, such as a database.
+pub trait StatementSource: std::fmt::Debug + Send + Sync {
+    /// Returns the name identifying the source in progress reports.
+    fn name(&self) -> PathBuf;
+
+    /// Starts reading the statements of the source.
+    fn read_quads(&self) -> Result<Box<dyn Iterator<Item = Result<oxrdf::Quad>>>>;
+}
+
+/// A destination of prepared batches, such as files to publish, object
+/// storage or an in-process consumer.
+pub trait BatchSink: std::fmt::Debug + Send {
+    /// Writes the batch with the given index, starting at 1, returning the
+    /// name identifying it in progress reports, or `None` if the sink no
+    /// longer accepts batches.
+    fn write(&mut self, index: usize, batch: &RDFBDataset) -> Result<Option<PathBuf>>;
+}
+
+/// The names of prepared files, where `{index}` is the index of the batch,
+/// `{count}` its number of statements and `{hash}` the first 12 hex digits
+/// of its SHA-256, e.g. `prepared.{index}.{count}.{hash}.rdfb`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FilenameTemplate(String);
+
+impl FilenameTemplate {
+    const PLACEHOLDERS: &'static [&'static str] = &["index", "count", "hash"];
+
+    /// Returns the name of the file of the batch with the given index.
+    pub fn filename(&self, index: usize, batch: &RDFBDataset) -> String {
+        let mut filename = self
+            .0
+            .replace("{index}", &format!("{index:06}"))
+            .replace("{count}", &batch.statement_count.to_string());
+        if filename.contains("{hash}") {
+            let hash = crate::cache::batch_hash(&batch.data);
+            filename = filename.replace("{hash}", &hash[..12]);
+        }
+        filename
+    }
+
+    /// Returns the index of the batch of a file named after the template.
+    pub fn index_of(&self, filename: &str) -> Option<usize> {
+        let mut template = self.0.as_str();
+        let mut name = filename;
+        let mut index = None;
+        while let Some(start) = template.find('{') {
+            let end = start + template[start..].find('}')?;
+            name = name.strip_prefix(&template[..start])?;
+            let len = match &template[start + 1..end] {
+                "hash" => 12,
+                _ => name.bytes().take_while(u8::is_ascii_digit).count(),
+            };
+            let value = name.get(..len).filter(|value| {
+                !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+            })?;
+            if &template[start + 1..end] == "index" {
+                index = value.parse().ok();
+            }
+            name = &name[len..];
+            template = &template[end + 1..];
+        }
+        (name == template).then_some(index).flatten()
+    }
+}
+
+impl Default for FilenameTemplate {
+    fn default() -> Self {
+        Self("prepared.{index}.rdfb".into())
+    }
+}
+
+impl std::fmt::Display for FilenameTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for FilenameTemplate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.contains(['/', '\\']) {
+            return Err(format!(
+                "filename template `{s}` must not contain a path separator"
+            ));
+        }
+        let mut rest = s;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed `{{` in filename template `{s}`"))?;
+            let placeholder = &rest[start + 1..start + end];
+            if !Self::PLACEHOLDERS.contains(&placeholder) {
+                return Err(format!(
+                    "unknown placeholder `{{{placeholder}}}`, expected one of: {{index}}, {{count}}, {{hash}}"
+                ));
+            }
+            rest = &rest[start + end + 1..];
+        }
+        // The index keeps the names unique and sorted in publishing order:
+        if !s.contains("{index}") {
+            return Err(format!("filename template `{s}` must contain {{index}}"));
+        }
+        // Prepared files are told from input files by their extension:
+        if !s.ends_with(".rdfb") {
+            return Err(format!("filename template `{s}` must end with .rdfb"));
+        }
+        Ok(Self(s.to_string()))
+    }
+}
+
+/// Writes the batches into a directory as `prepared.<INDEX>.rdfb` files, or
+/// as named by a template, and sends each file to be published.
+#[derive(Debug)]
+pub struct FileSink {
+    output_dir: PathBuf,
+    files_tx: Sender<PreparedBatch>,
+    template: FilenameTemplate,
+}
+
+impl FileSink {
+    pub fn new(output_dir: PathBuf, files_tx: Sender<PreparedBatch>) -> Self {
+        Self {
+            output_dir,
+            files_tx,
+            template: FilenameTemplate::default(),
+        }
+    }
+
+    /// Names the files after the template instead of `prepared.<INDEX>.rdfb`.
+    pub fn with_template(mut self, template: FilenameTemplate) -> Self {
+        self.template = template;
+        self
+    }
+}
+
+impl BatchSink for FileSink {
+    fn write(&mut self, index: usize, batch: &RDFBDataset) -> Result<Option<PathBuf>> {
+        let filename = self.output_dir.join(self.template.filename(index, batch));
+
+        let mut file =
+            std::fs::File::create(&filename).context("Failed to create output file for RDFB")?;
+        file.write_all(&batch.data)
+            .context("Failed to write RDFB data")?;
+
+        let prepared = PreparedBatch {
+            path: filename.clone(),
+            statement_count: batch.statement_count,
+            graph: batch.graph.clone(),
+            dataset: batch.dataset.clone(),
+        };
+        if self.files_tx.send(prepared).is_err() {
+            return Ok(None);
+        }
+        Ok(Some(filename))
+    }
+}
+
+/// Keeps a single batch in memory, failing if the input doesn't fit in one
+/// batch, e.g. to write it to the standard output once prepared.
+#[derive(Clone, Debug)]
+pub struct SingleBatchSink {
+    name: PathBuf,
+    batch: Arc<std::sync::Mutex<Option<Vec<u8>>>>,
+}
+
+impl SingleBatchSink {
+    /// Returns a sink naming the batch `name` in progress reports.
+    pub fn new(name: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            batch: Arc::default(),
+        }
+    }
+
+    /// Takes the data of the prepared batch, if any.
+    pub fn take(&self) -> Option<Vec<u8>> {
+        self.batch.lock().unwrap().take()
+    }
+}
+
+impl BatchSink for SingleBatchSink {
+    fn write(&mut self, _index: usize, batch: &RDFBDataset) -> Result<Option<PathBuf>> {
+        let mut prepared = self.batch.lock().unwrap();
+        if prepared.is_some() {
+            bail!("The input doesn't fit in one batch");
+        }
+        *prepared = Some(batch.data.clone());
+        Ok(Some(self.name.clone()))
+    }
 }
 
 #[derive(derive_builder::Builder, Debug)]
 #[builder(pattern = "owned")]
 pub struct Params<I> {
     files: I,
-    files_tx: Sender<(PathBuf, usize)>,
-    output_dir: PathBuf,
+    /// Receives the files written into `output_dir`, unless a sink is set.
+    #[builder(setter(strip_option), default)]
+    files_tx: Option<Sender<PreparedBatch>>,
+    #[builder(setter(strip_option), default)]
+    output_dir: Option<PathBuf>,
+    /// Receives the prepared batches instead of files in `output_dir`.
+    #[builder(setter(strip_option), default)]
+    sink: Option<Box<dyn BatchSink>>,
     #[builder(setter(into, strip_option), default)]
     report: Option<PrepareStatsReport>,
+    /// Route statements of each named graph into separate batches.
+    #[builder(default)]
+    split_by_graph: bool,
+    /// Share a single term dictionary across all batches.
+    #[builder(default)]
+    shared_dictionary: bool,
+    /// End batches before a named graph rather than within it, unless the
+    /// graph doesn't fit in a batch of its own.
+    #[builder(default)]
+    keep_graphs_whole: bool,
+    /// Derive the dataset of each batch from its input or named graph,
+    /// keeping the statements of different datasets in separate batches.
+    #[builder(setter(into), default)]
+    dataset_from: Option<DatasetFrom>,
+    /// Number of workers serializing batches in parallel.
+    #[builder(default = "6")]
+    workers: usize,
+    #[builder(default)]
+    timings: Arc<PrepareTimings>,
+    /// The target size of batches, adapted by the publisher if shared with it.
+    #[builder(default)]
+    batch_sizer: Arc<BatchSizer>,
+    /// Stop reading input after this many statements.
+    #[builder(setter(into), default)]
+    limit_statements: Option<usize>,
+    /// Stop preparing after this many batches.
+    #[builder(setter(into), default)]
+    limit_batches: Option<usize>,
+    /// Add provenance statements about each input file.
+    #[builder(setter(into), default)]
+    provenance: Option<Provenance>,
+    /// JSON-LD context applied to plain `.json` input files.
+    #[builder(setter(into), default)]
+    json_context: Option<String>,
+    /// Sources read after the input files.
+    #[builder(setter(into), default)]
+    sources: Vec<Arc<dyn StatementSource>>,
+    /// Add the statements inferred from the input with these entailment rules.
+    #[builder(setter(into), default)]
+    infer: Option<Profile>,
+    /// Max statement batches read ahead of the prepare workers.
+    #[builder(default = "100")]
+    read_buffer: usize,
+    /// Read N-Triples and N-Quads files mapped into memory, parsing chunks
+    /// of them in parallel.
+    #[builder(default)]
+    mmap: bool,
+    /// Number of threads parsing chunks of each N-Triples or N-Quads file in
+    /// parallel, or 1 to parse the files as they're read.
+    #[builder(default = "1")]
+    parse_threads: usize,
+    /// Number of workers reading input files in parallel, each taking the
+    /// next file once done with one. Only one is used with `infer`.
+    #[builder(default = "1")]
+    read_workers: usize,
+    /// Check that typed literals are valid for their XSD datatypes.
+    #[builder(setter(into), default)]
+    validate_literals: Option<LiteralValidation>,
+    /// Normalize IRIs, leaving out the statements with invalid ones.
+    #[builder(default)]
+    normalize_iris: bool,
+    /// Write the invalid IRIs found while normalizing into this JSON Lines file.
+    #[builder(setter(into), default)]
+    iri_violations: Option<PathBuf>,
+    /// Put literal values and language tags in Unicode Normalization Form C.
+    #[builder(default)]
+    nfc: bool,
+    /// Leave out the statements that fail to parse, rather than failing.
+    #[builder(default)]
+    skip_parse_errors: bool,
+    /// Write the parse errors into this JSON Lines file.
+    #[builder(setter(into), default)]
+    parse_errors: Option<PathBuf>,
+    /// Leave out the statements found in this filter, e.g. already published.
+    #[builder(setter(into), default)]
+    published: Option<Arc<StatementFilter>>,
+    /// Write the input files and statement ranges of each batch into this
+    /// JSON Lines file.
+    #[builder(setter(into), default)]
+    batch_sources: Option<PathBuf>,
+    /// Max estimated bytes of statements buffered by each prepare worker,
+    /// beyond which batches are flushed before reaching their target size.
+    #[builder(setter(into), default)]
+    max_buffer_bytes: Option<usize>,
+    /// Max time statements wait to be packed, beyond which batches are
+    /// flushed before reaching their target size, e.g. for inputs streaming
+    /// statements slowly.
+    #[builder(setter(into), default)]
+    flush_interval: Option<Duration>,
+    /// Max prepared batches waiting to be written.
+    #[builder(default = "10")]
+    write_buffer: usize,
+    /// Names of the files written into `output_dir`.
+    #[builder(default)]
+    filename_template: FilenameTemplate,
+    /// The index of the first batch, to number the batches after those of
+    /// an earlier run.
+    #[builder(default = "1")]
+    first_index: usize,
 }
 
 impl<I> Params<I> {
     pub fn new(
         files: I,
-        files_tx: Sender<(PathBuf, usize)>,
+        files_tx: Sender<PreparedBatch>,
         report: Option<PrepareStatsReport>,
         output_dir: PathBuf,
     ) -> Self {
         Self {
             files,
-            files_tx,
+            files_tx: Some(files_tx),
             report,
-            output_dir,
+            output_dir: Some(output_dir),
+            sink: None,
+            split_by_graph: false,
+            shared_dictionary: false,
+            keep_graphs_whole: false,
+            dataset_from: None,
+            workers: 6,
+            timings: Arc::default(),
+            batch_sizer: Arc::default(),
+            limit_statements: None,
+            limit_batches: None,
+            provenance: None,
+            json_context: None,
+            sources: Vec::new(),
+            infer: None,
+            read_buffer: 100,
+            mmap: false,
+            parse_threads: 1,
+            read_workers: 1,
+            validate_literals: None,
+            normalize_iris: false,
+            iri_violations: None,
+            nfc: false,
+            skip_parse_errors: false,
+            parse_errors: None,
+            published: None,
+            batch_sources: None,
+            max_buffer_bytes: None,
+            flush_interval: None,
+            write_buffer: 10,
+            filename_template: FilenameTemplate::default(),
+            first_index: 1,
         }
     }
 }
 
+/// A stage of the prepare pipeline, run on a thread of its own.
+type Worker = Box<dyn FnOnce() -> Result<()> + Send>;
+
+#[cfg(feature = "cli")]
 pub async fn prepare_datasets<I>(ctx: Context, params: Params<I>) -> Result<()>
 where
     I: Iterator<Item = PathBuf>,
 {
-    let (batch_tx, batch_rx) = crossbeam::channel::bounded(100);
-
     let mut set = JoinSet::new();
+    for worker in workers(ctx, params)? {
+        set.spawn_blocking(worker);
+    }
+    while let Some(handle) = set.join_next().await {
+        handle??;
+    }
+    Ok(())
+}
 
-    set.spawn_blocking({
-        let ctx = ctx.clone();
-        let files: Vec<PathBuf> = params.files.collect();
-        let report = params.report.clone();
-        move || read_worker_loop(ctx, &files, batch_tx, report)
-    });
+/// Prepares the datasets like `prepare_datasets`, blocking the current
+/// thread until done, for applications without a tokio runtime.
+pub fn prepare_datasets_sync<I>(ctx: Context, params: Params<I>) -> Result<()>
+where
+    I: Iterator<Item = PathBuf>,
+{
+    let handles: Vec<_> = workers(ctx, params)?
+        .into_iter()
+        .map(std::thread::spawn)
+        .collect();
+    // Every worker stops once its neighbours stop, so all of them are joined:
+    let mut result = Ok(());
+    for handle in handles {
+        let worker_result = handle
+            .join()
+            .unwrap_or_else(|_| Err(eyre!("prepare worker panicked")));
+        if result.is_ok() {
+            result = worker_result;
+        }
+    }
+    result
+}
 
-    let (dataset_tx, dataset_rx) = crossbeam::channel::bounded(10);
+/// Returns the workers of the pipeline reading, preparing and writing the batches.
+fn workers<I>(ctx: Context, params: Params<I>) -> Result<Vec<Worker>>
+where
+    I: Iterator<Item = PathBuf>,
+{
+    let sink: Box<dyn BatchSink> = match (params.sink, params.output_dir, params.files_tx) {
+        (Some(sink), _, _) => sink,
+        (None, Some(output_dir), Some(files_tx)) => {
+            Box::new(FileSink::new(output_dir, files_tx).with_template(params.filename_template))
+        }
+        _ => bail!("No output directory or sink for the prepared batches"),
+    };
+    let read_buffer = params.read_buffer.max(1);
+    let (batch_tx, batch_rx) = crossbeam::channel::bounded(read_buffer);
 
-    for _ in 0..6 {
-        let batch_rx = batch_rx.clone();
-        let dataset_tx = dataset_tx.clone();
+    let mut workers: Vec<Worker> = Vec::new();
+
+    // The read workers take the next input from a shared queue as they finish one:
+    let (input_tx, input_rx) = crossbeam::channel::unbounded();
+    for file in params.files {
+        input_tx.send((file, None)).ok();
+    }
+    for source in params.sources {
+        input_tx.send((source.name(), Some(source))).ok();
+    }
+    drop(input_tx);
+    let next_index = Arc::new(AtomicCell::new(0));
+    let violations = match params.iri_violations {
+        Some(ref path) => Some(Arc::new(JsonLinesLog::create(path)?)),
+        None => None,
+    };
+    let parse_errors = match params.parse_errors {
+        Some(ref path) => Some(Arc::new(JsonLinesLog::create(path)?)),
+        None => None,
+    };
+    let batch_sources = match params.batch_sources {
+        Some(ref path) => Some(JsonLinesLog::create(path)?),
+        None => None,
+    };
+    let split_by_graph = params.split_by_graph || params.dataset_from == Some(DatasetFrom::Graph);
+    let split_by_source = params
+        .dataset_from
+        .is_some_and(|from| from != DatasetFrom::Graph);
+    // The reasoner must see all the statements to compute their closure, and
+    // graphs are only kept whole if the statements of each file stay together:
+    let read_workers = match params.infer {
+        Some(_) => 1,
+        None if params.keep_graphs_whole => 1,
+        None => params.read_workers.max(1),
+    };
+    for _ in 0..read_workers {
         let ctx = ctx.clone();
-        set.spawn_blocking(|| prepare_worker_loop(ctx, batch_rx, dataset_tx));
+        let input_rx = input_rx.clone();
+        let next_index = next_index.clone();
+        let batch_tx = batch_tx.clone();
+        let report = params.report.clone();
+        let limit_statements = params.limit_statements;
+        let provenance = params.provenance.clone();
+        let json_context = params.json_context.clone();
+        let mmap = params.mmap;
+        let parse_threads = params.parse_threads;
+        let infer = params.infer;
+        let validate_literals = params.validate_literals;
+        let normalize_iris = params.normalize_iris;
+        let nfc = params.nfc;
+        let violations = violations.clone();
+        let skip_parse_errors = params.skip_parse_errors;
+        let published = params.published.clone();
+        let parse_errors = parse_errors.clone();
+        let flush_interval = params.flush_interval;
+        let timings = params.timings.clone();
+        workers.push(Box::new(move || {
+            read_worker_loop(
+                ctx,
+                input_rx,
+                &next_index,
+                batch_tx,
+                report,
+                split_by_graph,
+                split_by_source,
+                limit_statements,
+                provenance,
+                json_context,
+                mmap,
+                parse_threads,
+                infer,
+                validate_literals,
+                normalize_iris,
+                violations,
+                nfc,
+                skip_parse_errors,
+                parse_errors,
+                published,
+                flush_interval,
+                timings,
+            )
+        }));
+    }
+    drop(batch_tx);
+
+    let (dataset_tx, dataset_rx) = crossbeam::channel::bounded(params.write_buffer.max(1));
+
+    let keep_graphs_whole = params.keep_graphs_whole;
+    let flush_interval = params.flush_interval;
+    if params.shared_dictionary
+        || ((keep_graphs_whole || flush_interval.is_some()) && !split_by_source)
+    {
+        // Term IDs are assigned in batch order, so a single worker must prepare them all.
+        // Likewise, a graph is only kept whole if a single worker receives all of it, and
+        // statements streaming in one by one are only packed together by a single worker.
+        let ctx = ctx.clone();
+        let dictionary = params.shared_dictionary.then(TermDictionary::default);
+        let timings = params.timings.clone();
+        let sizer = params.batch_sizer.clone();
+        let max_buffer_bytes = params.max_buffer_bytes;
+        workers.push(Box::new(move || {
+            prepare_worker_loop(
+                ctx,
+                batch_rx,
+                dataset_tx,
+                dictionary,
+                None,
+                timings,
+                sizer,
+                max_buffer_bytes,
+                keep_graphs_whole,
+                flush_interval,
+            )
+        }));
+    } else if split_by_graph || split_by_source {
+        let ctx = ctx.clone();
+        let dataset_from = params.dataset_from;
+        let timings = params.timings.clone();
+        let sizer = params.batch_sizer.clone();
+        let max_buffer_bytes = params.max_buffer_bytes;
+        workers.push(Box::new(move || {
+            route_worker_loop(
+                ctx,
+                batch_rx,
+                dataset_tx,
+                dataset_from,
+                read_buffer,
+                timings,
+                sizer,
+                max_buffer_bytes,
+                keep_graphs_whole,
+                flush_interval,
+            )
+        }));
+    } else {
+        for _ in 0..params.workers.max(1) {
+            let batch_rx = batch_rx.clone();
+            let dataset_tx = dataset_tx.clone();
+            let ctx = ctx.clone();
+            let timings = params.timings.clone();
+            let sizer = params.batch_sizer.clone();
+            let max_buffer_bytes = params.max_buffer_bytes;
+            workers.push(Box::new(move || {
+                prepare_worker_loop(
+                    ctx,
+                    batch_rx,
+                    dataset_tx,
+                    None,
+                    None,
+                    timings,
+                    sizer,
+                    max_buffer_bytes,
+                    false,
+                    flush_interval,
+                )
+            }));
+        }
+        drop(dataset_tx);
     }
-    drop(dataset_tx);
 
-    set.spawn_blocking(|| {
+    workers.push(Box::new(move || {
         write_worker_loop(
             ctx,
             dataset_rx,
-            params.files_tx,
+            sink,
             params.report,
-            params.output_dir,
+            params.first_index,
+            params.limit_batches,
+            batch_sources,
+            params.timings,
         )
-    });
+    }));
 
-    while let Some(handle) = set.join_next().await {
-        handle??;
-    }
-    Ok(())
+    Ok(workers)
 }
 
+/// A statement read, with its index among all the statements read and, unless
+/// added by this tool, its number among the statements of its input,
+/// starting at 1.
+type ReadStatement = (usize, Option<usize>, oxrdf::Quad);
+
 struct StatementBatch {
-    quads: Vec<(usize, oxrdf::Quad)>,
+    /// The named graph shared by all quads, if the reader splits by graph.
+    graph: Option<String>,
+    /// The input the statements were read from.
+    source: Option<Arc<Path>>,
+    quads: Vec<ReadStatement>,
 }
 
+/// A prepared RDF/Borsh batch, before it is written.
 #[derive(Default)]
-struct RDFBDataset {
-    data: Vec<u8>,
-    statement_count: usize,
-    skipped_statements: usize,
+pub struct RDFBDataset {
+    /// The RDF/Borsh encoding of the statements.
+    pub data: Vec<u8>,
+    pub statement_count: usize,
+    /// The number of statements skipped for being too large to publish.
+    pub skipped_statements: usize,
+    /// The named graph of the statements, if output is split by graph.
+    pub graph: Option<String>,
+    /// The dataset derived from the input of the statements, if any.
+    pub dataset: Option<String>,
+    /// The ranges of statements of the inputs making up the batch.
+    pub sources: Vec<SourceRange>,
+}
+
+/// Consecutive statements of an input, by their numbers among its
+/// statements, starting at 1, which are also their lines in an N-Triples
+/// file without comments or blank lines.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct SourceRange {
+    pub file: PathBuf,
+    pub first: usize,
+    pub last: usize,
 }
 
+/// The inputs of a batch, as written into the batch sources file.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct BatchSources {
+    pub batch: PathBuf,
+    pub statement_count: usize,
+    /// The SHA-256 digest of the batch, in hexadecimal.
+    pub sha256: String,
+    pub sources: Vec<SourceRange>,
+}
+
+/// Reads the SHA-256 digests of the batches listed in a batch sources file,
+/// keyed by the canonical paths of the batches.
+pub fn read_batch_checksums(path: &Path) -> Result<HashMap<PathBuf, String>> {
+    let file = File::open(path)?;
+    let mut checksums = HashMap::new();
+    for line in std::io::BufRead::lines(BufReader::new(file)) {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let sources: BatchSources = serde_json::from_str(&line)?;
+        let batch = sources.batch.canonicalize().unwrap_or(sources.batch);
+        checksums.insert(batch, sources.sha256);
+    }
+    Ok(checksums)
+}
+
+#[allow(clippy::too_many_arguments)]
 fn read_worker_loop(
     ctx: Context,
-    files: &[PathBuf],
+    inputs: Receiver<(PathBuf, Option<Arc<dyn StatementSource>>)>,
+    next_index: &AtomicCell<usize>,
     batch_tx: Sender<StatementBatch>,
     report: Option<PrepareStatsReport>,
+    split_by_graph: bool,
+    split_by_source: bool,
+    limit_statements: Option<usize>,
+    provenance: Option<Provenance>,
+    json_context: Option<String>,
+    mmap: bool,
+    parse_threads: usize,
+    infer: Option<Profile>,
+    validate_literals: Option<LiteralValidation>,
+    normalize_iris: bool,
+    violations: Option<Arc<JsonLinesLog>>,
+    nfc: bool,
+    skip_parse_errors: bool,
+    parse_errors: Option<Arc<JsonLinesLog>>,
+    published: Option<Arc<StatementFilter>>,
+    flush_interval: Option<Duration>,
+    timings: Arc<PrepareTimings>,
 ) -> Result<()> {
-    struct CountingBufReader<R> {
-        inner: BufReader<R>,
-        count: Rc<RefCell<usize>>,
-    }
+    let limit_reached =
+        |statement_index: usize| limit_statements.is_some_and(|limit| statement_index >= limit);
+    let mut reasoner = infer.map(Reasoner::new);
 
-    impl<R> CountingBufReader<R> {
-        fn new(inner: BufReader<R>, count: Rc<RefCell<usize>>) -> Self {
-            Self { inner, count }
-        }
-    }
-
-    impl<R: std::io::Read> std::io::Read for CountingBufReader<R> {
-        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
-            let count = self.inner.read(buf)?;
-            *self.count.borrow_mut() += count;
-            Ok(count)
+    for (file, source) in inputs {
+        if limit_reached(next_index.load()) {
+            break;
         }
-    }
-
-    let batch_size = 100_000;
-    let mut statement_index: usize = 0;
-
-    for file in files {
-        let format = file
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .and_then(oxrdfio::RdfFormat::from_extension)
-            .ok_or_eyre("Unknown file format")?;
-        let reader = File::open(file).context("Failed to open input file")?;
-        let reader = BufReader::with_capacity(1 << 20, reader);
         let count = Rc::new(RefCell::new(0));
-        let reader = CountingBufReader::new(reader, count.clone());
-        let mut reader = oxrdfio::RdfParser::from_format(format).for_reader(reader);
+        let mut reader = match source {
+            Some(ref source) => source.read_quads()?,
+            None => open_input(
+                &file,
+                count.clone(),
+                json_context.as_deref(),
+                mmap,
+                parse_threads,
+            )?,
+        };
+        // whether the provenance statements of the file have been added
+        let mut described = false;
+        let input: Arc<Path> = Arc::from(file.as_path());
+        // the number of statements read from the file, including left out ones
+        let mut number = 0;
+        // Parsers of other formats may not go on after an error:
+        let recovers =
+            source.is_none() && input_format(&file).is_some_and(syntax::recovers_from_errors);
+        // whether the rest of the file is skipped after a parse error
+        let mut stop_reading = false;
 
         while !ctx.is_cancelled() {
-            let mut quads = Vec::with_capacity(batch_size);
+            ctx.block_while_paused();
+            let start = Instant::now();
+            let mut quads = Vec::with_capacity(READ_BATCH_SIZE);
+            let mut skipped_statements = 0;
+            let mut parse_error_count = 0;
+            let mut published_statements = 0;
 
             let finished = loop {
+                if limit_reached(next_index.load()) {
+                    break true;
+                }
                 let Some(quad) = reader.next() else {
                     break true;
                 };
-                let quad = quad?;
-                quads.push((statement_index, quad));
-                statement_index += 1;
-                if quads.len() >= batch_size {
+                number += 1;
+                let mut quad = match quad {
+                    Ok(quad) => quad,
+                    Err(err) => {
+                        let Some(error) = err.downcast_ref::<SyntaxError>() else {
+                            return Err(err);
+                        };
+                        if let Some(ref parse_errors) = parse_errors {
+                            parse_errors.record(&ParseErrorRecord {
+                                file: file.clone(),
+                                line: error.line,
+                                column: error.column,
+                                message: error.message.clone(),
+                            })?;
+                        }
+                        if !skip_parse_errors {
+                            if let Some(ref parse_errors) = parse_errors {
+                                parse_errors.flush()?;
+                            }
+                            return Err(err.wrap_err(format!("Failed to parse {}", file.display())));
+                        }
+                        tracing::warn!(?file, %error, "skipped statement failing to parse");
+                        parse_error_count += 1;
+                        if !recovers {
+                            tracing::warn!(
+                                ?file,
+                                "skipped the rest of the file after a parse error"
+                            );
+                            stop_reading = true;
+                            break true;
+                        }
+                        continue;
+                    }
+                };
+                // Another reader may have reached the limit since it was checked:
+                let statement_index = next_index.fetch_add(1);
+                if limit_reached(statement_index) {
+                    break true;
+                }
+                if normalize_iris {
+                    if let Err((iri, violation)) = iri::normalize_quad(&mut quad) {
+                        tracing::warn!(?file, index = statement_index, %iri, %violation, "invalid IRI");
+                        if let Some(ref violations) = violations {
+                            violations.record(&IriViolationRecord {
+                                file: file.clone(),
+                                statement: statement_index,
+                                iri,
+                                violation,
+                            })?;
+                        }
+                        skipped_statements += 1;
+                        continue;
+                    }
+                }
+                if nfc {
+                    literal::normalize_nfc(&mut quad);
+                }
+                if let Some(validation) = validate_literals {
+                    if let Some(literal) = literal::invalid_literal(quad.as_ref()) {
+                        if validation == LiteralValidation::Fail {
+                            bail!(
+                                "Invalid literal {literal} in statement {statement_index} of {}",
+                                file.display()
+                            );
+                        }
+                        tracing::warn!(?file, index = statement_index, %literal, "invalid literal");
+                        if validation == LiteralValidation::Skip {
+                            skipped_statements += 1;
+                            continue;
+                        }
+                    }
+                }
+                if published
+                    .as_ref()
+                    .is_some_and(|published| published.contains(&quad))
+                {
+                    published_statements += 1;
+                    continue;
+                }
+                quads.push((statement_index, Some(number), quad));
+                if quads.len() >= READ_BATCH_SIZE {
+                    break false;
+                }
+                // The next statement of a stream may be long in coming, so pass this one on:
+                if flush_interval.is_some() {
                     break false;
                 }
             };
 
+            if finished && !described {
+                if let (Some(provenance), None) = (&provenance, &source) {
+                    for quad in provenance.file_quads(&file)? {
+                        quads.push((next_index.fetch_add(1), None, quad));
+                    }
+                }
+                described = true;
+            }
+
+            if let Some(ref mut reasoner) = reasoner {
+                let inferred: Vec<_> = quads
+                    .iter()
+                    .flat_map(|(_, _, quad)| reasoner.insert(quad))
+                    .collect();
+                for quad in inferred {
+                    quads.push((next_index.fetch_add(1), None, quad));
+                }
+            }
+
+            timings.read.add_busy(start, quads.len());
+
             if finished && quads.is_empty() && *count.borrow() == 0 {
                 break;
             }
 
+            // The bytes read are reset even without a report, ending the loop above:
+            let bytes = std::mem::take(&mut *count.borrow_mut());
             if let Some(ref report) = report {
-                let mut bytes = count.borrow_mut();
-                report
-                    .tx
-                    .send(crate::ui::Event::Reader(crate::ui::ReaderProgress {
-                        filename: PathBuf::from(file),
-                        bytes: *bytes,
-                        statement_count: quads.len(),
-                        finished,
-                    }))
-                    .ok();
-                *bytes = 0;
+                report.send(crate::ui::Event::Reader(crate::ui::ReaderProgress {
+                    filename: file.clone(),
+                    bytes,
+                    statement_count: quads.len(),
+                    skipped_statements,
+                    parse_errors: parse_error_count,
+                    published_statements,
+                    finished,
+                }));
             }
 
-            if batch_tx.send(StatementBatch { quads }).is_err() {
+            let start = Instant::now();
+            if split_by_graph {
+                let mut graphs: HashMap<Option<String>, Vec<ReadStatement>> = HashMap::new();
+                for (index, number, quad) in quads {
+                    let graph = match quad.graph_name {
+                        oxrdf::GraphName::DefaultGraph => None,
+                        ref graph => Some(graph_key(graph)),
+                    };
+                    graphs.entry(graph).or_default().push((index, number, quad));
+                }
+                for (graph, quads) in graphs {
+                    let source = Some(input.clone());
+                    if batch_tx
+                        .send(StatementBatch {
+                            graph,
+                            source,
+                            quads,
+                        })
+                        .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+            } else if batch_tx
+                .send(StatementBatch {
+                    graph: None,
+                    source: Some(input.clone()),
+                    quads,
+                })
+                .is_err()
+            {
                 return Ok(());
             }
+            timings.read.add_stall(start);
+
+            // The statements read before the error are passed on, but no more:
+            if stop_reading {
+                break;
+            }
+        }
+        // An empty batch ends the input, so that its batches can be finished:
+        if split_by_source
+            && batch_tx
+                .send(StatementBatch {
+                    graph: None,
+                    source: Some(input),
+                    quads: Vec::new(),
+                })
+                .is_err()
+        {
+            return Ok(());
         }
     }
+    if let Some(violations) = violations {
+        violations.flush()?;
+    }
+    if let Some(parse_errors) = parse_errors {
+        parse_errors.flush()?;
+    }
     Ok(())
 }
 
+struct CountingBufReader<R> {
+    inner: BufReader<R>,
+    count: Rc<RefCell<usize>>,
+}
+
+impl<R> CountingBufReader<R> {
+    fn new(inner: BufReader<R>, count: Rc<RefCell<usize>>) -> Self {
+        Self { inner, count }
+    }
+}
+
+impl<R: std::io::Read> std::io::Read for CountingBufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        *self.count.borrow_mut() += count;
+        Ok(count)
+    }
+}
+
+/// Reads the statements of an input file in any of the supported formats.
+pub fn read_input(file: &Path) -> Result<Box<dyn Iterator<Item = Result<oxrdf::Quad>>>> {
+    open_input(file, Rc::default(), None, false, 1)
+}
+
+/// Returns whether the input is a stream of unknown size, a named pipe or a
+/// character device, rather than a regular file.
+#[cfg(unix)]
+pub fn is_stream(file: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt as _;
+
+    std::fs::metadata(file).is_ok_and(|metadata| {
+        let file_type = metadata.file_type();
+        file_type.is_fifo() || file_type.is_char_device()
+    })
+}
+
+/// Returns whether the input is a stream of unknown size, a named pipe or a
+/// character device, rather than a regular file.
+#[cfg(not(unix))]
+pub fn is_stream(_file: &Path) -> bool {
+    false
+}
+
+/// Opens an input file as a stream of statements, adding the bytes read from
+/// it to `count`.
+fn open_input(
+    file: &Path,
+    count: Rc<RefCell<usize>>,
+    json_context: Option<&str>,
+    mmap: bool,
+    parse_threads: usize,
+) -> Result<Box<dyn Iterator<Item = Result<oxrdf::Quad>>>> {
+    #[cfg(feature = "parquet")]
+    if crate::triple_table::is_triple_table(file) {
+        // Count the whole file as read once all its statements are read:
+        let size = std::fs::metadata(file)?.len() as usize;
+        let mut counted = false;
+        let quads = crate::triple_table::read_quads(file)?;
+        return Ok(Box::new(quads.chain(std::iter::from_fn(move || {
+            if !counted {
+                *count.borrow_mut() += size;
+                counted = true;
+            }
+            None
+        }))));
+    }
+
+    let Some(format) = input_format(file) else {
+        // Formats that aren't supported may be read by a plugin:
+        return match crate::plugin::find_reader(file) {
+            Some(program) => crate::plugin::read_quads(&program, file, count),
+            None => match file.extension().and_then(std::ffi::OsStr::to_str) {
+                Some(extension) => bail!(
+                    "Unknown file format, and no {}{extension} reader plugin in the PATH",
+                    crate::plugin::READER_PREFIX
+                ),
+                None => bail!("Unknown file format"),
+            },
+        };
+    };
+    let line_oriented = crate::chunked::is_line_oriented(format);
+    // Streams can't be mapped, so they're read as they come:
+    if mmap && line_oriented && !is_stream(file) {
+        return crate::mmap::read_quads(file, format, parse_threads, count);
+    }
+    let reader = File::open(file).context("Failed to open input file")?;
+    let reader = BufReader::with_capacity(1 << 20, reader);
+    if parse_threads > 1 && line_oriented {
+        return Ok(crate::chunked::read_quads(
+            reader,
+            format,
+            parse_threads,
+            count,
+        ));
+    }
+    let reader = CountingBufReader::new(reader, count);
+    let reader: Box<dyn Read> = match json_context {
+        // Plain JSON becomes JSON-LD by wrapping it as the graph of a document with the context:
+        Some(json_context) if file.extension().is_some_and(|ext| ext == "json") => {
+            let prefix = format!("{{\"@context\": {json_context}, \"@graph\": ");
+            Box::new(Cursor::new(prefix).chain(reader).chain(&b"}"[..]))
+        }
+        _ => Box::new(reader),
+    };
+    let quads = oxrdfio::RdfParser::from_format(format).for_reader(reader);
+    Ok(Box::new(quads.map(|quad| {
+        quad.map_err(|err| syntax::parse_error(err, 0))
+    })))
+}
+
+/// Returns the RDF format of an input file, given its extension.
+fn input_format(file: &Path) -> Option<oxrdfio::RdfFormat> {
+    file.extension()
+        .and_then(std::ffi::OsStr::to_str)
+        .and_then(oxrdfio::RdfFormat::from_extension)
+}
+
+/// Returns the IRI (or blank node label) identifying a named graph.
+fn graph_key(graph: &oxrdf::GraphName) -> String {
+    match graph {
+        oxrdf::GraphName::NamedNode(node) => node.as_str().to_string(),
+        oxrdf::GraphName::BlankNode(node) => node.to_string(),
+        oxrdf::GraphName::DefaultGraph => String::new(),
+    }
+}
+
+/// Dispatches batches to a dedicated prepare worker per named graph, and per
+/// input if the dataset is derived from it, so that every prepared dataset
+/// contains statements of a single graph and dataset only.
+#[allow(clippy::too_many_arguments)]
+fn route_worker_loop(
+    ctx: Context,
+    batch_rx: Receiver<StatementBatch>,
+    dataset_tx: Sender<RDFBDataset>,
+    dataset_from: Option<DatasetFrom>,
+    buffer: usize,
+    timings: Arc<PrepareTimings>,
+    sizer: Arc<BatchSizer>,
+    max_buffer_bytes: Option<usize>,
+    keep_graphs_whole: bool,
+    flush_interval: Option<Duration>,
+) -> Result<()> {
+    std::thread::scope(|scope| {
+        type Route = (Option<String>, Option<Arc<Path>>);
+        let mut routes: HashMap<Route, Sender<StatementBatch>> = HashMap::new();
+        let mut workers = Vec::new();
+        let by_source = dataset_from.is_some_and(|from| from != DatasetFrom::Graph);
+
+        for batch in batch_rx.iter() {
+            if ctx.is_cancelled() {
+                break;
+            }
+            let source = batch.source.clone().filter(|_| by_source);
+            if source.is_some() && batch.quads.is_empty() {
+                // The input ended, so its workers finish their last batches:
+                routes.retain(|(_, route_source), _| *route_source != source);
+                continue;
+            }
+            let dataset = source
+                .as_deref()
+                .zip(dataset_from)
+                .and_then(|(source, from)| from.input_dataset(source));
+            let route = routes
+                .entry((batch.graph.clone(), source))
+                .or_insert_with(|| {
+                    let (route_tx, route_rx) = crossbeam::channel::bounded(buffer);
+                    let ctx = ctx.clone();
+                    let dataset_tx = dataset_tx.clone();
+                    let timings = timings.clone();
+                    let sizer = sizer.clone();
+                    workers.push(scope.spawn(move || {
+                        prepare_worker_loop(
+                            ctx,
+                            route_rx,
+                            dataset_tx,
+                            None,
+                            dataset,
+                            timings,
+                            sizer,
+                            max_buffer_bytes,
+                            keep_graphs_whole,
+                            flush_interval,
+                        )
+                    }));
+                    route_tx
+                });
+            if route.send(batch).is_err() {
+                break;
+            }
+        }
+        drop(routes);
+
+        for worker in workers {
+            worker.join().expect("prepare worker panicked")?;
+        }
+        Ok(())
+    })
+}
+
+/// Packs the received statements into batches of the given dataset, if any.
+#[allow(clippy::too_many_arguments)]
 fn prepare_worker_loop(
     ctx: Context,
     batch_rx: Receiver<StatementBatch>,
     dataset_tx: Sender<RDFBDataset>,
+    dictionary: Option<TermDictionary>,
+    dataset: Option<String>,
+    timings: Arc<PrepareTimings>,
+    sizer: Arc<BatchSizer>,
+    max_buffer_bytes: Option<usize>,
+    keep_graphs_whole: bool,
+    flush_interval: Option<Duration>,
 ) -> Result<()> {
+    let mut packer = BatchPacker::new(dictionary, sizer, max_buffer_bytes);
+    packer.keep_graphs_whole = keep_graphs_whole;
+    packer.flush_interval = flush_interval;
+    let receive = |deadline: Option<Instant>| match deadline {
+        Some(deadline) => batch_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+        None => batch_rx.recv().map_err(RecvTimeoutError::from),
+    };
+    while let Some(mut prepared) = packer.next_batch(&ctx, &timings, receive) {
+        prepared.dataset = dataset.clone();
+        if dataset_tx.send(prepared).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Packs statements into RDF/Borsh batches on the current thread.
+///
+/// Unlike `prepare_datasets`, this needs neither a runtime nor threads, so it
+/// also works in WebAssembly, e.g. to prepare batches in a browser. With a
+/// shared term dictionary, the batches must be published in order.
+pub fn pack_statements<I>(
+    statements: I,
+    sizer: Arc<BatchSizer>,
+    shared_dictionary: bool,
+) -> impl Iterator<Item = RDFBDataset>
+where
+    I: IntoIterator<Item = oxrdf::Quad>,
+{
+    let (ctx, _) = crate::context::new_cancel_context();
+    let timings = PrepareTimings::default();
+    let mut statements = statements
+        .into_iter()
+        .enumerate()
+        .map(|(index, quad)| (index, None, quad));
+    let mut packer = BatchPacker::new(shared_dictionary.then(TermDictionary::default), sizer, None);
+    std::iter::from_fn(move || {
+        packer.next_batch(&ctx, &timings, |_| {
+            let quads: Vec<_> = statements.by_ref().take(READ_BATCH_SIZE).collect();
+            (!quads.is_empty())
+                .then_some(StatementBatch {
+                    graph: None,
+                    source: None,
+                    quads,
+                })
+                .ok_or(RecvTimeoutError::Disconnected)
+        })
+    })
+}
+
+/// A buffered statement, with its index, its estimated size, and its input
+/// and number among the statements of the input, if read from one.
+type BufferedStatement = (usize, usize, Option<(Arc<Path>, usize)>, Box<dyn Statement>);
+
+/// Searches for the number of buffered statements whose serialization comes
+/// closest to the target size of a batch.
+struct BatchPacker {
     // Buffer for storing statements that need to be retried
-    let mut statement_buffer: VecDeque<(usize, Box<dyn Statement>)> = VecDeque::new();
+    statement_buffer: VecDeque<BufferedStatement>,
+    // buffered_bytes is the estimated memory taken by the buffered statements
+    buffered_bytes: usize,
+    // max_buffer_bytes caps buffered_bytes, flushing smaller batches once reached
+    max_buffer_bytes: Option<usize>,
     // write_count is how many we're trying to serialize each iteration
-    let mut write_count: usize = 1;
+    write_count: usize,
     // write_count_delta controls how we update write_count if the resulting data is either too
     // large or too small
-    let mut write_count_delta: usize = 1;
+    write_count_delta: usize,
     // lowest_overflow is the lowest known write_count where result data is too large
-    let mut lowest_overflow: usize = usize::MAX;
+    lowest_overflow: usize,
     // have_more states whether the producer has more items
-    let mut have_more = true;
+    have_more: bool,
     // best_ratio contains the best known (non-overflowing) size ratio for each iteration.
     // It's used to quit early in the case where adding one more statement overflows but current
     // write_count doesn't meet ACCEPTABLE_RATIO.
-    let mut best_ratio: f64 = 0.0;
-
-    let mut skipped_statements: usize = 0;
+    best_ratio: f64,
+    // max_size is the target size of the current batch, fixed while searching for its write_count
+    max_size: usize,
+    skipped_statements: usize,
+    // graph is the named graph of the buffered statements when splitting by graph
+    graph: Option<String>,
+    // keep_graphs_whole ends batches before the named graph of their last statement if it
+    // continues after them
+    keep_graphs_whole: bool,
+    // flush_interval bounds the time from buffering a statement to packing it, after which
+    // flush_deadline has passed and the buffered statements are flushed
+    flush_interval: Option<Duration>,
+    flush_deadline: Option<Instant>,
+    flushing: bool,
+    dictionary: Option<TermDictionary>,
+    sizer: Arc<BatchSizer>,
+}
 
-    while !ctx.is_cancelled() {
-        while have_more && (statement_buffer.len() < write_count) {
-            let Ok(batch) = batch_rx.recv() else {
-                have_more = false;
-                break;
-            };
-            statement_buffer.extend(batch.quads.into_iter().map(|(i, stmt)| (i, stmt.into())));
+impl BatchPacker {
+    fn new(
+        dictionary: Option<TermDictionary>,
+        sizer: Arc<BatchSizer>,
+        max_buffer_bytes: Option<usize>,
+    ) -> Self {
+        Self {
+            statement_buffer: VecDeque::new(),
+            buffered_bytes: 0,
+            max_buffer_bytes,
+            write_count: 1,
+            write_count_delta: 1,
+            lowest_overflow: usize::MAX,
+            have_more: true,
+            best_ratio: 0.0,
+            max_size: sizer.target(),
+            skipped_statements: 0,
+            graph: None,
+            keep_graphs_whole: false,
+            flush_interval: None,
+            flush_deadline: None,
+            flushing: false,
+            dictionary,
+            sizer,
         }
+    }
 
-        if statement_buffer.is_empty() {
-            break;
-        }
+    /// Returns the next batch, calling `receive` for more statements while
+    /// the buffered ones may not fill it, or `None` once all are packed.
+    ///
+    /// `receive` waits for statements until the given deadline, if any.
+    fn next_batch(
+        &mut self,
+        ctx: &Context,
+        timings: &PrepareTimings,
+        mut receive: impl FnMut(Option<Instant>) -> Result<StatementBatch, RecvTimeoutError>,
+    ) -> Option<RDFBDataset> {
+        while !ctx.is_cancelled() {
+            // When keeping graphs whole, the statement after the batch tells if it ends a graph:
+            while self.have_more
+                && !self.flushing
+                && (self.statement_buffer.len()
+                    < self.write_count + usize::from(self.keep_graphs_whole))
+                && !self.buffer_full()
+            {
+                let start = Instant::now();
+                let received = receive(self.flush_deadline);
+                timings.prepare.add_stall(start);
+                let batch = match received {
+                    Ok(batch) => batch,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.flushing = true;
+                        break;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => {
+                        self.have_more = false;
+                        break;
+                    }
+                };
+                if self.statement_buffer.is_empty() && !batch.quads.is_empty() {
+                    self.flush_deadline = self
+                        .flush_interval
+                        .map(|interval| Instant::now() + interval);
+                }
+                self.graph = batch.graph;
+                for (index, number, quad) in batch.quads {
+                    let size = statement_size(&quad);
+                    let origin = batch.source.clone().zip(number);
+                    self.buffered_bytes += size;
+                    self.statement_buffer
+                        .push_back((index, size, origin, quad.into()));
+                }
+            }
 
-        let try_write_count = write_count.min(statement_buffer.len());
-        let ser_result =
-            serialize_statements(statement_buffer.range(..try_write_count).map(|(_, x)| x));
+            if self.statement_buffer.is_empty() {
+                return None;
+            }
 
-        let too_large = match ser_result {
-            Ok(ref data) => data.len() > MAX_FILE_SIZE,
-            Err(ref err) => err.kind() == std::io::ErrorKind::Other,
-        };
+            let try_write_count = self.write_count.min(self.statement_buffer.len());
+            let ser_result = self.serialize(try_write_count, timings);
 
-        if too_large {
-            // current size is larger than max
+            let too_large = match ser_result {
+                // A single statement larger than the target still makes a batch of its own:
+                Ok((ref data, _)) if try_write_count == 1 => data.len() > self.sizer.max_size(),
+                Ok((ref data, _)) => data.len() > self.max_size,
+                Err(ref err) => err.kind() == std::io::ErrorKind::Other,
+            };
 
-            if write_count == 1 {
-                if let Some((index, _)) = statement_buffer.pop_front() {
-                    tracing::warn!(?index, "statement is too large to be published even alone");
-                    skipped_statements += 1;
-                    continue;
+            if too_large {
+                // current size is larger than max
+
+                if self.write_count == 1 {
+                    if let Some((index, size, _, _)) = self.statement_buffer.pop_front() {
+                        self.buffered_bytes -= size;
+                        tracing::warn!(?index, "statement is too large to be published even alone");
+                        self.skipped_statements += 1;
+                        continue;
+                    }
                 }
-            }
-            lowest_overflow = lowest_overflow.min(write_count);
+                self.lowest_overflow = self.lowest_overflow.min(self.write_count);
+
+                // backtrack
+                self.write_count -= self.write_count_delta;
 
-            // backtrack
-            write_count -= write_count_delta;
+                if self.write_count_delta == 1 {
+                    // this helps get unstuck
+                    self.write_count = self.lowest_overflow - 2;
+                } else {
+                    // the last delta was too large so pull back
+                    self.write_count_delta >>= 1;
+                }
+
+                self.write_count_delta = self.write_count_delta.max(1);
 
-            if write_count_delta == 1 {
-                // this helps get unstuck
-                write_count = lowest_overflow - 2;
-            } else {
-                // the last delta was too large so pull back
-                write_count_delta >>= 1;
+                self.write_count += self.write_count_delta;
+                continue;
             }
 
-            write_count_delta = write_count_delta.max(1);
+            let (mut data, mut new_terms) = match ser_result {
+                Ok(result) => result,
+                Err(err) => panic!("{err}"), // TODO
+            };
 
-            write_count += write_count_delta;
-            continue;
-        }
+            let ratio = data.len() as f64 / self.max_size as f64;
+            let max_statements = self.sizer.max_statements().unwrap_or(usize::MAX);
 
-        let data = match ser_result {
-            Ok(data) => data,
-            Err(err) => panic!("{err}"), // TODO
-        };
+            if (ratio < ACCEPTABLE_RATIO)
+                && (ratio != self.best_ratio)
+                && (try_write_count < max_statements)
+                && (self.statement_buffer.len() > self.write_count
+                    || (self.have_more && !self.flushing && !self.buffer_full()))
+            {
+                // we're under the target
+                // ... and the best ratio is something else (anti-loop measure)
+                // ... and the batch may have more statements
+                // ... and there are more statements that could be included without exceeding
+                //     the buffer's memory cap
 
-        let ratio = data.len() as f64 / MAX_FILE_SIZE as f64;
+                self.best_ratio = self.best_ratio.max(ratio);
 
-        if (ratio < ACCEPTABLE_RATIO)
-            && (ratio != best_ratio)
-            && (statement_buffer.len() > write_count || have_more)
-        {
-            // we're under the target
-            // ... and the best ratio is something else (anti-loop measure)
-            // ... and there are more statements that could be included
+                self.write_count_delta <<= 1;
 
-            best_ratio = best_ratio.max(ratio);
+                let diff = self.lowest_overflow - self.write_count;
+                while self.write_count_delta >= diff {
+                    self.write_count_delta >>= 1;
+                }
 
-            write_count_delta <<= 1;
+                self.write_count_delta = self.write_count_delta.max(1);
 
-            let diff = lowest_overflow - write_count;
-            while write_count_delta >= diff {
-                write_count_delta >>= 1;
-            }
+                self.write_count = (self.write_count + self.write_count_delta).min(max_statements);
 
-            write_count_delta = write_count_delta.max(1);
+                if (self.write_count + 1) >= self.lowest_overflow {
+                    // It is possible that the final serialization of a dataset with *more* statements
+                    // ends up being *smaller* after compression.
+                    // If we end up here it means that the best_ratio was somewhere on N-1, N-2, ...
+                    // Just accept current ratio and on next iteration this will write the file.
+                } else {
+                    continue;
+                }
+            }
 
-            write_count += write_count_delta;
+            let mut try_write_count = try_write_count;
+            if let Some(count) = self.graph_start(try_write_count) {
+                // Fewer statements than serialized fit in the batch too:
+                if let Ok(result) = self.serialize(count, timings) {
+                    (data, new_terms) = result;
+                    try_write_count = count;
+                }
+            }
 
-            if (write_count + 1) >= lowest_overflow {
-                // It is possible that the final serialization of a dataset with *more* statements
-                // ends up being *smaller* after compression.
-                // If we end up here it means that the best_ratio was somewhere on N-1, N-2, ...
-                // Just accept current ratio and on next iteration this will write the file.
-            } else {
-                continue;
+            let mut sources: Vec<SourceRange> = Vec::new();
+            for (_, _, origin, _) in self.statement_buffer.range(..try_write_count) {
+                let Some((ref file, number)) = origin else {
+                    continue;
+                };
+                match sources.last_mut() {
+                    Some(range) if range.file == **file && range.last + 1 == *number => {
+                        range.last = *number;
+                    }
+                    _ => sources.push(SourceRange {
+                        file: file.to_path_buf(),
+                        first: *number,
+                        last: *number,
+                    }),
+                }
             }
-        }
 
-        if dataset_tx
-            .send(RDFBDataset {
+            let dataset = RDFBDataset {
                 data,
                 statement_count: try_write_count,
-                skipped_statements,
-            })
-            .is_err()
-        {
-            return Ok(());
+                skipped_statements: self.skipped_statements,
+                graph: self.graph.clone(),
+                dataset: None,
+                sources,
+            };
+
+            if let Some(ref mut dictionary) = self.dictionary {
+                dictionary.extend(new_terms);
+            }
+            timings.prepare.add_statements(try_write_count);
+            timings.prepared_batches.fetch_add(1);
+
+            let drained_bytes: usize = self
+                .statement_buffer
+                .drain(..try_write_count)
+                .map(|(_, size, _, _)| size)
+                .sum();
+            self.buffered_bytes -= drained_bytes;
+
+            // Statements left over were buffered before the deadline, so flush them next too:
+            if self.statement_buffer.is_empty() {
+                self.flush_deadline = None;
+                self.flushing = false;
+            }
+
+            // reset these:
+            self.write_count = 1;
+            self.best_ratio = 0.0;
+            self.lowest_overflow = usize::MAX;
+            self.skipped_statements = 0;
+            self.max_size = self.sizer.target();
+
+            return Some(dataset);
         }
+        None
+    }
 
-        statement_buffer.drain(..try_write_count);
+    /// Serializes the first `count` buffered statements, with the terms they
+    /// add to the shared dictionary, if any.
+    fn serialize(
+        &self,
+        count: usize,
+        timings: &PrepareTimings,
+    ) -> std::io::Result<(Vec<u8>, Vec<BorshTerm>)> {
+        let start = Instant::now();
+        let statements = self.statement_buffer.range(..count).map(|(_, _, _, x)| x);
+        let result = match self.dictionary {
+            Some(ref dictionary) => dictionary.serialize_statements(statements),
+            None => serialize_statements(statements).map(|data| (data, Vec::new())),
+        };
+        timings.prepare.add_busy(start, 0);
+        timings.serialize_attempts.fetch_add(1);
+        result
+    }
 
-        // reset these:
-        write_count = 1;
-        best_ratio = 0.0;
-        lowest_overflow = usize::MAX;
-        skipped_statements = 0;
+    /// Returns the number of statements before the named graph of the last of
+    /// the first `count` buffered statements, if keeping graphs whole and the
+    /// graph continues after them but starts after the first statement.
+    fn graph_start(&self, count: usize) -> Option<usize> {
+        if !self.keep_graphs_whole {
+            return None;
+        }
+        let graph = |index: usize| {
+            let (_, _, _, statement) = &self.statement_buffer[index];
+            statement.context().map(|term| term.as_str())
+        };
+        let next = self.statement_buffer.get(count).map(|_| graph(count));
+        let last = graph(count - 1);
+        if next != Some(last.clone()) {
+            return None;
+        }
+        (1..count).rev().find(|&index| graph(index - 1) != last)
     }
 
-    Ok(())
+    /// Returns whether the buffered statements take up the memory allowed.
+    fn buffer_full(&self) -> bool {
+        self.max_buffer_bytes
+            .is_some_and(|max| self.buffered_bytes >= max)
+    }
+}
+
+/// Returns the estimated memory taken by the statement while buffered.
+fn statement_size(quad: &oxrdf::Quad) -> usize {
+    let graph = match quad.graph_name.as_ref() {
+        oxrdf::GraphNameRef::NamedNode(node) => node.as_str().len(),
+        oxrdf::GraphNameRef::BlankNode(node) => node.as_str().len(),
+        oxrdf::GraphNameRef::DefaultGraph => 0,
+    };
+    STATEMENT_OVERHEAD
+        + term_size(quad.subject.as_ref().into())
+        + quad.predicate.as_str().len()
+        + term_size(quad.object.as_ref())
+        + graph
 }
 
+fn term_size(term: oxrdf::TermRef<'_>) -> usize {
+    match term {
+        oxrdf::TermRef::NamedNode(node) => node.as_str().len(),
+        oxrdf::TermRef::BlankNode(node) => node.as_str().len(),
+        oxrdf::TermRef::Literal(literal) => {
+            literal.value().len()
+                + literal.datatype().as_str().len()
+                + literal.language().map_or(0, str::len)
+        }
+        oxrdf::TermRef::Triple(triple) => {
+            term_size(triple.subject.as_ref().into())
+                + triple.predicate.as_str().len()
+                + term_size(triple.object.as_ref())
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn write_worker_loop(
     ctx: crate::context::Context,
     dataset_rx: Receiver<RDFBDataset>,
-    files_tx: Sender<(PathBuf, usize)>,
+    mut sink: Box<dyn BatchSink>,
     report: Option<PrepareStatsReport>,
-    output_dir: PathBuf,
+    first_index: usize,
+    limit_batches: Option<usize>,
+    batch_sources: Option<JsonLinesLog>,
+    timings: Arc<PrepareTimings>,
 ) -> Result<()> {
-    // The index for output file. Used as `prepared.{:06d}.rdfb`.
-    let mut file_idx: usize = 1;
+    // The index of the batch, starting at `first_index`.
+    let mut file_idx = first_index;
     let mut total_written: usize = 0;
 
     while !ctx.is_cancelled() {
-        let Ok(prepared) = dataset_rx.recv() else {
+        if limit_batches.is_some_and(|limit| file_idx - first_index >= limit) {
+            break;
+        }
+        let start = Instant::now();
+        let received = dataset_rx.recv();
+        timings.write.add_stall(start);
+        let Ok(mut prepared) = received else {
             break;
         };
-        let filename = output_dir.join(format!("prepared.{:06}.rdfb", file_idx));
-
-        let mut file =
-            std::fs::File::create(&filename).context("Failed to create output file for RDFB")?;
-        file.write_all(&prepared.data)
-            .context("Failed to write RDFB data")?;
-
-        if files_tx
-            .send((filename.clone(), prepared.statement_count))
-            .is_err()
-        {
-            return Ok(());
+        let start = Instant::now();
+        let written = sink.write(file_idx, &prepared)?;
+        timings.write.add_busy(start, prepared.statement_count);
+        let Some(filename) = written else {
+            break;
+        };
+        if let Some(ref batch_sources) = batch_sources {
+            batch_sources.record(&BatchSources {
+                batch: filename.clone(),
+                statement_count: prepared.statement_count,
+                sha256: crate::cache::batch_hash(&prepared.data),
+                sources: std::mem::take(&mut prepared.sources),
+            })?;
         }
 
         if let Some(ref report) = report {
             let filename = filename.clone();
-            report
-                .tx
-                .send(crate::ui::Event::Prepare(crate::ui::PrepareProgress {
-                    filename,
-                    bytes: prepared.data.len(),
-                    statement_count: prepared.statement_count,
-                    skipped_statements: prepared.skipped_statements,
-                }))
-                .ok();
+            report.send(crate::ui::Event::Prepare(crate::ui::PrepareProgress {
+                filename,
+                bytes: prepared.data.len(),
+                statement_count: prepared.statement_count,
+                skipped_statements: prepared.skipped_statements,
+            }));
         }
 
         total_written += prepared.statement_count;
@@ -377,6 +1949,9 @@ fn write_worker_loop(
         file_idx += 1;
     }
 
+    if let Some(batch_sources) = batch_sources {
+        batch_sources.flush()?;
+    }
     Ok(())
 }
 
@@ -410,6 +1985,12 @@ impl std::io::Write for SharedBufferWriter {
     }
 }
 
+/// Serializes the statements into a single RDF/Borsh batch, whatever its
+/// size, e.g. to convert them rather than to publish them.
+pub fn serialize_quads(quads: Vec<oxrdf::Quad>) -> Result<Vec<u8>, std::io::Error> {
+    serialize_statements(quads.into_iter().map(Box::<dyn Statement>::from))
+}
+
 fn serialize_statements<T, I>(statements: I) -> Result<Vec<u8>, std::io::Error>
 where
     T: AsRef<dyn Statement>,
@@ -426,3 +2007,167 @@ where
 
     Ok(buf.take())
 }
+
+/// RDF/Borsh version of batches referencing a term dictionary shared across batches.
+pub const SHARED_DICTIONARY_VERSION: u8 = b'2';
+
+/// A term dictionary shared across all batches of a prepare run.
+///
+/// Each batch only carries the terms first seen in it and refers to the terms
+/// of earlier batches by their already assigned IDs, so the batches must be
+/// published in order.
+#[derive(Default)]
+struct TermDictionary {
+    terms: HashMap<BorshTerm, u32>,
+}
+
+impl TermDictionary {
+    /// Serializes the statements, returning the data and the terms it introduces.
+    fn serialize_statements<T, I>(
+        &self,
+        statements: I,
+    ) -> Result<(Vec<u8>, Vec<BorshTerm>), std::io::Error>
+    where
+        T: AsRef<dyn Statement>,
+        I: Iterator<Item = T>,
+    {
+        let first_id = self.terms.len() as u32 + 1;
+        let mut new_terms: Vec<BorshTerm> = Vec::new();
+        let mut new_ids: HashMap<BorshTerm, u32> = HashMap::new();
+
+        let mut intern = |term: &dyn Term| -> u32 {
+            let term = BorshTerm::from(term);
+            if let Some(&id) = self.terms.get(&term) {
+                return id;
+            }
+            *new_ids.entry(term.clone()).or_insert_with(|| {
+                new_terms.push(term);
+                first_id + new_terms.len() as u32 - 1
+            })
+        };
+
+        let mut quads: BTreeSet<[u32; 4]> = BTreeSet::new();
+        for statement in statements {
+            let statement = statement.as_ref();
+            quads.insert([
+                statement.context().map(&mut intern).unwrap_or(0),
+                intern(statement.subject()),
+                intern(statement.predicate()),
+                intern(statement.object()),
+            ]);
+        }
+
+        let mut data = Vec::with_capacity(MAX_FILE_SIZE);
+        data.extend_from_slice(b"RDFB");
+        data.push(SHARED_DICTIONARY_VERSION);
+        data.push(0b00000111);
+        data.extend_from_slice(&(quads.len() as u32).to_le_bytes());
+
+        let mut compressor = lz4_flex::frame::FrameEncoder::new(&mut data);
+        first_id.serialize(&mut compressor)?;
+        new_terms.serialize(&mut compressor)?;
+        quads.serialize(&mut compressor)?;
+        compressor.finish().map_err(std::io::Error::other)?;
+
+        Ok((data, new_terms))
+    }
+
+    /// Assigns IDs to the terms introduced by a batch that was accepted.
+    fn extend(&mut self, new_terms: Vec<BorshTerm>) {
+        for term in new_terms {
+            let id = self.terms.len() as u32 + 1;
+            self.terms.insert(term, id);
+        }
+    }
+}
+
+/// WebAssembly has no clock without a host, so the timings of batches
+/// prepared in WebAssembly stay zero.
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+mod wasm_clock {
+    use std::time::Duration;
+
+    #[derive(Clone, Copy, Debug)]
+    pub struct Instant;
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_the_rest_of_a_file_after_a_parse_error() {
+        let dir = std::env::temp_dir().join(format!(
+            "asimov-dataset-test-{}-parse-error",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        // RDF/XML parsers can't go on after an error:
+        let file = dir.join("data.rdf");
+        std::fs::write(
+            &file,
+            r#"<?xml version="1.0"?>
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#" xmlns:ex="http://example.org/">
+  <rdf:Description rdf:about="http://example.org/s"><ex:p>a</ex:p></rdf:Description>
+  <rdf:Description rdf:about="http://example.org/s"><ex:p>b</ex:p></rdf:Description>
+  <rdf:Description rdf:about="http://example.org/s"><ex:p>c</ex:q></rdf:Description>
+  <rdf:Description rdf:about="http://example.org/s"><ex:p>d</ex:p></rdf:Description>
+</rdf:RDF>
+"#,
+        )
+        .unwrap();
+        let errors = dir.join("errors.jsonl");
+        let parse_errors = Arc::new(JsonLinesLog::create(&errors).unwrap());
+
+        let (input_tx, input_rx) = crossbeam::channel::unbounded();
+        input_tx.send((file, None)).unwrap();
+        drop(input_tx);
+        let (batch_tx, batch_rx) = crossbeam::channel::unbounded();
+        let (ctx, _canceller) = crate::context::new_cancel_context();
+        read_worker_loop(
+            ctx,
+            input_rx,
+            &AtomicCell::new(0),
+            batch_tx,
+            None,
+            false,
+            false,
+            None,
+            None,
+            None,
+            false,
+            1,
+            None,
+            None,
+            false,
+            None,
+            false,
+            true,
+            Some(parse_errors),
+            None,
+            None,
+            Arc::default(),
+        )
+        .unwrap();
+
+        let values: Vec<String> = batch_rx
+            .iter()
+            .flat_map(|batch| batch.quads)
+            .map(|(_, _, quad)| quad.object.to_string())
+            .collect();
+        assert_eq!(values, [r#""a""#, r#""b""#]);
+        let records = std::fs::read_to_string(&errors).unwrap();
+        assert_eq!(records.lines().count(), 1);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
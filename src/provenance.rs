@@ -0,0 +1,106 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{Context as _, Result};
+use oxrdf::{
+    vocab::{rdf, xsd},
+    BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term,
+};
+use sha2::{Digest, Sha256};
+use std::{fs::File, path::Path};
+
+/// The named graph into which provenance statements are added.
+pub const PROVENANCE_GRAPH: &str = "urn:asimov:provenance";
+
+const PROV: &str = "http://www.w3.org/ns/prov#";
+const FOAF_ACCOUNT_NAME: &str = "http://xmlns.com/foaf/0.1/accountName";
+
+/// Describes the origin of the prepared statements with PROV-O statements.
+#[derive(Clone, Debug, Default)]
+pub struct Provenance {
+    /// The account publishing the statements, if they are being published.
+    pub publisher: Option<String>,
+}
+
+impl Provenance {
+    /// Returns the provenance statements for an input file: the file as a
+    /// `prov:Entity` identified by its SHA-256, used by a preparation
+    /// `prov:Activity` associated with this tool and the publishing account.
+    pub fn file_quads(&self, file: &Path) -> Result<Vec<Quad>> {
+        let mut hasher = Sha256::new();
+        let mut reader = File::open(file).context("Failed to open input file")?;
+        std::io::copy(&mut reader, &mut hasher).context("Failed to hash input file")?;
+        let digest: String = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+
+        let graph = GraphName::NamedNode(NamedNode::new_unchecked(PROVENANCE_GRAPH));
+        let prov = |term: &str| NamedNode::new_unchecked(format!("{PROV}{term}"));
+
+        let entity = NamedNode::new_unchecked(format!("urn:sha256:{digest}"));
+        let activity = BlankNode::default();
+        let tool = NamedNode::new_unchecked(format!(
+            "https://crates.io/crates/{}/{}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ));
+        let timestamp = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true);
+
+        let mut triples: Vec<(NamedOrBlankNode, NamedNode, Term)> = vec![
+            (
+                entity.clone().into(),
+                rdf::TYPE.into(),
+                prov("Entity").into(),
+            ),
+            (
+                entity.clone().into(),
+                prov("atLocation"),
+                Literal::new_simple_literal(file.display().to_string()).into(),
+            ),
+            (
+                tool.clone().into(),
+                rdf::TYPE.into(),
+                prov("SoftwareAgent").into(),
+            ),
+            (
+                activity.clone().into(),
+                rdf::TYPE.into(),
+                prov("Activity").into(),
+            ),
+            (activity.clone().into(), prov("used"), entity.into()),
+            (
+                activity.clone().into(),
+                prov("endedAtTime"),
+                Literal::new_typed_literal(timestamp, xsd::DATE_TIME).into(),
+            ),
+            (
+                activity.clone().into(),
+                prov("wasAssociatedWith"),
+                tool.into(),
+            ),
+        ];
+
+        if let Some(ref publisher) = self.publisher {
+            let agent = BlankNode::default();
+            triples.extend([
+                (agent.clone().into(), rdf::TYPE.into(), prov("Agent").into()),
+                (
+                    agent.clone().into(),
+                    NamedNode::new_unchecked(FOAF_ACCOUNT_NAME),
+                    Literal::new_simple_literal(publisher).into(),
+                ),
+                (activity.into(), prov("wasAssociatedWith"), agent.into()),
+            ]);
+        }
+
+        let quads = triples
+            .into_iter()
+            .map(|(subject, predicate, object)| {
+                Quad::new(subject, predicate, object, graph.clone())
+            })
+            .collect();
+
+        Ok(quads)
+    }
+}
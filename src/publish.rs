@@ -2,7 +2,7 @@
 
 use borsh::BorshSerialize;
 use color_eyre::{
-    eyre::{eyre, Context as _, Result},
+    eyre::{bail, eyre, Context as _, Report, Result},
     Section,
 };
 use crossbeam::channel::Sender;
@@ -12,17 +12,59 @@ use near_api::{
         errors::{
             ActionError, ActionErrorKind, CompilationError, FunctionCallError, TxExecutionError,
         },
-        views::FinalExecutionStatus,
+        hash::CryptoHash,
+        transaction::TransactionV0,
+        views::{FinalExecutionOutcomeView, FinalExecutionStatus},
     },
     AccountId, NearGas, NetworkConfig, Transaction,
 };
-use std::{io::Read, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use crate::context::Context;
+use crate::{
+    balance::{BalanceGuard, Budget},
+    cache::{self, PublishCache},
+    context::Context,
+    finality::Stage,
+    keys::Nonces,
+    ledger::{self, LedgerEntry},
+    metrics::Metrics,
+    offline::{self, OfflineBatch, OfflineTransaction},
+    pacing::Pacing,
+    prepare::{BatchSizer, PreparedBatch, SHARED_DICTIONARY_VERSION},
+    publisher::{NearPublisher, Publisher},
+    rpc::{self, WaitUntil},
+};
 
 #[derive(Clone, Debug)]
 pub struct PublishStatsReport {
-    pub tx: Sender<crate::ui::Event>,
+    reporter: crate::ui::EventReporter,
+}
+
+impl PublishStatsReport {
+    /// Returns a report sending the progress events to the channel.
+    pub fn new(tx: Sender<crate::ui::Event>) -> Self {
+        Self {
+            reporter: tx.into(),
+        }
+    }
+
+    /// Returns a report passing the progress events to the callback, on the
+    /// thread reporting each event.
+    pub fn from_fn(callback: impl Fn(crate::ui::Event) + Send + Sync + 'static) -> Self {
+        Self {
+            reporter: crate::ui::EventReporter::Callback(Arc::new(callback)),
+        }
+    }
+
+    /// Reports the progress event.
+    pub fn send(&self, event: crate::ui::Event) {
+        self.reporter.send(event)
+    }
 }
 
 /// Splits the files into (prepared, unprepared) according to their file extension.
@@ -33,15 +75,87 @@ pub fn split_prepared_files(files: &[PathBuf]) -> (Vec<PathBuf>, Vec<PathBuf>) {
         .partition(|file| file.extension().is_some_and(|ext| ext == "rdfb"))
 }
 
+/// Sends each batch both to `tx` and to `mirror_tx`, to publish it to a
+/// mirror repository too, until the batches run out or both publishers stop.
+///
+/// The file of each batch is hard linked (or copied) into `dir` for the
+/// mirror, since each publisher removes the files it has published. Sending
+/// blocks until both publishers have room for the batch, keeping them in step.
+pub fn mirror_batches<I>(
+    batches: I,
+    dir: &std::path::Path,
+    tx: Sender<PreparedBatch>,
+    mirror_tx: Sender<PreparedBatch>,
+) -> Result<()>
+where
+    I: Iterator<Item = PreparedBatch>,
+{
+    let (mut tx, mut mirror_tx) = (Some(tx), Some(mirror_tx));
+    for (index, batch) in batches.enumerate() {
+        if let Some(ref sender) = mirror_tx {
+            let filename = batch.path.file_name().unwrap_or_default().to_string_lossy();
+            let path = dir.join(format!("{index:06}.{filename}"));
+            std::fs::hard_link(&batch.path, &path)
+                .or_else(|_| std::fs::copy(&batch.path, &path).map(|_| ()))
+                .with_context(|| {
+                    format!(
+                        "Failed to copy batch {} for the mirror",
+                        batch.path.display()
+                    )
+                })?;
+            let mirror_batch = PreparedBatch {
+                path,
+                ..batch.clone()
+            };
+            if sender.send(mirror_batch).is_err() {
+                mirror_tx = None;
+            }
+        }
+        if tx
+            .as_ref()
+            .is_some_and(|sender| sender.send(batch).is_err())
+        {
+            tx = None;
+        }
+        if tx.is_none() && mirror_tx.is_none() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// The bundled basic repository contract.
+pub const LOG_VAULT_WASM: &[u8] = include_bytes!("../assets/log_vault.wasm");
+
+/// A function call initializing or migrating a freshly deployed repository contract.
+#[derive(Clone, Debug)]
+pub struct ContractCall {
+    pub method: String,
+    /// JSON arguments of the call.
+    pub args: Vec<u8>,
+}
+
+/// Deploys the contract code at the repository address, followed in the same
+/// transaction by the given call, e.g. to initialize or migrate the contract.
 pub async fn upload_repository_contract(
     repository: AccountId,
     signer_id: AccountId,
     signer: Arc<near_api::Signer>,
     network: &NetworkConfig,
+    code: Vec<u8>,
+    call: Option<ContractCall>,
 ) -> Result<()> {
-    let code = include_bytes!("../assets/log_vault.wasm").to_vec();
+    let mut actions = vec![Action::DeployContract(DeployContractAction { code })];
+    if let Some(call) = call {
+        actions.push(Action::FunctionCall(Box::new(FunctionCallAction {
+            method_name: call.method,
+            args: call.args,
+            gas: NearGas::from_tgas(MAX_TX_TGAS).as_gas(),
+            deposit: 0,
+        })));
+    }
     let tx_outcome = Transaction::construct(signer_id.clone(), repository.clone())
-        .add_action(Action::DeployContract(DeployContractAction { code }))
+        .add_actions(actions)
         .with_signer(signer)
         .send_to(network)
         .await
@@ -56,6 +170,107 @@ pub async fn upload_repository_contract(
     }
 }
 
+/// Returns the hash of the contract code deployed at the repository address
+/// as of the block, or `None` if the account has no contract.
+pub async fn repository_code_hash(
+    repository: &AccountId,
+    network: &NetworkConfig,
+    block: rpc::Block,
+) -> Result<Option<CryptoHash>> {
+    let code_hash = rpc::view_account(network, repository, block)
+        .await
+        .with_context(|| format!("Failed to view account {repository}"))?
+        .code_hash;
+    Ok((code_hash != CryptoHash::default()).then_some(code_hash))
+}
+
+/// The change a publish run makes to the repository with its batches.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Operation {
+    /// Insert the statements of the batches, with `rdf_insert`.
+    #[default]
+    Insert,
+    /// Delete the statements of the batches, with `rdf_delete`.
+    Delete,
+}
+
+impl Operation {
+    /// Returns the contract method called with each batch.
+    pub fn method_name(&self) -> &'static str {
+        match self {
+            Self::Insert => "rdf_insert",
+            Self::Delete => "rdf_delete",
+        }
+    }
+}
+
+/// The order in which the batches of a publish run take effect on chain.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum PublishOrder {
+    /// Each transaction is executed before the next one is submitted, so the
+    /// batches are inserted in the order they were prepared, as needed when
+    /// later statements supersede earlier ones.
+    Strict,
+    /// Transactions may be submitted without waiting for the earlier ones,
+    /// with `async_submit`, and then be executed in any order.
+    #[default]
+    Relaxed,
+}
+
+impl PublishOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Strict => "strict",
+            Self::Relaxed => "relaxed",
+        }
+    }
+
+    /// Checks that transactions submitted and waited for this way take
+    /// effect in this order.
+    ///
+    /// With strict ordering, transactions broadcast without waiting could be
+    /// executed out of order, and so could the receipts of a transaction only
+    /// waited for until included in a block.
+    pub fn check(&self, async_submit: bool, wait_until: WaitUntil) -> Result<()> {
+        if *self == Self::Relaxed {
+            return Ok(());
+        }
+        if async_submit {
+            return Err(eyre!(
+                "Strict ordering waits for each transaction before submitting the next one"
+            ))
+            .with_suggestion(|| "Drop `--async-submit`, or use `--ordering relaxed`");
+        }
+        if !wait_until.has_outcome() {
+            return Err(eyre!(
+                "Strict ordering waits for each transaction to be executed, not only included"
+            ))
+            .with_suggestion(|| "Wait until `optimistic` or a later finality");
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for PublishOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for PublishOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "strict" => Ok(Self::Strict),
+            "relaxed" => Ok(Self::Relaxed),
+            _ => Err(format!(
+                "unknown ordering `{s}`, expected one of: strict, relaxed"
+            )),
+        }
+    }
+}
+
 #[derive(derive_builder::Builder)]
 #[builder(pattern = "owned")]
 pub struct Params<I> {
@@ -68,6 +283,77 @@ pub struct Params<I> {
     files: I,
     #[builder(setter(into, strip_option), default)]
     report: Option<PublishStatsReport>,
+    /// Max number of batches sent as separate actions of one transaction.
+    #[builder(default = "1")]
+    max_batches_per_tx: usize,
+    /// Dataset names for named graphs, keyed by graph IRI.
+    #[builder(default)]
+    graph_datasets: HashMap<String, String>,
+    #[builder(setter(strip_option), default)]
+    metrics: Option<Arc<Metrics>>,
+    /// Where to list the unpublished batches if publishing fails.
+    #[builder(setter(into, strip_option), default)]
+    failed_batches: Option<PathBuf>,
+    /// NDJSON receipt ledger to which every submitted transaction is appended.
+    #[builder(setter(into), default)]
+    ledger: Option<PathBuf>,
+    /// Finality to wait for after submitting each transaction.
+    #[builder(default)]
+    wait_until: WaitUntil,
+    /// Broadcast all transactions without waiting, then wait for their
+    /// finality afterwards.
+    #[builder(default)]
+    async_submit: bool,
+    /// Whether each transaction must be executed before the next one is
+    /// submitted.
+    #[builder(default)]
+    ordering: PublishOrder,
+    /// Simulate each `rdf_insert` call before submitting it.
+    #[builder(default)]
+    simulate: bool,
+    /// Check that each batch decodes before submitting it.
+    #[builder(default = "true")]
+    verify: bool,
+    /// SHA-256 digests the batches must have, keyed by their canonical paths.
+    #[builder(default)]
+    checksums: HashMap<PathBuf, String>,
+    /// Cache of the batches already published to the repository, which are skipped.
+    #[builder(setter(strip_option), default)]
+    cache: Option<Arc<PublishCache>>,
+    /// Publish batches even if the cache lists them as already published.
+    #[builder(default)]
+    force: bool,
+    /// Whether the batches are inserted or deleted. The cache only covers
+    /// inserted batches.
+    #[builder(default)]
+    operation: Operation,
+    /// Directory into which the transactions are written unsigned, with
+    /// their nonce and block hash fixed, instead of being submitted.
+    #[builder(setter(into, strip_option), default)]
+    offline_sign_out: Option<PathBuf>,
+    /// The nonces last used with each access key of the signer.
+    #[builder(setter(skip))]
+    nonces: Nonces,
+    /// Slows down or pauses submissions while the network is congested.
+    #[builder(setter(strip_option), default)]
+    pacing: Option<Arc<Pacing>>,
+    /// Pauses submissions while the balance of the signer is too low.
+    #[builder(setter(strip_option), default)]
+    balance_guard: Option<Arc<BalanceGuard>>,
+    /// Stops submissions before the tokens burnt could exceed a budget.
+    #[builder(setter(strip_option), default)]
+    budget: Option<Arc<Budget>>,
+    /// The target size of the batches being prepared, adapted to the gas
+    /// burnt by each transaction.
+    #[builder(setter(strip_option), default)]
+    batch_sizer: Option<Arc<BatchSizer>>,
+    /// Max total size of the arguments packed into a single transaction.
+    #[builder(default = "MAX_TX_SIZE")]
+    max_tx_size: usize,
+    /// Submits the transactions, by default to the repository on the network
+    /// with the signer.
+    #[builder(setter(strip_option), default)]
+    publisher: Option<Arc<dyn Publisher>>,
 }
 
 impl<I> Params<I> {
@@ -88,73 +374,1002 @@ impl<I> Params<I> {
             network,
             files,
             report,
+            max_batches_per_tx: 1,
+            graph_datasets: HashMap::new(),
+            metrics: None,
+            failed_batches: None,
+            ledger: None,
+            wait_until: WaitUntil::default(),
+            async_submit: false,
+            ordering: PublishOrder::Relaxed,
+            simulate: false,
+            verify: true,
+            checksums: HashMap::new(),
+            cache: None,
+            force: false,
+            operation: Operation::Insert,
+            offline_sign_out: None,
+            nonces: Nonces::default(),
+            pacing: None,
+            balance_guard: None,
+            budget: None,
+            batch_sizer: None,
+            max_tx_size: MAX_TX_SIZE,
+            publisher: None,
         }
     }
 }
 
-pub async fn publish_datasets<I>(ctx: Context, params: Params<I>) -> Result<()>
+pub async fn publish_datasets<I>(ctx: Context, mut params: Params<I>) -> Result<()>
 where
-    I: Iterator<Item = (PathBuf, usize)>,
+    I: Iterator<Item = PreparedBatch>,
 {
-    let dataset = params.dataset.unwrap_or(String::from(""));
-    for (filename, statement_count) in params.files {
+    params
+        .ordering
+        .check(params.async_submit, params.wait_until)?;
+    let max_batches_per_tx = params.max_batches_per_tx.max(1);
+    if params.publisher.is_none() {
+        params.publisher = Some(Arc::new(NearPublisher::new(
+            params.network.clone(),
+            params.repository.clone(),
+            params.signer_id.clone(),
+            params.signer.clone(),
+        )));
+    }
+
+    // Batches waiting to be sent together in the next transaction.
+    let mut pending: Vec<PendingBatch> = Vec::with_capacity(max_batches_per_tx);
+    let mut pending_size: usize = 0;
+    // Transactions broadcast without waiting, to be reconciled at the end.
+    let mut submitted: Vec<(CryptoHash, Vec<PendingBatch>)> = Vec::new();
+
+    while let Some(batch) = params.files.next() {
+        ctx.wait_while_paused().await;
         if ctx.is_cancelled() {
             break;
         }
-        let mut args = Vec::new();
-        1_u8.serialize(&mut args)?; // version 1
-        dataset.serialize(&mut args)?;
+        let dataset = params.batch_dataset(&batch);
+        let PreparedBatch {
+            path: filename,
+            statement_count,
+            ..
+        } = batch;
+
+        let mut data = Vec::new();
+        let bytes = std::fs::File::open(&filename)?.read_to_end(&mut data)?;
+        let verified = params.verify_batch(&filename, &data);
+
+        let args = insert_args(&dataset, &data)?;
+
+        let hash = cache::batch_hash(&args);
+        if let (Some(cache), false, Operation::Insert) =
+            (&params.cache, params.force, params.operation)
+        {
+            if let Some(transaction) = cache.transaction(&hash) {
+                tracing::info!(?filename, %transaction, "skipped already published batch");
+                std::fs::remove_file(&filename).ok();
+                if let Some(ref report) = params.report {
+                    report.send(crate::ui::Event::PublishSkipped(
+                        crate::ui::PublishSkipped {
+                            filename,
+                            dataset,
+                            transaction,
+                        },
+                    ));
+                }
+                continue;
+            }
+        }
+
+        let batch = PendingBatch {
+            filename,
+            bytes,
+            statement_count,
+            dataset,
+            hash,
+            args,
+        };
+
+        // A corrupted batch would make the contract panic, burning gas:
+        if let Err(err) = verified {
+            params.report_failure(std::slice::from_ref(&batch), &err);
+            pending.push(batch);
+            return Err(abort_publish(&mut params, submitted, pending, err).await);
+        }
+
+        if params.simulate {
+            if let Err(err) = simulate_batch(&params, &batch).await {
+                params.report_failure(std::slice::from_ref(&batch), &err);
+                pending.push(batch);
+                return Err(abort_publish(&mut params, submitted, pending, err).await);
+            }
+        }
+
+        if !pending.is_empty()
+            && (pending.len() >= max_batches_per_tx
+                || pending_size + batch.args.len() > params.max_tx_size)
+        {
+            if let Err(err) = flush_batches(&params, &mut pending, &mut submitted).await {
+                params.report_failure(&pending, &err);
+                pending.push(batch);
+                return Err(abort_publish(&mut params, submitted, pending, err).await);
+            }
+            pending_size = 0;
+        }
+
+        pending_size += batch.args.len();
+        pending.push(batch);
+    }
+
+    if !pending.is_empty() && !ctx.is_cancelled() {
+        if let Err(err) = flush_batches(&params, &mut pending, &mut submitted).await {
+            params.report_failure(&pending, &err);
+            return Err(abort_publish(&mut params, submitted, pending, err).await);
+        }
+    }
+
+    let mut failed: Vec<PendingBatch> = Vec::new();
+    let mut first_error = None;
+    for (tx_hash, batches) in submitted {
+        if let Err(err) = reconcile_transaction(&params, tx_hash, &batches).await {
+            failed.extend(batches);
+            first_error.get_or_insert(err);
+        }
+    }
+    match first_error {
+        Some(err) => Err(record_failed_batches(&mut params, &failed, err)),
+        None => Ok(()),
+    }
+}
+
+/// Sends the pending batches in one transaction. In asynchronous mode the
+/// transaction is only broadcast, and kept in `submitted` for reconciliation.
+async fn flush_batches<I>(
+    params: &Params<I>,
+    pending: &mut Vec<PendingBatch>,
+    submitted: &mut Vec<(CryptoHash, Vec<PendingBatch>)>,
+) -> Result<()> {
+    if let Some(ref dir) = params.offline_sign_out {
+        write_unsigned_batches(params, pending, dir).await?;
+        pending.clear();
+        return Ok(());
+    }
+    if !params.async_submit {
+        send_batches(params, pending).await?;
+        pending.clear();
+        return Ok(());
+    }
+
+    let tx_hash = submit_batches(params, pending).await?;
+    let batches = pending
+        .drain(..)
+        .map(|batch| PendingBatch {
+            args: Vec::new(), // no longer needed
+            ..batch
+        })
+        .collect();
+    submitted.push((tx_hash, batches));
+    Ok(())
+}
+
+/// Stops publishing after the error, recording the batches of unsuccessful
+/// transactions, the pending batches and all those that would have followed.
+async fn abort_publish<I>(
+    params: &mut Params<I>,
+    submitted: Vec<(CryptoHash, Vec<PendingBatch>)>,
+    pending: Vec<PendingBatch>,
+    error: Report,
+) -> Report
+where
+    I: Iterator<Item = PreparedBatch>,
+{
+    // Transactions already broadcast may still succeed, so wait for them:
+    let mut failed: Vec<PendingBatch> = Vec::new();
+    for (tx_hash, batches) in submitted {
+        if reconcile_transaction(params, tx_hash, &batches)
+            .await
+            .is_err()
+        {
+            failed.extend(batches);
+        }
+    }
+    failed.extend(pending);
+    record_failed_batches(params, &failed, error)
+}
+
+/// Simulates the `rdf_insert` or `rdf_delete` call of the batch, failing if
+/// it would fail.
+async fn simulate_batch<I>(params: &Params<I>, batch: &PendingBatch) -> Result<()> {
+    let msg = || format!("Simulation of batch {} failed", batch.filename.display());
+    if batch.args.len() > params.max_tx_size {
+        return Err(eyre!(
+            "The arguments are {} bytes, more than the {} bytes allowed in a transaction",
+            batch.args.len(),
+            params.max_tx_size
+        ))
+        .wrap_err_with(msg);
+    }
+    params
+        .publisher()
+        .simulate(params.operation.method_name(), &batch.args)
+        .await
+        .inspect_err(|_| params.record_rpc_error())
+        .wrap_err_with(msg)
+        .with_note(|| "Nothing was submitted for this batch")
+}
+
+/// Waits for a transaction broadcast without waiting to reach the configured
+/// finality, and finishes its batches.
+async fn reconcile_transaction<I>(
+    params: &Params<I>,
+    tx_hash: CryptoHash,
+    batches: &[PendingBatch],
+) -> Result<()> {
+    let tx_outcome = await_stages(params, tx_hash, batches)
+        .await
+        .inspect_err(|err| params.report_failure(batches, err))?;
+
+    finish_batches(params, batches, tx_hash, tx_outcome)
+        .await
+        .inspect_err(|err| {
+            tracing::warn!(%tx_hash, ?err, "failed to reconcile transaction");
+            params.report_failure(batches, err);
+        })
+}
+
+impl<I> Params<I> {
+    fn publisher(&self) -> &dyn Publisher {
+        self.publisher
+            .as_deref()
+            .expect("publisher is set when publishing starts")
+    }
+
+    fn record_rpc_error(&self) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_rpc_error();
+        }
+    }
+
+    /// Reports the stage reached by the transaction of the batches.
+    fn report_stage(&self, batches: &[PendingBatch], tx_hash: CryptoHash, stage: Stage) {
+        let Some(ref report) = self.report else {
+            return;
+        };
+        for batch in batches {
+            report.send(crate::ui::Event::Publish(crate::ui::PublishProgress {
+                filename: batch.filename.clone(),
+                bytes: batch.bytes,
+                statement_count: batch.statement_count,
+                dataset: batch.dataset.clone(),
+                transaction: tx_hash.to_string(),
+                explorer_url: rpc::explorer_url(&self.network, &tx_hash),
+                finality: self.wait_until,
+                stage,
+                gas_burnt: 0,
+                tokens_burnt: 0,
+                unconfirmed: false,
+            }));
+        }
+    }
+
+    /// Reports the batches as failed to the UI as soon as the failure occurs.
+    fn report_failure(&self, batches: &[PendingBatch], error: &Report) {
+        if let Some(ref report) = self.report {
+            report.send(crate::ui::Event::PublishFailure(
+                crate::ui::PublishFailure {
+                    filenames: batches.iter().map(|batch| batch.filename.clone()).collect(),
+                    error: error.root_cause().to_string(),
+                },
+            ));
+        }
+    }
+
+    /// Checks that the batch decodes, if `verify`, and that it has the
+    /// expected checksum, if any.
+    fn verify_batch(&self, filename: &Path, data: &[u8]) -> Result<()> {
+        if self.verify {
+            crate::decode::verify(data)
+                .with_context(|| format!("Corrupted batch {}", filename.display()))?;
+        }
+        let path = filename
+            .canonicalize()
+            .unwrap_or_else(|_| filename.to_path_buf());
+        if let Some(expected) = self.checksums.get(&path) {
+            let actual = cache::batch_hash(data);
+            if actual != *expected {
+                bail!(
+                    "Batch {} has SHA-256 {actual}, but {expected} was recorded",
+                    filename.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the dataset name under which the batch is published.
+    fn batch_dataset(&self, batch: &PreparedBatch) -> String {
+        dataset_of(batch, self.dataset.as_deref(), &self.graph_datasets)
+    }
+}
+
+/// Returns the arguments of `rdf_insert` for the RDF/Borsh batch: the
+/// envelope version, the dataset name and the encoding, then the batch.
+pub fn insert_args(dataset: &str, data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut args = Vec::with_capacity(data.len() + dataset.len() + 16);
+    1_u8.serialize(&mut args)?; // version 1
+    dataset.serialize(&mut args)?;
+    if data.get(4) == Some(&SHARED_DICTIONARY_VERSION) {
+        2_u8.serialize(&mut args)?; // RDF/Borsh with shared term dictionary encoding
+    } else {
         1_u8.serialize(&mut args)?; // RDF/Borsh dataset encoding
+    }
+    args.extend_from_slice(data);
+    Ok(args)
+}
 
-        let bytes = std::fs::File::open(&filename)?.read_to_end(&mut args)?;
+/// Returns the dataset name of the batch: its own, the one of its named graph,
+/// or the default one.
+pub fn dataset_of(
+    batch: &PreparedBatch,
+    dataset: Option<&str>,
+    graph_datasets: &HashMap<String, String>,
+) -> String {
+    if let Some(ref dataset) = batch.dataset {
+        return dataset.clone();
+    }
+    match batch.graph {
+        Some(ref graph) => graph_datasets
+            .get(graph)
+            .cloned()
+            .unwrap_or_else(|| graph_dataset_name(graph)),
+        None => dataset.unwrap_or_default().to_string(),
+    }
+}
+
+/// The batches left unpublished by a failed publish run.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct FailedBatches {
+    pub repository: String,
+    pub error: String,
+    pub batches: Vec<FailedBatch>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct FailedBatch {
+    pub path: PathBuf,
+    pub statement_count: usize,
+    pub dataset: String,
+}
+
+/// Writes the failed batch and all the batches that would have followed it
+/// into the failed batches manifest, if one is configured.
+fn record_failed_batches<I>(
+    params: &mut Params<I>,
+    pending: &[PendingBatch],
+    error: Report,
+) -> Report
+where
+    I: Iterator<Item = PreparedBatch>,
+{
+    let Some(path) = params.failed_batches.clone() else {
+        return error;
+    };
+
+    let mut batches: Vec<FailedBatch> = pending
+        .iter()
+        .map(|batch| FailedBatch {
+            path: batch.filename.clone(),
+            statement_count: batch.statement_count,
+            dataset: batch.dataset.clone(),
+        })
+        .collect();
+    while let Some(batch) = params.files.next() {
+        let dataset = params.batch_dataset(&batch);
+        batches.push(FailedBatch {
+            path: batch.path,
+            statement_count: batch.statement_count,
+            dataset,
+        });
+    }
+
+    let manifest = FailedBatches {
+        repository: params.repository.to_string(),
+        error: format!("{error:#}"),
+        batches,
+    };
+    let written = serde_json::to_vec_pretty(&manifest)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(&path, json));
+
+    match written {
+        Ok(()) => error.with_note(|| {
+            format!(
+                "The {} unpublished batches are listed in {}",
+                manifest.batches.len(),
+                path.display()
+            )
+        }),
+        Err(err) => error.with_note(|| {
+            format!(
+                "Failed to write the unpublished batches to {}: {}",
+                path.display(),
+                err
+            )
+        }),
+    }
+}
+
+/// Derives a dataset name from a graph IRI, using its fragment or last path segment.
+pub fn graph_dataset_name(graph: &str) -> String {
+    graph
+        .rsplit(['#', '/', ':'])
+        .find(|segment| !segment.is_empty())
+        .unwrap_or(graph)
+        .to_string()
+}
+
+/// Returns the gas and tokens (in yoctoNEAR) burnt by the whole transaction,
+/// including its receipts.
+fn transaction_cost(tx_outcome: &FinalExecutionOutcomeView) -> (u64, u128) {
+    std::iter::once(&tx_outcome.transaction_outcome)
+        .chain(tx_outcome.receipts_outcome.iter())
+        .map(|outcome| &outcome.outcome)
+        .fold((0, 0), |(gas, tokens), outcome| {
+            (gas + outcome.gas_burnt, tokens + outcome.tokens_burnt)
+        })
+}
+
+/// Returns the storage used by the repository account, in bytes.
+pub async fn repository_storage_usage(
+    repository: &AccountId,
+    network: &NetworkConfig,
+) -> Result<u64> {
+    Ok(rpc::view_account(network, repository, rpc::Block::Final)
+        .await
+        .with_context(|| format!("Failed to view account {repository}"))?
+        .storage_usage)
+}
 
-        let tx_outcome = Transaction::construct(params.signer_id.clone(), params.repository.clone())
-            .add_action(Action::FunctionCall(Box::new(FunctionCallAction {
-                method_name: "rdf_insert".into(),
-                args,
-                gas: NearGas::from_tgas(300).as_gas(),
+async fn record_ledger_entries<I>(
+    params: &Params<I>,
+    ledger: &std::path::Path,
+    batches: &[PendingBatch],
+    tx_hash: CryptoHash,
+    tx_outcome: Option<&FinalExecutionOutcomeView>,
+) -> Result<()> {
+    let block_hash = tx_outcome.map(|outcome| outcome.transaction_outcome.block_hash);
+    let block_height = match block_hash {
+        Some(block_hash) => rpc::block_height(&params.network, block_hash)
+            .await
+            .inspect_err(|err| tracing::warn!(?err, %block_hash, "failed to fetch block height"))
+            .ok(),
+        None => None,
+    };
+
+    let (gas_burnt, tokens_burnt) = tx_outcome.map(transaction_cost).unwrap_or_default();
+
+    let status = match tx_outcome.map(|outcome| &outcome.status) {
+        Some(FinalExecutionStatus::SuccessValue(_)) => "success",
+        Some(FinalExecutionStatus::Failure(_)) => "failure",
+        Some(FinalExecutionStatus::NotStarted | FinalExecutionStatus::Started) => "pending",
+        None => params.wait_until.as_str(),
+    };
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let entries: Vec<LedgerEntry> = batches
+        .iter()
+        .map(|batch| LedgerEntry {
+            timestamp,
+            network: params.network.network_name.clone(),
+            repository: params.repository.to_string(),
+            dataset: batch.dataset.clone(),
+            transaction: tx_hash.to_string(),
+            signer: Some(params.signer_id.to_string()),
+            block_hash: block_hash.map(|hash| hash.to_string()),
+            block_height,
+            filename: batch.filename.clone(),
+            hash: Some(batch.hash.clone()),
+            statement_count: batch.statement_count,
+            bytes: batch.bytes,
+            gas_burnt,
+            tokens_burnt: tokens_burnt.to_string(),
+            status: status.into(),
+        })
+        .collect();
+
+    Ok(ledger::append(ledger, &entries)?)
+}
+
+/// Default max total size of `rdf_insert` arguments packed into a single
+/// transaction, the max transaction size of the NEAR protocol.
+pub const MAX_TX_SIZE: usize = 1_572_864;
+
+/// Max times a transaction is resent with a later nonce after an
+/// `InvalidNonce` error.
+const MAX_NONCE_RETRIES: usize = 3;
+
+/// Max gas that can be attached to a single transaction, shared by its actions.
+pub(crate) const MAX_TX_TGAS: u64 = 300;
+
+struct PendingBatch {
+    filename: PathBuf,
+    bytes: usize,
+    statement_count: usize,
+    dataset: String,
+    /// The hash of the arguments, identifying the batch in the publish cache.
+    hash: String,
+    args: Vec<u8>,
+}
+
+/// Sends the batches as `rdf_insert` or `rdf_delete` actions of a single transaction and waits
+/// for the transaction to reach the configured finality.
+async fn send_batches<I>(params: &Params<I>, batches: &[PendingBatch]) -> Result<()> {
+    let tx_hash = submit_batches(params, batches).await?;
+    let tx_outcome = await_stages(params, tx_hash, batches).await?;
+    finish_batches(params, batches, tx_hash, tx_outcome).await
+}
+
+/// Broadcasts a transaction with the batches through the publisher, without
+/// waiting for it, returning its hash.
+async fn submit_batches<I>(params: &Params<I>, batches: &[PendingBatch]) -> Result<CryptoHash> {
+    let actions = batch_actions(params, batches);
+    let filenames: Vec<&PathBuf> = batches.iter().map(|batch| &batch.filename).collect();
+
+    if let Some(ref pacing) = params.pacing {
+        pacing.wait(&params.network).await;
+    }
+    if let Some(ref guard) = params.balance_guard {
+        guard
+            .wait(&params.network, &params.signer_id, params.report.as_ref())
+            .await;
+    }
+    if let Some(ref budget) = params.budget {
+        budget.reserve(&params.network).await?;
+    }
+    let mut retries = 0;
+    let tx_hash = loop {
+        match params.publisher().send(actions.clone(), None).await {
+            Ok((tx_hash, _)) => break tx_hash,
+            // Another client used the access key, so resend with a later nonce:
+            Err(err) if retries < MAX_NONCE_RETRIES && rpc::invalid_nonce(&err).is_some() => {
+                tracing::warn!(?filenames, ?err, "invalid nonce, retrying");
+                params.record_rpc_error();
+                retries += 1;
+            }
+            Err(err) => {
+                params.record_rpc_error();
+                // The transaction may have been sent nonetheless:
+                if let Some(ref budget) = params.budget {
+                    budget.charge(None);
+                }
+                return Err(err);
+            }
+        }
+    };
+    tracing::info!(?filenames, %tx_hash, "submitted dataset");
+    params.report_stage(batches, tx_hash, Stage::Submitted);
+
+    Ok(tx_hash)
+}
+
+/// Waits for a broadcast transaction to advance through the stages up to the
+/// configured finality, reporting the stages reached before it, and returns
+/// its execution outcome if the finality level provides one.
+async fn await_stages<I>(
+    params: &Params<I>,
+    tx_hash: CryptoHash,
+    batches: &[PendingBatch],
+) -> Result<Option<FinalExecutionOutcomeView>> {
+    let status = |wait_until| async move {
+        params
+            .publisher()
+            .status(tx_hash, wait_until)
+            .await
+            .inspect_err(|_| params.record_rpc_error())
+            .wrap_err_with(|| format!("Failed to query status of transaction {tx_hash}"))
+    };
+    for stage in Stage::between(params.wait_until) {
+        let wait_until = stage.wait_until().expect("stage after the submission");
+        status(wait_until).await?;
+        params.report_stage(batches, tx_hash, stage);
+    }
+    let tx_outcome = status(params.wait_until).await?;
+    tracing::info!(
+        %tx_hash,
+        status = ?tx_outcome.as_ref().map(|outcome| &outcome.transaction_outcome.outcome.status),
+        wait_until = ?params.wait_until,
+        "uploaded dataset"
+    );
+    Ok(tx_outcome)
+}
+
+/// Returns the `rdf_insert` or `rdf_delete` actions of the batches, splitting
+/// the gas of a transaction between them in proportion to their size.
+fn batch_actions<I>(params: &Params<I>, batches: &[PendingBatch]) -> Vec<Action> {
+    let gas = split_gas(batches.iter().map(|batch| batch.args.len()));
+    batches
+        .iter()
+        .zip(gas)
+        .map(|(batch, gas)| {
+            Action::FunctionCall(Box::new(FunctionCallAction {
+                method_name: params.operation.method_name().into(),
+                args: batch.args.clone(),
+                gas,
                 deposit: 0,
-            })))
-            .with_signer(params.signer.clone())
-            .send_to(&params.network)
+            }))
+        })
+        .collect()
+}
+
+/// Splits the max gas of a transaction between actions in proportion to the
+/// size of their arguments, as the gas of `rdf_insert` grows with the bytes
+/// it decodes and stores.
+fn split_gas(sizes: impl ExactSizeIterator<Item = usize> + Clone) -> Vec<u64> {
+    let max_gas = NearGas::from_tgas(MAX_TX_TGAS).as_gas();
+    let count = sizes.len().max(1) as u64;
+    let total: u128 = sizes.clone().map(|size| size as u128).sum();
+    if total == 0 {
+        return sizes.map(|_| max_gas / count).collect();
+    }
+    sizes
+        .map(|size| (max_gas as u128 * size as u128 / total) as u64)
+        .collect()
+}
+
+/// Writes a transaction with the batches into the directory, unsigned, using
+/// the next nonce of the signer's access key and a recent block hash.
+async fn write_unsigned_batches<I>(
+    params: &Params<I>,
+    batches: &[PendingBatch],
+    dir: &Path,
+) -> Result<()> {
+    let public_key = params.signer.get_public_key().await?;
+    let (nonce, block_hash) = params
+        .nonces
+        .next(&params.signer_id, &public_key, &params.network)
+        .await
+        .inspect_err(|_| params.record_rpc_error())?;
+    let transaction = near_api::near_primitives::transaction::Transaction::V0(TransactionV0 {
+        signer_id: params.signer_id.clone(),
+        public_key,
+        nonce,
+        receiver_id: params.repository.clone(),
+        block_hash: block_hash.into(),
+        actions: batch_actions(params, batches),
+    });
+    let offline_batches = batches
+        .iter()
+        .map(|batch| OfflineBatch {
+            filename: batch.filename.clone(),
+            dataset: batch.dataset.clone(),
+            statement_count: batch.statement_count,
+            hash: batch.hash.clone(),
+        })
+        .collect();
+    let path = OfflineTransaction::new(&transaction, offline_batches)?.write(dir)?;
+    tracing::info!(?path, nonce, "wrote unsigned transaction");
+    Ok(())
+}
+
+/// Broadcasts the signed transactions written into the directory by an
+/// offline signing run, in nonce order, waiting for each to reach the given
+/// finality. Returns the hashes of the transactions.
+///
+/// Each successful transaction's file is renamed with a `.sent` extension, so
+/// that broadcasting the directory again only sends the remaining ones.
+pub async fn broadcast_signed_transactions(
+    network: &NetworkConfig,
+    repository: &AccountId,
+    dir: &Path,
+    wait_until: WaitUntil,
+    cache: Option<Arc<PublishCache>>,
+) -> Result<Vec<CryptoHash>> {
+    let mut tx_hashes = Vec::new();
+    for (path, transaction) in offline::read_transactions(dir)? {
+        let msg = || format!("Failed to broadcast transaction {}", path.display());
+        if transaction.receiver_id != repository.as_str() {
+            return Err(eyre!(
+                "The transaction is for repository {}, not {repository}",
+                transaction.receiver_id
+            ))
+            .wrap_err_with(msg);
+        }
+        let signed_transaction = transaction.signed().wrap_err_with(msg)?;
+        let tx_hash = signed_transaction.get_hash();
+        let outcome = rpc::send_transaction(network, signed_transaction, Some(wait_until))
             .await
-            .inspect(
-                |outcome| tracing::info!(?filename, status = ?outcome.transaction_outcome.outcome.status, "uploaded dataset"),
-            )?;
-
-        if let FinalExecutionStatus::Failure(error) = tx_outcome.status {
-            let msg = format!("Failed to upload batch: {}", filename.display());
-
-            if matches!(
-                error,
-                TxExecutionError::ActionError(ActionError {
-                    kind: ActionErrorKind::FunctionCallError(FunctionCallError::CompilationError(
-                        CompilationError::CodeDoesNotExist { account_id: _ }
-                    )),
-                    ..
-                })
-            ) {
-                return Err(error)
-                    .wrap_err(msg)
-                    .with_note(|| "The address does not contain a contract with a method `rdf_insert`")
-                    .with_suggestion(|| "If you want to upload a basic vault at the address you can rerun the publish command with the option `--upload-contract`");
+            .wrap_err_with(msg)?
+            .final_execution_outcome
+            .map(|outcome| outcome.into_outcome());
+        let status = outcome.map(|outcome| outcome.status);
+        if let Some(FinalExecutionStatus::Failure(error)) = status {
+            return Err(error).wrap_err_with(msg);
+        }
+        tracing::info!(?path, %tx_hash, "broadcast signed transaction");
+
+        // Only batches known to be stored are skipped by later runs:
+        let succeeded = matches!(status, Some(FinalExecutionStatus::SuccessValue(_)));
+        if let (true, Some(cache)) = (succeeded, &cache) {
+            let hashes: Vec<&str> = transaction
+                .batches
+                .iter()
+                .map(|batch| batch.hash.as_str())
+                .collect();
+            if let Err(err) = cache.insert(&hashes, &tx_hash.to_string()) {
+                tracing::warn!(?err, path = ?cache.path(), "failed to update publish cache");
             }
+        }
+        let mut sent = path.clone().into_os_string();
+        sent.push(".sent");
+        std::fs::rename(&path, sent).ok();
+        tx_hashes.push(tx_hash);
+    }
+    Ok(tx_hashes)
+}
+
+/// Records the outcome of a transaction and reports its batches as published,
+/// or fails if the transaction failed.
+async fn finish_batches<I>(
+    params: &Params<I>,
+    batches: &[PendingBatch],
+    tx_hash: CryptoHash,
+    tx_outcome: Option<FinalExecutionOutcomeView>,
+) -> Result<()> {
+    if let Some(ref budget) = params.budget {
+        budget.charge(
+            tx_outcome
+                .as_ref()
+                .map(|outcome| transaction_cost(outcome).1),
+        );
+    }
+    if let Some(ref ledger) = params.ledger {
+        record_ledger_entries(params, ledger, batches, tx_hash, tx_outcome.as_ref())
+            .await
+            .with_context(|| format!("Failed to append to ledger {}", ledger.display()))?;
+    }
+
+    // Batches packed into one transaction share its cost equally:
+    let (gas_burnt, tokens_burnt) = tx_outcome
+        .as_ref()
+        .map(transaction_cost)
+        .unwrap_or_default();
+    let batch_count = batches.len().max(1);
+    // Batches running out of gas burn all of it, which shrinks the target too:
+    if let (Some(sizer), Some(_)) = (&params.batch_sizer, &tx_outcome) {
+        let bytes: usize = batches.iter().map(|batch| batch.bytes).sum();
+        sizer.observe(
+            bytes / batch_count,
+            gas_burnt / batch_count as u64,
+            NearGas::from_tgas(MAX_TX_TGAS / batch_count as u64).as_gas(),
+        );
+    }
+    let succeeded = matches!(
+        tx_outcome.as_ref().map(|outcome| &outcome.status),
+        Some(FinalExecutionStatus::SuccessValue(_))
+    );
+    let unconfirmed = tx_outcome.is_none();
+
+    if let Some(FinalExecutionStatus::Failure(error)) = tx_outcome.map(|outcome| outcome.status) {
+        let msg = match batches {
+            [batch] => format!("Failed to upload batch: {}", batch.filename.display()),
+            _ => format!(
+                "Failed to upload batches: {}",
+                batches
+                    .iter()
+                    .map(|batch| batch.filename.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+
+        if matches!(
+            error,
+            TxExecutionError::ActionError(ActionError {
+                kind: ActionErrorKind::FunctionCallError(FunctionCallError::CompilationError(
+                    CompilationError::CodeDoesNotExist { account_id: _ }
+                )),
+                ..
+            })
+        ) {
+            let method = params.operation.method_name();
+            return Err(error)
+                .wrap_err(msg)
+                .with_note(|| format!("The address does not contain a contract with a method `{method}`"))
+                .with_suggestion(|| "If you want to upload a basic vault at the address you can rerun the publish command with the option `--upload-contract`");
+        }
 
-            return Err(error).wrap_err(msg);
+        return Err(error).wrap_err(msg);
+    }
+
+    // Only batches known to be published are cached, so that others are retried:
+    if let (Some(cache), true, Operation::Insert) = (&params.cache, succeeded, params.operation) {
+        let hashes: Vec<&str> = batches.iter().map(|batch| batch.hash.as_str()).collect();
+        if let Err(err) = cache.insert(&hashes, &tx_hash.to_string()) {
+            tracing::warn!(?err, path = ?cache.path(), "failed to update publish cache");
         }
+    }
 
-        std::fs::remove_file(&filename).ok();
+    for batch in batches {
+        // Without an outcome the batch may yet fail, so keep it to be republished:
+        if unconfirmed {
+            tracing::warn!(filename = ?batch.filename, %tx_hash, "kept batch of unconfirmed transaction");
+        } else {
+            std::fs::remove_file(&batch.filename).ok();
+        }
 
         if let Some(ref report) = params.report {
-            report
-                .tx
-                .send(crate::ui::Event::Publish(crate::ui::PublishProgress {
-                    filename,
-                    bytes,
-                    statement_count,
-                }))
-                .ok();
+            report.send(crate::ui::Event::Publish(crate::ui::PublishProgress {
+                filename: batch.filename.clone(),
+                bytes: batch.bytes,
+                statement_count: batch.statement_count,
+                dataset: batch.dataset.clone(),
+                transaction: tx_hash.to_string(),
+                explorer_url: rpc::explorer_url(&params.network, &tx_hash),
+                finality: params.wait_until,
+                stage: Stage::of(params.wait_until),
+                gas_burnt: gas_burnt / batch_count as u64,
+                tokens_burnt: tokens_burnt / batch_count as u128,
+                unconfirmed,
+            }));
         }
     }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_gas_in_proportion_to_size() {
+        let max_gas = NearGas::from_tgas(MAX_TX_TGAS).as_gas();
+        let gas = split_gas([300, 100].into_iter());
+        assert_eq!(gas, vec![max_gas / 4 * 3, max_gas / 4]);
+        assert!(gas.iter().sum::<u64>() <= max_gas);
+    }
+
+    #[test]
+    fn split_gas_evenly_without_size() {
+        let max_gas = NearGas::from_tgas(MAX_TX_TGAS).as_gas();
+        assert_eq!(split_gas([0, 0].into_iter()), vec![max_gas / 2; 2]);
+        assert_eq!(split_gas([42].into_iter()), vec![max_gas]);
+    }
+
+    #[cfg(feature = "test-utils")]
+    mod mock {
+        use super::*;
+        use crate::{context::new_cancel_context, publisher::MockPublisher};
+        use near_api::Signer;
+        use near_crypto::{KeyType, SecretKey};
+
+        /// Writes `count` batch files into a fresh directory named after the test.
+        fn write_batches(test: &str, count: u8) -> (PathBuf, Vec<PreparedBatch>) {
+            let dir = std::env::temp_dir()
+                .join("asimov-dataset-tests")
+                .join(format!("{test}-{}", std::process::id()));
+            std::fs::remove_dir_all(&dir).ok();
+            std::fs::create_dir_all(&dir).unwrap();
+            let batches = (0..count)
+                .map(|index| {
+                    let path = dir.join(format!("{index}.rdfb"));
+                    std::fs::write(&path, [index; 8]).unwrap();
+                    PreparedBatch::new(path, 1)
+                })
+                .collect();
+            (dir, batches)
+        }
+
+        fn params(
+            publisher: &Arc<MockPublisher>,
+            batches: &[PreparedBatch],
+        ) -> ParamsBuilder<std::vec::IntoIter<PreparedBatch>> {
+            let secret_key = SecretKey::from_random(KeyType::ED25519);
+            ParamsBuilder::default()
+                .signer_id("signer.testnet".parse().unwrap())
+                .signer(Signer::new(Signer::from_secret_key(secret_key)).unwrap())
+                .repository("repository.testnet".parse().unwrap())
+                .dataset("test".to_string())
+                .network(NetworkConfig::testnet())
+                .files(batches.to_vec().into_iter())
+                // The batches aren't valid RDF/Borsh:
+                .verify(false)
+                .publisher(publisher.clone())
+        }
+
+        fn mock_publisher() -> MockPublisher {
+            MockPublisher::new(
+                "repository.testnet".parse().unwrap(),
+                "signer.testnet".parse().unwrap(),
+            )
+        }
+
+        #[tokio::test]
+        async fn publishes_each_batch() {
+            let (dir, batches) = write_batches("publishes_each_batch", 3);
+            let publisher = Arc::new(mock_publisher().with_gas_burnt(1_000));
+            let params = params(&publisher, &batches).build().unwrap();
+
+            publish_datasets(new_cancel_context().0, params)
+                .await
+                .unwrap();
+
+            let expected: Vec<Vec<u8>> = (0..3u8)
+                .map(|index| insert_args("test", &[index; 8]).unwrap())
+                .collect();
+            assert_eq!(publisher.call_args(), expected);
+            assert!(batches.iter().all(|batch| !batch.path.exists()));
+            std::fs::remove_dir_all(dir).ok();
+        }
+
+        #[tokio::test]
+        async fn resends_after_an_invalid_nonce() {
+            let (dir, batches) = write_batches("resends_after_an_invalid_nonce", 1);
+            let publisher = Arc::new(mock_publisher().with_invalid_nonces(MAX_NONCE_RETRIES));
+            let params = params(&publisher, &batches).build().unwrap();
+
+            publish_datasets(new_cancel_context().0, params)
+                .await
+                .unwrap();
+
+            assert_eq!(publisher.transactions().len(), 1);
+            assert!(!batches[0].path.exists());
+            std::fs::remove_dir_all(dir).ok();
+        }
+
+        #[tokio::test]
+        async fn stops_after_too_many_invalid_nonces() {
+            let (dir, batches) = write_batches("stops_after_too_many_invalid_nonces", 1);
+            let publisher = Arc::new(mock_publisher().with_invalid_nonces(MAX_NONCE_RETRIES + 1));
+            let params = params(&publisher, &batches).build().unwrap();
+
+            let err = publish_datasets(new_cancel_context().0, params)
+                .await
+                .unwrap_err();
+
+            assert!(rpc::invalid_nonce(&err).is_some());
+            assert!(publisher.transactions().is_empty());
+            assert!(batches[0].path.exists());
+            std::fs::remove_dir_all(dir).ok();
+        }
+
+        #[tokio::test]
+        async fn records_the_batches_left_by_a_failure() {
+            let (dir, batches) = write_batches("records_the_batches_left_by_a_failure", 3);
+            let failure = TxExecutionError::ActionError(ActionError {
+                index: Some(0),
+                kind: ActionErrorKind::FunctionCallError(FunctionCallError::ExecutionError(
+                    "Smart contract panicked".into(),
+                )),
+            });
+            let publisher = Arc::new(mock_publisher().with_failure(failure));
+            let failed_batches = dir.join("failed.json");
+            let params = params(&publisher, &batches)
+                .failed_batches(&failed_batches)
+                .build()
+                .unwrap();
+
+            let err = publish_datasets(new_cancel_context().0, params)
+                .await
+                .unwrap_err();
+
+            assert_eq!(
+                err.to_string(),
+                format!("Failed to upload batch: {}", batches[0].path.display())
+            );
+            assert_eq!(publisher.transactions().len(), 1);
+            let manifest: FailedBatches =
+                serde_json::from_slice(&std::fs::read(&failed_batches).unwrap()).unwrap();
+            let paths: Vec<&PathBuf> = manifest.batches.iter().map(|batch| &batch.path).collect();
+            assert_eq!(
+                paths,
+                batches.iter().map(|batch| &batch.path).collect::<Vec<_>>()
+            );
+            assert!(batches.iter().all(|batch| batch.path.exists()));
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+}
@@ -0,0 +1,326 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::Result;
+use near_api::{
+    near_primitives::{action::Action, hash::CryptoHash, views::FinalExecutionOutcomeView},
+    AccountId, NetworkConfig, Signer, Transaction,
+};
+use std::sync::Arc;
+
+use crate::{
+    keys::Nonces,
+    rpc::{self, WaitUntil},
+};
+
+/// Submits the transactions of a publish run to a repository.
+///
+/// [`NearPublisher`] signs and sends them to a NEAR network. With the
+/// `test-utils` feature, [`MockPublisher`] records them in memory instead.
+#[async_trait::async_trait]
+pub trait Publisher: Send + Sync {
+    /// Sends a transaction with the actions to the repository, returning its
+    /// hash and, if the finality level provides one, its execution outcome.
+    /// Without a finality level, the transaction is only broadcast.
+    ///
+    /// If the transaction is rejected for its nonce, as detected by
+    /// [`rpc::invalid_nonce`], sending the actions again uses a later nonce.
+    async fn send(
+        &self,
+        actions: Vec<Action>,
+        wait_until: Option<WaitUntil>,
+    ) -> Result<(CryptoHash, Option<FinalExecutionOutcomeView>)>;
+
+    /// Waits until a transaction broadcast without waiting reaches the
+    /// finality level, returning its execution outcome if the level provides one.
+    async fn status(
+        &self,
+        tx_hash: CryptoHash,
+        wait_until: WaitUntil,
+    ) -> Result<Option<FinalExecutionOutcomeView>>;
+
+    /// Runs a function call of the repository's contract without submitting
+    /// it, failing if it would fail.
+    async fn simulate(&self, method_name: &str, args: &[u8]) -> Result<()>;
+}
+
+/// Signs transactions with the signer's access keys and sends them to the
+/// repository on a NEAR network.
+pub struct NearPublisher {
+    network: NetworkConfig,
+    repository: AccountId,
+    signer_id: AccountId,
+    signer: Arc<Signer>,
+    nonces: Nonces,
+}
+
+impl NearPublisher {
+    pub fn new(
+        network: NetworkConfig,
+        repository: AccountId,
+        signer_id: AccountId,
+        signer: Arc<Signer>,
+    ) -> Self {
+        Self {
+            network,
+            repository,
+            signer_id,
+            signer,
+            nonces: Nonces::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Publisher for NearPublisher {
+    async fn send(
+        &self,
+        actions: Vec<Action>,
+        wait_until: Option<WaitUntil>,
+    ) -> Result<(CryptoHash, Option<FinalExecutionOutcomeView>)> {
+        // The signer rotates through the access keys of its pool:
+        let public_key = self.signer.get_public_key().await?;
+        let (nonce, block_hash) = self
+            .nonces
+            .next(&self.signer_id, &public_key, &self.network)
+            .await?;
+        let signed_transaction =
+            Transaction::construct(self.signer_id.clone(), self.repository.clone())
+                .add_actions(actions)
+                .with_signer(self.signer.clone())
+                .presign_offline(public_key.clone(), block_hash, nonce)
+                .await?
+                .tr
+                .signed()
+                .expect("transaction is presigned");
+        let tx_hash = signed_transaction.get_hash();
+
+        let response = rpc::send_transaction(&self.network, signed_transaction, wait_until)
+            .await
+            .inspect_err(|err| {
+                // Another client used the access key, so skip past its nonce:
+                if let Some(ak_nonce) = rpc::invalid_nonce(err) {
+                    tracing::warn!(%public_key, nonce, ak_nonce, "invalid nonce");
+                    self.nonces.observe(&public_key, ak_nonce);
+                }
+            })?;
+        let tx_outcome = response
+            .final_execution_outcome
+            .map(|outcome| outcome.into_outcome());
+        Ok((tx_hash, tx_outcome))
+    }
+
+    async fn status(
+        &self,
+        tx_hash: CryptoHash,
+        wait_until: WaitUntil,
+    ) -> Result<Option<FinalExecutionOutcomeView>> {
+        let response =
+            rpc::transaction_status(&self.network, tx_hash, self.signer_id.clone(), wait_until)
+                .await?;
+        Ok(response
+            .final_execution_outcome
+            .map(|outcome| outcome.into_outcome()))
+    }
+
+    async fn simulate(&self, method_name: &str, args: &[u8]) -> Result<()> {
+        rpc::simulate_function_call(
+            &self.network,
+            &self.repository,
+            method_name,
+            args,
+            rpc::Block::Final,
+        )
+        .await
+    }
+}
+
+#[cfg(feature = "test-utils")]
+pub use mock::MockPublisher;
+
+#[cfg(feature = "test-utils")]
+mod mock {
+    use super::*;
+    use eyre::eyre;
+    use near_api::near_primitives::{
+        errors::{InvalidTxError, TxExecutionError},
+        transaction::{SignedTransaction, TransactionV0},
+        views::{
+            ExecutionOutcomeView, ExecutionOutcomeWithIdView, ExecutionStatusView,
+            FinalExecutionStatus,
+        },
+    };
+    use near_crypto::{KeyType, PublicKey, Signature};
+    use near_jsonrpc_client::{
+        errors::{JsonRpcError, JsonRpcServerError},
+        methods::send_tx::RpcTransactionError,
+    };
+    use std::sync::Mutex;
+
+    /// A publisher recording the transactions in memory, executing each
+    /// action with a fixed gas cost, for exercising the publish pipeline
+    /// without a network.
+    #[derive(Debug)]
+    pub struct MockPublisher {
+        repository: AccountId,
+        signer_id: AccountId,
+        gas_burnt: u64,
+        failure: Option<TxExecutionError>,
+        invalid_nonces: Mutex<usize>,
+        transactions: Mutex<Vec<SignedTransaction>>,
+    }
+
+    impl MockPublisher {
+        pub fn new(repository: AccountId, signer_id: AccountId) -> Self {
+            Self {
+                repository,
+                signer_id,
+                gas_burnt: 0,
+                failure: None,
+                invalid_nonces: Mutex::default(),
+                transactions: Mutex::default(),
+            }
+        }
+
+        /// Sets the gas burnt by each action of the transactions.
+        pub fn with_gas_burnt(mut self, gas_burnt: u64) -> Self {
+            self.gas_burnt = gas_burnt;
+            self
+        }
+
+        /// Makes the execution of every transaction fail with the error.
+        pub fn with_failure(mut self, error: TxExecutionError) -> Self {
+            self.failure = Some(error);
+            self
+        }
+
+        /// Makes the next sends be rejected for an invalid nonce, as if
+        /// another client had used the access key, `count` times.
+        pub fn with_invalid_nonces(self, count: usize) -> Self {
+            *self.invalid_nonces.lock().unwrap() = count;
+            self
+        }
+
+        /// Returns the transactions sent so far, in order.
+        pub fn transactions(&self) -> Vec<SignedTransaction> {
+            self.transactions.lock().unwrap().clone()
+        }
+
+        /// Returns the function call arguments of the actions sent so far, in order.
+        pub fn call_args(&self) -> Vec<Vec<u8>> {
+            self.transactions()
+                .into_iter()
+                .flat_map(|transaction| transaction.transaction.actions().to_vec())
+                .filter_map(|action| match action {
+                    Action::FunctionCall(call) => Some(call.args),
+                    _ => None,
+                })
+                .collect()
+        }
+
+        fn outcome(&self, transaction: &SignedTransaction) -> FinalExecutionOutcomeView {
+            let tx_hash = transaction.get_hash();
+            let gas_burnt = self.gas_burnt * transaction.transaction.actions().len() as u64;
+            let (status, receipt_status) = match self.failure {
+                Some(ref error) => (
+                    FinalExecutionStatus::Failure(error.clone()),
+                    ExecutionStatusView::Failure(error.clone()),
+                ),
+                None => (
+                    FinalExecutionStatus::SuccessValue(Vec::new()),
+                    ExecutionStatusView::SuccessValue(Vec::new()),
+                ),
+            };
+            let outcome = |executor_id: &AccountId, gas_burnt, status| ExecutionOutcomeView {
+                logs: Vec::new(),
+                receipt_ids: Vec::new(),
+                gas_burnt,
+                tokens_burnt: 0,
+                executor_id: executor_id.clone(),
+                status,
+                metadata: Default::default(),
+            };
+            FinalExecutionOutcomeView {
+                status,
+                transaction: transaction.clone().into(),
+                transaction_outcome: ExecutionOutcomeWithIdView {
+                    proof: Vec::new(),
+                    block_hash: CryptoHash::default(),
+                    id: tx_hash,
+                    outcome: outcome(
+                        &self.signer_id,
+                        0,
+                        ExecutionStatusView::SuccessReceiptId(tx_hash),
+                    ),
+                },
+                receipts_outcome: vec![ExecutionOutcomeWithIdView {
+                    proof: Vec::new(),
+                    block_hash: CryptoHash::default(),
+                    id: tx_hash,
+                    outcome: outcome(&self.repository, gas_burnt, receipt_status),
+                }],
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Publisher for MockPublisher {
+        async fn send(
+            &self,
+            actions: Vec<Action>,
+            wait_until: Option<WaitUntil>,
+        ) -> Result<(CryptoHash, Option<FinalExecutionOutcomeView>)> {
+            let mut transactions = self.transactions.lock().unwrap();
+            let nonce = transactions.len() as u64 + 1;
+            let mut invalid_nonces = self.invalid_nonces.lock().unwrap();
+            if *invalid_nonces > 0 {
+                *invalid_nonces -= 1;
+                let error = RpcTransactionError::InvalidTransaction {
+                    context: InvalidTxError::InvalidNonce {
+                        tx_nonce: nonce,
+                        ak_nonce: nonce,
+                    },
+                };
+                return Err(
+                    JsonRpcError::ServerError(JsonRpcServerError::HandlerError(error)).into(),
+                );
+            }
+            let transaction =
+                near_api::near_primitives::transaction::Transaction::V0(TransactionV0 {
+                    signer_id: self.signer_id.clone(),
+                    public_key: PublicKey::empty(KeyType::ED25519),
+                    nonce,
+                    receiver_id: self.repository.clone(),
+                    block_hash: CryptoHash::default(),
+                    actions,
+                });
+            let transaction =
+                SignedTransaction::new(Signature::empty(KeyType::ED25519), transaction);
+            let tx_hash = transaction.get_hash();
+            let tx_outcome = wait_until
+                .is_some_and(|wait_until| wait_until.has_outcome())
+                .then(|| self.outcome(&transaction));
+            transactions.push(transaction);
+            Ok((tx_hash, tx_outcome))
+        }
+
+        async fn status(
+            &self,
+            tx_hash: CryptoHash,
+            wait_until: WaitUntil,
+        ) -> Result<Option<FinalExecutionOutcomeView>> {
+            let transactions = self.transactions.lock().unwrap();
+            let transaction = transactions
+                .iter()
+                .find(|transaction| transaction.get_hash() == tx_hash)
+                .ok_or_else(|| eyre!("Unknown transaction {tx_hash}"))?;
+            Ok(wait_until.has_outcome().then(|| self.outcome(transaction)))
+        }
+
+        async fn simulate(&self, _method_name: &str, _args: &[u8]) -> Result<()> {
+            match self.failure {
+                Some(ref error) => Err(eyre!("{error}")),
+                None => Ok(()),
+            }
+        }
+    }
+}
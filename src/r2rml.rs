@@ -0,0 +1,711 @@
+// This is free and unencumbered software released into the public domain.
+
+//! A subset of the [R2RML](https://www.w3.org/TR/r2rml/) mapping language,
+//! generating statements from the rows of SQL queries.
+//!
+//! Supported are triples maps with a `rr:tableName` or `rr:sqlQuery` logical
+//! table, and term maps with `rr:constant`, `rr:column` or `rr:template`
+//! values (including the `rr:subject`, `rr:predicate`, `rr:object` and
+//! `rr:graph` shortcuts), `rr:termType`, `rr:language`, `rr:datatype` and
+//! `rr:class`. Referencing object maps (`rr:parentTriplesMap`) are not.
+
+use eyre::{bail, eyre, Context as _, Result};
+use oxrdf::{
+    BlankNode, Graph, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Subject, SubjectRef,
+    Term, TermRef, Triple,
+};
+use std::path::Path;
+
+const RR: &str = "http://www.w3.org/ns/r2rml#";
+
+/// The values of a row returned by the database, by column name.
+pub trait Row {
+    /// Returns the value of the column in its text form, or `None` if it is null.
+    fn value(&self, column: &str) -> Result<Option<&str>>;
+}
+
+/// An R2RML mapping, made of triples maps.
+#[derive(Clone, Debug)]
+pub struct Mapping {
+    pub triples_maps: Vec<TriplesMap>,
+}
+
+/// Maps each row of a logical table to statements about one subject.
+#[derive(Clone, Debug)]
+pub struct TriplesMap {
+    /// The SQL query selecting the rows of the logical table.
+    pub query: String,
+    pub subject: TermMap,
+    pub classes: Vec<NamedNode>,
+    pub graphs: Vec<TermMap>,
+    pub predicate_objects: Vec<PredicateObjectMap>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PredicateObjectMap {
+    pub predicates: Vec<TermMap>,
+    pub objects: Vec<TermMap>,
+    pub graphs: Vec<TermMap>,
+}
+
+/// Generates a term from a row.
+#[derive(Clone, Debug)]
+pub struct TermMap {
+    pub value: TermValue,
+    pub term_type: TermType,
+    pub language: Option<String>,
+    pub datatype: Option<NamedNode>,
+}
+
+#[derive(Clone, Debug)]
+pub enum TermValue {
+    Constant(Term),
+    Column(String),
+    Template(Vec<TemplatePart>),
+}
+
+#[derive(Clone, Debug)]
+pub enum TemplatePart {
+    Text(String),
+    Column(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TermType {
+    Iri,
+    BlankNode,
+    Literal,
+}
+
+/// The position of a term map in a triples map, which determines its default term type.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Position {
+    Subject,
+    Predicate,
+    Object,
+    Graph,
+}
+
+impl Mapping {
+    /// Reads a mapping from a Turtle file.
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let file = std::fs::File::open(path).context("Failed to open R2RML mapping")?;
+        let mut graph = Graph::new();
+        for quad in oxrdfio::RdfParser::from_format(oxrdfio::RdfFormat::Turtle).for_reader(file) {
+            let quad = quad.context("Failed to parse R2RML mapping")?;
+            graph.insert(&Triple::from(quad));
+        }
+        Self::from_graph(&graph)
+    }
+
+    /// Reads the mapping from its RDF graph, where every subject with a
+    /// `rr:logicalTable` is a triples map.
+    pub fn from_graph(graph: &Graph) -> Result<Self> {
+        let logical_table = rr("logicalTable");
+        let mut nodes: Vec<SubjectRef> = graph
+            .triples_for_predicate(logical_table.as_ref())
+            .map(|triple| triple.subject)
+            .collect();
+        nodes.dedup();
+
+        let triples_maps = nodes
+            .into_iter()
+            .map(|node| {
+                TriplesMap::from_graph(graph, node)
+                    .with_context(|| format!("Invalid triples map {node}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if triples_maps.is_empty() {
+            bail!("The R2RML mapping has no triples maps");
+        }
+        Ok(Self { triples_maps })
+    }
+}
+
+impl TriplesMap {
+    fn from_graph(graph: &Graph, node: SubjectRef) -> Result<Self> {
+        let table = object(graph, node, "logicalTable")
+            .and_then(as_subject)
+            .ok_or_else(|| eyre!("Invalid rr:logicalTable"))?;
+        let query = match (
+            string(graph, table, "sqlQuery"),
+            string(graph, table, "tableName"),
+        ) {
+            (Some(query), _) => query.trim().trim_end_matches(';').to_string(),
+            (None, Some(table)) => format!("SELECT * FROM {table}"),
+            (None, None) => bail!("The logical table has neither rr:sqlQuery nor rr:tableName"),
+        };
+
+        let (subject_map, subject) = match object(graph, node, "subject") {
+            Some(constant) => (None, TermMap::constant(constant.into_owned())),
+            None => {
+                let subject_map = object(graph, node, "subjectMap")
+                    .and_then(as_subject)
+                    .ok_or_else(|| eyre!("Missing rr:subjectMap"))?;
+                (
+                    Some(subject_map),
+                    TermMap::from_graph(graph, subject_map, Position::Subject)?,
+                )
+            }
+        };
+        if subject.term_type == TermType::Literal {
+            bail!("The subject map must generate IRIs or blank nodes");
+        }
+
+        let mut classes = Vec::new();
+        let mut graphs = Vec::new();
+        if let Some(subject_map) = subject_map {
+            for class in objects(graph, subject_map, "class") {
+                match class {
+                    TermRef::NamedNode(class) => classes.push(class.into_owned()),
+                    _ => bail!("Invalid rr:class {class}"),
+                }
+            }
+            graphs = graph_maps(graph, subject_map)?;
+        }
+
+        let predicate_objects = objects(graph, node, "predicateObjectMap")
+            .map(|map| {
+                let map = as_subject(map).ok_or_else(|| eyre!("Invalid rr:predicateObjectMap"))?;
+                PredicateObjectMap::from_graph(graph, map)
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            query,
+            subject,
+            classes,
+            graphs,
+            predicate_objects,
+        })
+    }
+
+    /// Appends the statements generated from a row to `quads`.
+    pub fn row_quads(&self, row: &impl Row, quads: &mut Vec<Quad>) -> Result<()> {
+        let Some(subject) = self.subject.subject(row)? else {
+            return Ok(());
+        };
+        let graphs = graph_names(&self.graphs, row)?;
+
+        let rdf_type = NamedNode::from(oxrdf::vocab::rdf::TYPE);
+        for class in &self.classes {
+            for graph in &graphs {
+                quads.push(Quad::new(
+                    subject.clone(),
+                    rdf_type.clone(),
+                    class.clone(),
+                    graph.clone(),
+                ));
+            }
+        }
+
+        for map in &self.predicate_objects {
+            let mut map_graphs = graph_names(&map.graphs, row)?;
+            if map.graphs.is_empty() {
+                map_graphs = graphs.clone();
+            } else if !self.graphs.is_empty() {
+                map_graphs.extend(graphs.iter().cloned());
+            }
+            for predicate in &map.predicates {
+                let Some(predicate) = predicate.named_node(row)? else {
+                    continue;
+                };
+                for object in &map.objects {
+                    let Some(object) = object.term(row)? else {
+                        continue;
+                    };
+                    for graph in &map_graphs {
+                        quads.push(Quad::new(
+                            subject.clone(),
+                            predicate.clone(),
+                            object.clone(),
+                            graph.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl PredicateObjectMap {
+    fn from_graph(graph: &Graph, node: SubjectRef) -> Result<Self> {
+        let predicates = term_maps(
+            graph,
+            node,
+            "predicate",
+            "predicateMap",
+            Position::Predicate,
+        )?;
+        let objects = term_maps(graph, node, "object", "objectMap", Position::Object)?;
+        if predicates.is_empty() || objects.is_empty() {
+            bail!("A predicate-object map needs predicates and objects");
+        }
+        Ok(Self {
+            predicates,
+            objects,
+            graphs: graph_maps(graph, node)?,
+        })
+    }
+}
+
+impl TermMap {
+    fn constant(term: Term) -> Self {
+        let term_type = match term {
+            Term::NamedNode(_) => TermType::Iri,
+            Term::BlankNode(_) => TermType::BlankNode,
+            _ => TermType::Literal,
+        };
+        Self {
+            value: TermValue::Constant(term),
+            term_type,
+            language: None,
+            datatype: None,
+        }
+    }
+
+    fn from_graph(graph: &Graph, node: SubjectRef, position: Position) -> Result<Self> {
+        if object(graph, node, "parentTriplesMap").is_some() {
+            bail!("Referencing object maps (rr:parentTriplesMap) are not supported");
+        }
+        if let Some(constant) = object(graph, node, "constant") {
+            return Ok(Self::constant(constant.into_owned()));
+        }
+
+        let language = string(graph, node, "language");
+        let datatype = match object(graph, node, "datatype") {
+            Some(TermRef::NamedNode(datatype)) => Some(datatype.into_owned()),
+            Some(datatype) => bail!("Invalid rr:datatype {datatype}"),
+            None => None,
+        };
+        let (value, default_type) = match (
+            string(graph, node, "column"),
+            string(graph, node, "template"),
+        ) {
+            (Some(column), _) => (TermValue::Column(unquote(&column)), TermType::Literal),
+            (None, Some(template)) => (
+                TermValue::Template(parse_template(&template)?),
+                TermType::Iri,
+            ),
+            (None, None) => bail!("A term map needs rr:constant, rr:column or rr:template"),
+        };
+        let default_type = match position {
+            Position::Object if language.is_some() || datatype.is_some() => TermType::Literal,
+            Position::Object => default_type,
+            _ => TermType::Iri,
+        };
+        let term_type = match object(graph, node, "termType") {
+            Some(TermRef::NamedNode(term_type)) if term_type == rr("IRI").as_ref() => TermType::Iri,
+            Some(TermRef::NamedNode(term_type)) if term_type == rr("BlankNode").as_ref() => {
+                TermType::BlankNode
+            }
+            Some(TermRef::NamedNode(term_type)) if term_type == rr("Literal").as_ref() => {
+                TermType::Literal
+            }
+            Some(term_type) => bail!("Invalid rr:termType {term_type}"),
+            None => default_type,
+        };
+        let allowed = match position {
+            Position::Subject => term_type != TermType::Literal,
+            Position::Predicate | Position::Graph => term_type == TermType::Iri,
+            Position::Object => true,
+        };
+        if !allowed {
+            bail!("Invalid term type {term_type:?} for the term map");
+        }
+
+        Ok(Self {
+            value,
+            term_type,
+            language,
+            datatype,
+        })
+    }
+
+    /// Returns the term generated from a row, or `None` if a column is null.
+    pub fn term(&self, row: &impl Row) -> Result<Option<Term>> {
+        let value = match self.value {
+            TermValue::Constant(ref term) => return Ok(Some(term.clone())),
+            TermValue::Column(ref column) => match row.value(column)? {
+                Some(value) => value.to_string(),
+                None => return Ok(None),
+            },
+            TermValue::Template(ref parts) => {
+                let mut value = String::new();
+                for part in parts {
+                    match part {
+                        TemplatePart::Text(text) => value.push_str(text),
+                        TemplatePart::Column(column) => match row.value(column)? {
+                            Some(column) if self.term_type == TermType::Iri => {
+                                value.push_str(&iri_safe(column))
+                            }
+                            Some(column) => value.push_str(column),
+                            None => return Ok(None),
+                        },
+                    }
+                }
+                value
+            }
+        };
+
+        let term = match self.term_type {
+            TermType::Iri => NamedNode::new(&value)
+                .with_context(|| format!("Invalid generated IRI `{value}`"))?
+                .into(),
+            TermType::BlankNode => {
+                // Blank node labels can't contain most punctuation:
+                let label: String = value
+                    .chars()
+                    .map(|c| {
+                        if c.is_alphanumeric() || c == '-' {
+                            c
+                        } else {
+                            '_'
+                        }
+                    })
+                    .collect();
+                BlankNode::new(label)
+                    .with_context(|| format!("Invalid generated blank node `{value}`"))?
+                    .into()
+            }
+            TermType::Literal => match (&self.language, &self.datatype) {
+                (Some(language), _) => Literal::new_language_tagged_literal(value, language)
+                    .with_context(|| format!("Invalid language tag `{language}`"))?
+                    .into(),
+                (None, Some(datatype)) => {
+                    Literal::new_typed_literal(value, datatype.clone()).into()
+                }
+                (None, None) => Literal::new_simple_literal(value).into(),
+            },
+        };
+        Ok(Some(term))
+    }
+
+    fn subject(&self, row: &impl Row) -> Result<Option<Subject>> {
+        Ok(match self.term(row)? {
+            Some(Term::NamedNode(node)) => Some(node.into()),
+            Some(Term::BlankNode(node)) => Some(node.into()),
+            Some(term) => bail!("Invalid generated subject {term}"),
+            None => None,
+        })
+    }
+
+    fn named_node(&self, row: &impl Row) -> Result<Option<NamedNode>> {
+        Ok(match self.term(row)? {
+            Some(Term::NamedNode(node)) => Some(node),
+            Some(term) => bail!("Invalid generated IRI {term}"),
+            None => None,
+        })
+    }
+}
+
+fn rr(term: &str) -> NamedNode {
+    NamedNode::new_unchecked(format!("{RR}{term}"))
+}
+
+fn object<'a>(graph: &'a Graph, node: SubjectRef<'a>, predicate: &str) -> Option<TermRef<'a>> {
+    graph.object_for_subject_predicate(node, rr(predicate).as_ref())
+}
+
+fn objects<'a>(
+    graph: &'a Graph,
+    node: SubjectRef<'a>,
+    predicate: &str,
+) -> impl Iterator<Item = TermRef<'a>> {
+    let predicate = rr(predicate);
+    graph
+        .objects_for_subject_predicate(node, predicate.as_ref())
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+fn string(graph: &Graph, node: SubjectRef, predicate: &str) -> Option<String> {
+    match object(graph, node, predicate)? {
+        TermRef::Literal(literal) => Some(literal.value().to_string()),
+        _ => None,
+    }
+}
+
+fn as_subject(term: TermRef) -> Option<SubjectRef> {
+    match term {
+        TermRef::NamedNode(node) => Some(node.into()),
+        TermRef::BlankNode(node) => Some(node.into()),
+        _ => None,
+    }
+}
+
+/// Returns the term maps given with either the constant shortcut or the map property.
+fn term_maps(
+    graph: &Graph,
+    node: SubjectRef,
+    shortcut: &str,
+    property: &str,
+    position: Position,
+) -> Result<Vec<TermMap>> {
+    let mut maps: Vec<TermMap> = objects(graph, node, shortcut)
+        .map(|constant| TermMap::constant(constant.into_owned()))
+        .collect();
+    for map in objects(graph, node, property) {
+        let map = as_subject(map).ok_or_else(|| eyre!("Invalid rr:{property}"))?;
+        maps.push(TermMap::from_graph(graph, map, position)?);
+    }
+    Ok(maps)
+}
+
+fn graph_maps(graph: &Graph, node: SubjectRef) -> Result<Vec<TermMap>> {
+    term_maps(graph, node, "graph", "graphMap", Position::Graph)
+}
+
+/// Returns the graph names generated from a row, the default graph if there are no graph maps.
+fn graph_names(maps: &[TermMap], row: &impl Row) -> Result<Vec<GraphName>> {
+    if maps.is_empty() {
+        return Ok(vec![GraphName::DefaultGraph]);
+    }
+    let default_graph = rr("defaultGraph");
+    let mut graphs = Vec::new();
+    for map in maps {
+        match map.named_node(row)? {
+            Some(graph) if graph == default_graph => graphs.push(GraphName::DefaultGraph),
+            Some(graph) => graphs.push(NamedOrBlankNode::from(graph).into()),
+            None => {}
+        }
+    }
+    Ok(graphs)
+}
+
+/// Splits a string template such as `http://example.org/{id}` into text and column references.
+fn parse_template(template: &str) -> Result<Vec<TemplatePart>> {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '{' => {
+                let mut column = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some('\\') => column.extend(chars.next()),
+                        Some(c) => column.push(c),
+                        None => bail!("Unclosed column reference in template `{template}`"),
+                    }
+                }
+                if !text.is_empty() {
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(TemplatePart::Column(unquote(&column)));
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        parts.push(TemplatePart::Text(text));
+    }
+    Ok(parts)
+}
+
+/// Removes the double quotes of a delimited SQL identifier.
+fn unquote(column: &str) -> String {
+    column
+        .strip_prefix('"')
+        .and_then(|column| column.strip_suffix('"'))
+        .unwrap_or(column)
+        .to_string()
+}
+
+/// Percent-encodes the characters of a value that are not unreserved in IRIs.
+fn iri_safe(value: &str) -> String {
+    let mut safe = String::with_capacity(value.len());
+    for c in value.chars() {
+        if c.is_alphanumeric() || matches!(c, '-' | '.' | '_' | '~') {
+            safe.push(c);
+        } else {
+            let mut bytes = [0; 4];
+            for byte in c.encode_utf8(&mut bytes).bytes() {
+                safe.push_str(&format!("%{byte:02X}"));
+            }
+        }
+    }
+    safe
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFIXES: &str = "@prefix rr: <http://www.w3.org/ns/r2rml#> .\n\
+                            @prefix ex: <http://example.org/> .\n";
+
+    struct TestRow(&'static [(&'static str, &'static str)]);
+
+    impl Row for TestRow {
+        fn value(&self, column: &str) -> Result<Option<&str>> {
+            Ok(self
+                .0
+                .iter()
+                .find(|(name, _)| *name == column)
+                .map(|(_, value)| *value))
+        }
+    }
+
+    fn mapping(turtle: &str) -> Result<Mapping> {
+        let turtle = format!("{PREFIXES}{turtle}");
+        let mut graph = Graph::new();
+        for quad in oxrdfio::RdfParser::from_format(oxrdfio::RdfFormat::Turtle)
+            .for_reader(turtle.as_bytes())
+        {
+            graph.insert(&Triple::from(quad.unwrap()));
+        }
+        Mapping::from_graph(&graph)
+    }
+
+    fn error(turtle: &str) -> String {
+        mapping(turtle).unwrap_err().root_cause().to_string()
+    }
+
+    fn ex(name: &str) -> NamedNode {
+        NamedNode::new_unchecked(format!("http://example.org/{name}"))
+    }
+
+    #[test]
+    fn maps_subjects_and_predicate_objects() {
+        let mapping = mapping(
+            "ex:People rr:logicalTable [ rr:tableName \"people\" ] ;
+                rr:subjectMap [ rr:template \"http://example.org/person/{id}\" ; rr:class ex:Person ] ;
+                rr:predicateObjectMap [
+                    rr:predicate ex:name ;
+                    rr:objectMap [ rr:column \"name\" ; rr:language \"en\" ]
+                ] .",
+        )
+        .unwrap();
+        let [ref map] = mapping.triples_maps[..] else {
+            panic!("expected a single triples map");
+        };
+        assert_eq!(map.query, "SELECT * FROM people");
+
+        let mut quads = Vec::new();
+        map.row_quads(&TestRow(&[("id", "1 a"), ("name", "Ann")]), &mut quads)
+            .unwrap();
+        let person = ex("person/1%20a");
+        assert_eq!(
+            quads,
+            [
+                Quad::new(
+                    person.clone(),
+                    oxrdf::vocab::rdf::TYPE,
+                    ex("Person"),
+                    GraphName::DefaultGraph,
+                ),
+                Quad::new(
+                    person,
+                    ex("name"),
+                    Literal::new_language_tagged_literal_unchecked("Ann", "en"),
+                    GraphName::DefaultGraph,
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn maps_queries_graphs_and_null_columns() {
+        let mapping = mapping(
+            "ex:Items rr:logicalTable [ rr:sqlQuery \"SELECT id, price FROM items;\\n\" ] ;
+                rr:subjectMap [ rr:column \"id\" ; rr:termType rr:BlankNode ; rr:graph ex:g ] ;
+                rr:predicateObjectMap [
+                    rr:predicateMap [ rr:constant ex:price ] ;
+                    rr:objectMap [ rr:column \"price\" ; rr:datatype ex:decimal ]
+                ] .",
+        )
+        .unwrap();
+        let map = &mapping.triples_maps[0];
+        assert_eq!(map.query, "SELECT id, price FROM items");
+        assert_eq!(map.subject.term_type, TermType::BlankNode);
+
+        let mut quads = Vec::new();
+        map.row_quads(&TestRow(&[("id", "a.1"), ("price", "9.5")]), &mut quads)
+            .unwrap();
+        assert_eq!(
+            quads,
+            [Quad::new(
+                BlankNode::new_unchecked("a_1"),
+                ex("price"),
+                Literal::new_typed_literal("9.5", ex("decimal")),
+                NamedOrBlankNode::from(ex("g")),
+            )]
+        );
+
+        // Rows with a null column generate no statement from it:
+        quads.clear();
+        map.row_quads(&TestRow(&[("id", "b")]), &mut quads).unwrap();
+        assert!(quads.is_empty());
+        map.row_quads(&TestRow(&[("price", "1")]), &mut quads)
+            .unwrap();
+        assert!(quads.is_empty());
+    }
+
+    #[test]
+    fn parses_templates() {
+        let parts = parse_template(r#"http://example.org/\{x\}/{"Id"}-{n}"#).unwrap();
+        assert!(matches!(
+            &parts[..],
+            [
+                TemplatePart::Text(a),
+                TemplatePart::Column(b),
+                TemplatePart::Text(c),
+                TemplatePart::Column(d),
+            ] if a == "http://example.org/{x}/" && b == "Id" && c == "-" && d == "n"
+        ));
+        assert!(parse_template("http://example.org/{id").is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_mappings() {
+        assert_eq!(
+            error("ex:a ex:b ex:c ."),
+            "The R2RML mapping has no triples maps"
+        );
+        assert_eq!(
+            error("ex:M rr:logicalTable [ rr:tableName \"t\" ] ."),
+            "Missing rr:subjectMap"
+        );
+        assert_eq!(
+            error("ex:M rr:logicalTable [ ex:a ex:b ] ; rr:subject ex:s ."),
+            "The logical table has neither rr:sqlQuery nor rr:tableName"
+        );
+        assert_eq!(
+            error(
+                "ex:M rr:logicalTable [ rr:tableName \"t\" ] ;
+                    rr:subjectMap [ rr:column \"id\" ; rr:termType rr:Literal ] ."
+            ),
+            "Invalid term type Literal for the term map"
+        );
+        assert_eq!(
+            error(
+                "ex:M rr:logicalTable [ rr:tableName \"t\" ] ;
+                    rr:subject ex:s ;
+                    rr:predicateObjectMap [
+                        rr:predicate ex:p ;
+                        rr:objectMap [ rr:parentTriplesMap ex:Other ]
+                    ] ."
+            ),
+            "Referencing object maps (rr:parentTriplesMap) are not supported"
+        );
+        assert_eq!(
+            error(
+                "ex:M rr:logicalTable [ rr:tableName \"t\" ] ;
+                    rr:subject ex:s ;
+                    rr:predicateObjectMap [ rr:predicate ex:p ] ."
+            ),
+            "A predicate-object map needs predicates and objects"
+        );
+        assert_eq!(
+            error(
+                "ex:M rr:logicalTable [ rr:tableName \"t\" ] ;
+                    rr:subjectMap [ rr:template \"http://example.org/{id\" ] ."
+            ),
+            "Unclosed column reference in template `http://example.org/{id`"
+        );
+    }
+}
@@ -0,0 +1,599 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{
+    near_primitives::{
+        errors::InvalidTxError,
+        hash::CryptoHash,
+        transaction::SignedTransaction,
+        types::{BlockId, BlockReference, Finality, FunctionArgs, Nonce},
+        views::{AccessKeyList, AccessKeyView, AccountView, QueryRequest, TxExecutionStatus},
+    },
+    AccountId, NetworkConfig, RPCEndpoint,
+};
+use near_crypto::PublicKey;
+use near_jsonrpc_client::{
+    errors::JsonRpcError,
+    methods::{
+        block::RpcBlockRequest,
+        query::{RpcQueryError, RpcQueryRequest},
+        send_tx::{RpcSendTransactionRequest, RpcTransactionError, RpcTransactionResponse},
+        tx::{RpcTransactionStatusRequest, TransactionInfo},
+        EXPERIMENTAL_protocol_config::RpcProtocolConfigRequest,
+        RpcMethod,
+    },
+    JsonRpcClient, JsonRpcClientConnector,
+};
+use near_jsonrpc_primitives::types::query::QueryResponseKind;
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+    time::Duration,
+};
+
+use crate::config;
+
+pub use crate::finality::WaitUntil;
+
+/// The HTTP settings of the JSON-RPC clients, e.g. to fail fast on flaky
+/// endpoints instead of waiting on them for as long as the OS lets a
+/// connection hang.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RpcOptions {
+    /// The time to wait for a response, from connecting until the body is read.
+    pub timeout: Option<Duration>,
+    /// The time to wait for a connection to the endpoint.
+    pub connect_timeout: Option<Duration>,
+    /// The idle time before TCP keep-alive probes are sent, zero disabling
+    /// them.
+    pub keep_alive: Option<Duration>,
+    /// The time an idle connection is kept open to be reused, zero closing
+    /// connections once idle.
+    pub pool_idle_timeout: Option<Duration>,
+    /// The URL of the proxy to send requests through, except to the hosts
+    /// in `NO_PROXY`. Without one, the proxies of the `HTTPS_PROXY`,
+    /// `HTTP_PROXY` and `ALL_PROXY` environment variables are used.
+    pub proxy: Option<String>,
+    /// A PEM file of certificates to trust in addition to the system's,
+    /// e.g. of the private CA of an internally hosted RPC node.
+    pub ca_cert: Option<PathBuf>,
+    /// A PEM file of the certificate chain authenticating the client, with
+    /// its PKCS #8 private key in `client_key`.
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    /// The URLs of the RPC endpoints to use instead of the network's public
+    /// ones, e.g. of an internally hosted node, in the order to try them.
+    pub endpoints: Vec<String>,
+    /// The headers sent with every request, e.g. the API key of a paid RPC
+    /// provider.
+    pub headers: Vec<(String, String)>,
+    /// The directory to write the request and response of each call to, to
+    /// trace them for a bug report.
+    pub debug_dir: Option<PathBuf>,
+}
+
+static CONNECTOR: OnceLock<JsonRpcClientConnector> = OnceLock::new();
+
+static ENDPOINTS: OnceLock<Vec<RPCEndpoint>> = OnceLock::new();
+
+static DEBUG_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// The number of calls written to the debug directory.
+static DEBUG_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+/// Configures the JSON-RPC clients of all the following calls, and the
+/// endpoints of the networks they are sent to.
+///
+/// The transactions sent through near-api, such as contract deployments,
+/// keep its default client.
+pub fn configure(options: &RpcOptions) -> Result<()> {
+    if let Some(ref dir) = options.debug_dir {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+        DEBUG_DIR
+            .set(dir.clone())
+            .map_err(|_| eyre!("The RPC debug directory is already configured"))?;
+    }
+    if !options.endpoints.is_empty() {
+        let endpoints = options
+            .endpoints
+            .iter()
+            .map(|url| {
+                url.parse()
+                    .map(RPCEndpoint::new)
+                    .map_err(|err| eyre!("Invalid RPC endpoint `{url}`: {err}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        ENDPOINTS
+            .set(endpoints)
+            .map_err(|_| eyre!("The RPC endpoints are already configured"))?;
+    }
+    let mut headers = reqwest::header::HeaderMap::with_capacity(1 + options.headers.len());
+    headers.insert(
+        reqwest::header::CONTENT_TYPE,
+        reqwest::header::HeaderValue::from_static("application/json"),
+    );
+    for (name, value) in &options.headers {
+        let name = reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .map_err(|err| eyre!("Invalid RPC header name `{name}`: {err}"))?;
+        let mut value = reqwest::header::HeaderValue::from_str(value)
+            .map_err(|err| eyre!("Invalid value of RPC header `{name}`: {err}"))?;
+        // Keep API keys out of debug logs:
+        value.set_sensitive(true);
+        headers.append(name, value);
+    }
+    let mut builder = reqwest::Client::builder().default_headers(headers);
+    if let Some(timeout) = options.timeout {
+        builder = builder.timeout(timeout);
+    }
+    if let Some(connect_timeout) = options.connect_timeout {
+        builder = builder.connect_timeout(connect_timeout);
+    }
+    if let Some(keep_alive) = options.keep_alive {
+        builder = builder.tcp_keepalive(Some(keep_alive).filter(|interval| !interval.is_zero()));
+    }
+    if let Some(ref proxy) = options.proxy {
+        let proxy = reqwest::Proxy::all(proxy.as_str())
+            .map_err(|err| eyre!("Invalid proxy `{proxy}`: {err}"))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+    if let Some(ref path) = options.ca_cert {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificate {}", path.display()))?;
+        for certificate in reqwest::Certificate::from_pem_bundle(&pem)
+            .map_err(|err| eyre!("Invalid CA certificate {}: {err}", path.display()))?
+        {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    match (&options.client_cert, &options.client_key) {
+        (Some(cert_path), Some(key_path)) => {
+            let cert = std::fs::read(cert_path).with_context(|| {
+                format!("Failed to read client certificate {}", cert_path.display())
+            })?;
+            let key = std::fs::read(key_path)
+                .with_context(|| format!("Failed to read client key {}", key_path.display()))?;
+            let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key).map_err(|err| {
+                eyre!("Invalid client certificate {}: {err}", cert_path.display())
+            })?;
+            builder = builder.identity(identity);
+        }
+        (None, None) => {}
+        _ => bail!("A client certificate and its key are both required"),
+    }
+    match options.pool_idle_timeout {
+        Some(timeout) if timeout.is_zero() => builder = builder.pool_max_idle_per_host(0),
+        Some(timeout) => builder = builder.pool_idle_timeout(timeout),
+        None => {}
+    }
+    let client = builder
+        .build()
+        .map_err(|err| eyre!("Failed to configure the RPC client: {err}"))?;
+    CONNECTOR
+        .set(JsonRpcClient::with(client))
+        .map_err(|_| eyre!("The RPC client is already configured"))
+}
+
+/// Returns the status the RPC node waits for at the finality level.
+fn execution_status(wait_until: WaitUntil) -> TxExecutionStatus {
+    match wait_until {
+        WaitUntil::Included => TxExecutionStatus::Included,
+        WaitUntil::Optimistic => TxExecutionStatus::ExecutedOptimistic,
+        WaitUntil::Executed => TxExecutionStatus::Executed,
+        WaitUntil::Final => TxExecutionStatus::Final,
+    }
+}
+
+/// Returns the explorer URL of a transaction, if the network has a known explorer.
+pub fn explorer_url(network: &NetworkConfig, tx_hash: &CryptoHash) -> Option<String> {
+    match network.network_name.as_str() {
+        "mainnet" => Some(format!("https://nearblocks.io/txns/{tx_hash}")),
+        "testnet" => Some(format!("https://testnet.nearblocks.io/txns/{tx_hash}")),
+        _ => None,
+    }
+}
+
+/// Connects a JSON-RPC client to the endpoint.
+pub fn connect(endpoint: &RPCEndpoint) -> JsonRpcClient {
+    let client = match CONNECTOR.get() {
+        Some(connector) => connector.connect(endpoint.url.as_str()),
+        None => JsonRpcClient::connect(endpoint.url.as_str()),
+    };
+    match endpoint.api_key {
+        Some(ref api_key) => {
+            client.header(near_jsonrpc_client::auth::ApiKey::from(api_key.clone()))
+        }
+        None => client,
+    }
+}
+
+/// Sends a signed transaction and waits until it reaches the given finality,
+/// or returns as soon as it is accepted if no finality is given.
+pub async fn send_transaction(
+    network: &NetworkConfig,
+    signed_transaction: SignedTransaction,
+    wait_until: Option<WaitUntil>,
+) -> Result<RpcTransactionResponse> {
+    call(network, || RpcSendTransactionRequest {
+        signed_transaction: signed_transaction.clone(),
+        wait_until: wait_until.map_or(TxExecutionStatus::None, execution_status),
+    })
+    .await
+}
+
+/// Returns the nonce of the access key if the error is the rejection of a
+/// transaction whose nonce isn't greater than it.
+pub fn invalid_nonce(err: &eyre::Report) -> Option<Nonce> {
+    match err
+        .downcast_ref::<JsonRpcError<RpcTransactionError>>()
+        .and_then(JsonRpcError::handler_error)
+    {
+        Some(RpcTransactionError::InvalidTransaction {
+            context: InvalidTxError::InvalidNonce { ak_nonce, .. },
+        }) => Some(*ak_nonce),
+        _ => None,
+    }
+}
+
+/// Waits until a previously sent transaction reaches the given finality.
+pub async fn transaction_status(
+    network: &NetworkConfig,
+    tx_hash: CryptoHash,
+    sender_account_id: AccountId,
+    wait_until: WaitUntil,
+) -> Result<RpcTransactionResponse> {
+    call(network, || RpcTransactionStatusRequest {
+        transaction_info: TransactionInfo::TransactionId {
+            tx_hash,
+            sender_account_id: sender_account_id.clone(),
+        },
+        wait_until: execution_status(wait_until),
+    })
+    .await
+}
+
+/// The block whose state is read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Block {
+    /// The last final block.
+    #[default]
+    Final,
+    /// The block at a past height, whose state only archival nodes keep.
+    Height(u64),
+}
+
+impl Block {
+    /// Returns the block at the height, if any, or else the last final block.
+    pub fn at(height: Option<u64>) -> Self {
+        height.map_or(Self::Final, Self::Height)
+    }
+
+    fn reference(self) -> BlockReference {
+        match self {
+            Self::Final => BlockReference::Finality(Finality::Final),
+            Self::Height(height) => BlockReference::BlockId(BlockId::Height(height)),
+        }
+    }
+}
+
+/// Returns the height of the block with the given hash.
+pub async fn block_height(network: &NetworkConfig, block_hash: CryptoHash) -> Result<u64> {
+    let block = call(network, || RpcBlockRequest {
+        block_reference: BlockReference::BlockId(BlockId::Hash(block_hash)),
+    })
+    .await?;
+    Ok(block.header.height)
+}
+
+/// Returns the height of the last final block.
+pub async fn final_block_height(network: &NetworkConfig) -> Result<u64> {
+    let block = call(network, || RpcBlockRequest {
+        block_reference: BlockReference::Finality(Finality::Final),
+    })
+    .await?;
+    Ok(block.header.height)
+}
+
+/// Gas in the delayed receipts of a shard at which it is fully congested,
+/// the protocol's `max_congestion_incoming_gas`.
+const MAX_CONGESTION_INCOMING_GAS: u128 = 20_000_000_000_000_000;
+
+/// Gas in the buffered outgoing receipts of a shard at which it is fully
+/// congested, the protocol's `max_congestion_outgoing_gas`.
+const MAX_CONGESTION_OUTGOING_GAS: u128 = 10_000_000_000_000_000;
+
+/// Size of the receipts of a shard at which it is fully congested, the
+/// protocol's `max_congestion_memory_consumption`.
+const MAX_CONGESTION_MEMORY_CONSUMPTION: u64 = 1_000_000_000;
+
+/// The load of a network in a block.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NetworkLoad {
+    /// The gas price, in yoctoNEAR.
+    pub gas_price: u128,
+    /// The congestion level of the most congested shard, from 0 (none) to 1
+    /// (fully congested).
+    pub congestion: f64,
+}
+
+/// Returns the gas price and the shard congestion of the block.
+pub async fn network_load(network: &NetworkConfig, block: Block) -> Result<NetworkLoad> {
+    let block = call(network, || RpcBlockRequest {
+        block_reference: block.reference(),
+    })
+    .await?;
+    let congestion = block
+        .chunks
+        .iter()
+        .filter_map(|chunk| chunk.congestion_info.as_ref())
+        .map(|info| {
+            let incoming = info.delayed_receipts_gas as f64 / MAX_CONGESTION_INCOMING_GAS as f64;
+            let outgoing = info.buffered_receipts_gas as f64 / MAX_CONGESTION_OUTGOING_GAS as f64;
+            let memory = info.receipt_bytes as f64 / MAX_CONGESTION_MEMORY_CONSUMPTION as f64;
+            incoming.max(outgoing).max(memory).min(1.0)
+        })
+        .fold(0.0, f64::max);
+    Ok(NetworkLoad {
+        gas_price: block.header.gas_price,
+        congestion,
+    })
+}
+
+/// Returns the max size of a transaction, in bytes, in the protocol config
+/// of the block.
+pub async fn max_transaction_size(network: &NetworkConfig, block: Block) -> Result<usize> {
+    let config = call(network, || RpcProtocolConfigRequest {
+        block_reference: block.reference(),
+    })
+    .await?;
+    Ok(config
+        .runtime_config
+        .wasm_config
+        .limit_config
+        .max_transaction_size as usize)
+}
+
+/// Returns the max size of the call arguments accepted by a contract, in
+/// bytes, as returned by its view method `method_name` called without
+/// arguments.
+pub async fn contract_max_payload(
+    network: &NetworkConfig,
+    contract: &AccountId,
+    method_name: &str,
+) -> Result<usize> {
+    let response = query(network, Block::Final, || QueryRequest::CallFunction {
+        account_id: contract.clone(),
+        method_name: method_name.into(),
+        args: FunctionArgs::from(b"{}".to_vec()),
+    })
+    .await?;
+    let QueryResponseKind::CallResult(result) = response.kind else {
+        bail!("Unexpected response to a call of {method_name}");
+    };
+    let max_payload: u64 = serde_json::from_slice(&result.result)
+        .with_context(|| format!("Unexpected result of {method_name}"))?;
+    Ok(max_payload as usize)
+}
+
+/// Returns the account as of the block.
+pub async fn view_account(
+    network: &NetworkConfig,
+    account: &AccountId,
+    block: Block,
+) -> Result<AccountView> {
+    let response = query(network, block, || QueryRequest::ViewAccount {
+        account_id: account.clone(),
+    })
+    .await?;
+    match response.kind {
+        QueryResponseKind::ViewAccount(view) => Ok(view),
+        _ => bail!("Unexpected response to a view of account {account}"),
+    }
+}
+
+/// Returns the access key of the account as of the last final block, and
+/// the hash of that block.
+pub async fn view_access_key(
+    network: &NetworkConfig,
+    account: &AccountId,
+    public_key: &PublicKey,
+) -> Result<(AccessKeyView, CryptoHash)> {
+    let response = query(network, Block::Final, || QueryRequest::ViewAccessKey {
+        account_id: account.clone(),
+        public_key: public_key.clone(),
+    })
+    .await?;
+    match response.kind {
+        QueryResponseKind::AccessKey(view) => Ok((view, response.block_hash)),
+        _ => bail!("Unexpected response to a view of access key {public_key}"),
+    }
+}
+
+/// Returns the access keys of the account as of the last final block.
+pub async fn view_access_keys(
+    network: &NetworkConfig,
+    account: &AccountId,
+) -> Result<AccessKeyList> {
+    let response = query(network, Block::Final, || QueryRequest::ViewAccessKeyList {
+        account_id: account.clone(),
+    })
+    .await?;
+    match response.kind {
+        QueryResponseKind::AccessKeyList(list) => Ok(list),
+        _ => bail!("Unexpected response to a view of the access keys of {account}"),
+    }
+}
+
+/// Queries the state of the block.
+async fn query(
+    network: &NetworkConfig,
+    block: Block,
+    request: impl Fn() -> QueryRequest,
+) -> Result<near_jsonrpc_primitives::types::query::RpcQueryResponse> {
+    call(network, || RpcQueryRequest {
+        block_reference: block.reference(),
+        request: request(),
+    })
+    .await
+}
+
+/// Simulates a function call by running it as a view call on the state of
+/// the block.
+///
+/// View calls can't modify state, so the simulation stops successfully at the
+/// first state modification. Any error raised before that, such as a missing
+/// contract or method, invalid arguments, a panic or running out of gas, fails
+/// the simulation.
+pub async fn simulate_function_call(
+    network: &NetworkConfig,
+    contract: &AccountId,
+    method_name: &str,
+    args: &[u8],
+    block: Block,
+) -> Result<()> {
+    let result = call(network, || RpcQueryRequest {
+        block_reference: block.reference(),
+        request: QueryRequest::CallFunction {
+            account_id: contract.clone(),
+            method_name: method_name.into(),
+            args: FunctionArgs::from(args.to_vec()),
+        },
+    })
+    .await;
+
+    let Err(err) = result else {
+        return Ok(());
+    };
+    match err
+        .downcast_ref::<JsonRpcError<RpcQueryError>>()
+        .and_then(JsonRpcError::handler_error)
+    {
+        Some(RpcQueryError::ContractExecutionError { vm_error, .. })
+            if vm_error.contains("ProhibitedInView") =>
+        {
+            Ok(())
+        }
+        Some(RpcQueryError::ContractExecutionError { vm_error, .. }) => Err(eyre!("{vm_error}")),
+        _ => Err(err),
+    }
+}
+
+/// Calls an RPC method, failing over to the next RPC endpoint of the network
+/// on transport errors.
+async fn call<M>(network: &NetworkConfig, method: impl Fn() -> M) -> Result<M::Response>
+where
+    M: RpcMethod,
+    M::Response: serde::Serialize,
+    JsonRpcError<M::Error>: std::error::Error + Send + Sync + 'static,
+{
+    let mut last_error = None;
+    for endpoint in &network.rpc_endpoints {
+        let request = method();
+        let method_name = request.method_name().to_string();
+        let payload = DEBUG_DIR
+            .get()
+            .map(|_| near_jsonrpc_client::methods::to_json(&request));
+        let result = connect(endpoint).call(request).await;
+        if let (Some(dir), Some(payload)) = (DEBUG_DIR.get(), payload) {
+            write_debug_call(dir, endpoint, &method_name, payload, &result);
+        }
+        match result {
+            Ok(response) => return Ok(response),
+            // The node handled and rejected the request, so don't resend it:
+            Err(err) if err.handler_error().is_some() => return Err(err.into()),
+            Err(err) => {
+                tracing::warn!(url = %endpoint.url, method = method_name, ?err, "RPC call failed");
+                last_error = Some(err);
+            }
+        }
+    }
+    Err(match last_error {
+        Some(err) => err.into(),
+        None => eyre!("No RPC endpoints configured for {}", network.network_name),
+    })
+}
+
+/// Writes a call to the debug directory as a JSON file of the endpoint, the
+/// request and the response or error, numbered in the order of the calls.
+///
+/// The endpoint is written without credentials nor query, and the headers,
+/// which may hold API keys, aren't written.
+fn write_debug_call<R: serde::Serialize, E: std::fmt::Debug>(
+    dir: &std::path::Path,
+    endpoint: &RPCEndpoint,
+    method_name: &str,
+    payload: std::io::Result<serde_json::Value>,
+    result: &Result<R, E>,
+) {
+    let mut payload = payload.unwrap_or_else(|err| format!("{err}").into());
+    // The ID is generated anew for the payload, and isn't the one sent:
+    if let Some(payload) = payload.as_object_mut() {
+        payload.remove("id");
+    }
+    let mut call = serde_json::json!({
+        "endpoint": endpoint_url(endpoint),
+        "request": payload,
+    });
+    match result {
+        Ok(response) => call["response"] = serde_json::to_value(response).unwrap_or_default(),
+        Err(err) => call["error"] = format!("{err:?}").into(),
+    }
+    let index = DEBUG_CALLS.fetch_add(1, Ordering::Relaxed);
+    let path = dir.join(format!("{index:06}-{method_name}.json"));
+    let written = serde_json::to_vec_pretty(&call)
+        .map_err(std::io::Error::from)
+        .and_then(|json| std::fs::write(&path, json));
+    if let Err(err) = written {
+        tracing::warn!(?err, path = %path.display(), "failed to write RPC call");
+    }
+}
+
+/// Returns the URL of the endpoint without credentials nor query, which
+/// may hold API keys, e.g. to report it.
+pub fn endpoint_url(endpoint: &RPCEndpoint) -> String {
+    let mut url = endpoint.url.clone();
+    let _ = url.set_username("");
+    let _ = url.set_password(None);
+    url.set_query(None);
+    url.into()
+}
+
+/// Returns the config of the named network, or of the network of the
+/// repository's top-level account if no network is named.
+pub fn network_config(network: Option<&str>, repository: &AccountId) -> Result<NetworkConfig> {
+    let network = match network {
+        Some(network) => network.to_string(),
+        None => {
+            // infer from repository accountid
+            match repository.as_str().split('.').next_back() {
+                Some("near") => "mainnet".into(),
+                Some("testnet") => "testnet".into(),
+                // Otherwise, the network of the config, if any:
+                _ => match std::env::var(config::NETWORK_VAR) {
+                    Ok(network) if !network.is_empty() => network,
+                    _ => bail!("Unable to infer network, please provide --network"),
+                },
+            }
+        }
+    };
+    named_network_config(&network)
+}
+
+/// Returns the config of the network, `mainnet` or `testnet`.
+pub fn named_network_config(network: &str) -> Result<NetworkConfig> {
+    let mut config = match network {
+        "mainnet" => near_api::NetworkConfig::mainnet(),
+        "testnet" => near_api::NetworkConfig::testnet(),
+        network => {
+            bail!("Unknown network name: {}", network);
+        }
+    };
+    if let Some(endpoints) = ENDPOINTS.get() {
+        config.rpc_endpoints = endpoints.clone();
+    }
+    Ok(config)
+}
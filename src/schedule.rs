@@ -0,0 +1,258 @@
+// This is free and unencumbered software released into the public domain.
+
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone, Timelike};
+
+/// How far ahead to look for the next run before giving up, in days.
+const MAX_LOOKAHEAD_DAYS: i64 = 5 * 366;
+
+/// A cron schedule of the form `<MINUTE> <HOUR> <DAY-OF-MONTH> <MONTH> <DAY-OF-WEEK>`,
+/// in local time.
+///
+/// Each field is `*`, a value, a range `<FROM>-<TO>` or a comma-separated
+/// list of these, each optionally followed by a step `/<STEP>`. Days of the
+/// week are numbered from 0 (Sunday) to 7 (also Sunday). As in cron, a day
+/// matches if either the day of the month or the day of the week matches
+/// when both are restricted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+    expression: String,
+    /// The matching values of each field, as bit sets.
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    any_day_of_month: bool,
+    any_day_of_week: bool,
+}
+
+impl Schedule {
+    pub fn as_str(&self) -> &str {
+        &self.expression
+    }
+
+    /// Returns the first time strictly after the given one matching the schedule.
+    pub fn next_after(&self, time: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = time.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let end = start + Duration::days(MAX_LOOKAHEAD_DAYS);
+        let mut candidate = start;
+        while candidate < end {
+            if !has(self.months, candidate.month()) || !self.matches_day(&candidate) {
+                candidate = (candidate.date() + Duration::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, candidate.hour()) {
+                candidate = candidate.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, candidate.minute()) {
+                candidate += Duration::minutes(1);
+            } else {
+                // Times skipped by a daylight saving change don't run:
+                match Local.from_local_datetime(&candidate).earliest() {
+                    Some(time) => return Some(time),
+                    None => candidate += Duration::minutes(1),
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, date: &NaiveDateTime) -> bool {
+        let day_of_month = has(self.days_of_month, date.day());
+        let day_of_week = has(self.days_of_week, date.weekday().num_days_from_sunday());
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        }
+    }
+}
+
+impl std::fmt::Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(format!(
+                "invalid schedule `{s}`, expected five fields: minute, hour, day of month, month and day of week"
+            ));
+        };
+        let mut days_of_week = parse_field(day_of_week, "day of week", 0, 7)?;
+        // Both 0 and 7 are Sunday:
+        if has(days_of_week, 7) {
+            days_of_week |= 1;
+        }
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days_of_month: parse_field(day_of_month, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            days_of_week,
+            any_day_of_month: day_of_month == "*",
+            any_day_of_week: day_of_week == "*",
+        })
+    }
+}
+
+fn has(values: u64, value: u32) -> bool {
+    values & (1 << value) != 0
+}
+
+/// Parses a cron field into the bit set of the values it matches.
+fn parse_field(field: &str, name: &str, min: usize, max: usize) -> Result<u64, String> {
+    let invalid =
+        || format!("invalid {name} `{field}` in schedule, expected values from {min} to {max}");
+    let parse = |value: &str| {
+        value
+            .parse::<usize>()
+            .ok()
+            .filter(|value| (min..=max).contains(value))
+            .ok_or_else(invalid)
+    };
+
+    let mut values = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<usize>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(invalid()),
+            },
+            None => (part, 1),
+        };
+        let (from, to) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((from, to)) => (parse(from)?, parse(to)?),
+                // A single value with a step runs from the value to the maximum:
+                None if step > 1 => (parse(range)?, max),
+                None => (parse(range)?, parse(range)?),
+            },
+        };
+        if from > to {
+            return Err(invalid());
+        }
+        for value in (from..=to).step_by(step) {
+            values |= 1 << value;
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: &str = "%Y-%m-%d %H:%M";
+
+    fn local(time: &str) -> DateTime<Local> {
+        let time = NaiveDateTime::parse_from_str(time, FORMAT).unwrap();
+        Local.from_local_datetime(&time).earliest().unwrap()
+    }
+
+    fn next(schedule: &str, after: &str) -> Option<String> {
+        let schedule: Schedule = schedule.parse().unwrap();
+        schedule
+            .next_after(local(after))
+            .map(|time| time.format(FORMAT).to_string())
+    }
+
+    #[test]
+    fn runs_daily() {
+        let schedule = "0 3 * * *";
+        assert_eq!(
+            next(schedule, "2025-01-15 02:59").unwrap(),
+            "2025-01-15 03:00"
+        );
+        // The next run is strictly after the given time:
+        assert_eq!(
+            next(schedule, "2025-01-15 03:00").unwrap(),
+            "2025-01-16 03:00"
+        );
+        assert_eq!(
+            next(schedule, "2025-01-31 10:00").unwrap(),
+            "2025-02-01 03:00"
+        );
+    }
+
+    #[test]
+    fn runs_in_steps() {
+        let schedule = "*/15 * * * *";
+        assert_eq!(
+            next(schedule, "2025-01-15 10:07").unwrap(),
+            "2025-01-15 10:15"
+        );
+        assert_eq!(
+            next(schedule, "2025-01-15 10:45").unwrap(),
+            "2025-01-15 11:00"
+        );
+        assert_eq!(
+            next("5/20 8-9 * * *", "2025-01-15 09:45").unwrap(),
+            "2025-01-16 08:05"
+        );
+    }
+
+    #[test]
+    fn runs_on_days_of_the_week() {
+        // From Friday to Monday:
+        assert_eq!(
+            next("30 9 * * 1-5", "2025-01-17 10:00").unwrap(),
+            "2025-01-20 09:30"
+        );
+        // Both 0 and 7 are Sunday:
+        assert_eq!(
+            next("0 0 * * 7", "2025-01-15 12:00").unwrap(),
+            "2025-01-19 00:00"
+        );
+        assert_eq!(
+            next("0 0 * * 0", "2025-01-15 12:00").unwrap(),
+            "2025-01-19 00:00"
+        );
+    }
+
+    #[test]
+    fn runs_on_either_restricted_day() {
+        let schedule = "0 12 1 * 1";
+        assert_eq!(
+            next(schedule, "2025-01-15 13:00").unwrap(),
+            "2025-01-20 12:00"
+        );
+        assert_eq!(
+            next(schedule, "2025-01-27 13:00").unwrap(),
+            "2025-02-01 12:00"
+        );
+    }
+
+    #[test]
+    fn runs_on_rare_days() {
+        assert_eq!(
+            next("0 0 29 2 *", "2025-01-01 00:00").unwrap(),
+            "2028-02-29 00:00"
+        );
+        assert_eq!(next("0 0 31 2 *", "2025-01-01 00:00"), None);
+    }
+
+    #[test]
+    fn parses_schedules() {
+        let schedule: Schedule = " 0  3 * *  1,3-5 ".parse().unwrap();
+        assert_eq!(schedule.to_string(), "0 3 * * 1,3-5");
+
+        for invalid in [
+            "0 3 * *",
+            "0 3 * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+            "5-1 * * * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,444 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::channel::Sender;
+use eyre::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::ui::Event;
+
+/// Max size of the request line and headers of a request.
+const MAX_HEAD_SIZE: u64 = 16 * 1024;
+
+/// Max size of the body of a request, far above that of a job request.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// How long a client may take to send its request or receive a response
+/// before the connection is dropped.
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Max number of the latest progress events of a job kept to be streamed.
+const MAX_JOB_EVENTS: usize = 10_000;
+
+/// How long a finished job is kept to be queried.
+const FINISHED_JOB_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// A prepare or publish job submitted to the server.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum JobRequest {
+    Prepare {
+        files: Vec<PathBuf>,
+        /// Directory where the prepared files are stored, a temporary one by default.
+        #[serde(default)]
+        output_dir: Option<PathBuf>,
+    },
+    Publish {
+        repository: String,
+        files: Vec<PathBuf>,
+        #[serde(default)]
+        dataset: Option<String>,
+        #[serde(default)]
+        network: Option<String>,
+        #[serde(default)]
+        signer: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// The state of a job, as returned by the API.
+#[derive(Clone, Debug, Serialize)]
+pub struct JobSummary {
+    pub id: u64,
+    pub request: JobRequest,
+    pub status: JobStatus,
+    pub error: Option<String>,
+    pub statements_read: usize,
+    pub batches_prepared: usize,
+    pub statements_published: usize,
+    pub batches_published: usize,
+    pub batches_skipped: usize,
+    pub batches_failed: usize,
+    pub prepared_files: Vec<PathBuf>,
+    pub transactions: Vec<String>,
+}
+
+impl JobSummary {
+    fn record(&mut self, event: &Event) {
+        match event {
+            Event::Reader(progress) => self.statements_read += progress.statement_count,
+            Event::Prepare(progress) => {
+                self.batches_prepared += 1;
+                self.prepared_files.push(progress.filename.clone());
+            }
+            Event::Publish(progress) if progress.published() => {
+                self.statements_published += progress.statement_count;
+                self.batches_published += 1;
+                if self.transactions.last() != Some(&progress.transaction) {
+                    self.transactions.push(progress.transaction.clone());
+                }
+            }
+            Event::PublishFailure(failure) => self.batches_failed += failure.filenames.len(),
+            Event::PublishSkipped(_) => self.batches_skipped += 1,
+            Event::Publish(_) | Event::PublishPaused(_) => {}
+        }
+    }
+}
+
+/// Runs the job with the given ID, sending its progress events to the sender.
+pub type JobRunner =
+    Arc<dyn Fn(u64, JobRequest, Sender<Event>) -> BoxFuture<'static, Result<()>> + Send + Sync>;
+
+struct Job {
+    state: Mutex<JobState>,
+    /// Notified whenever the state changes.
+    changed: Condvar,
+}
+
+struct JobState {
+    summary: JobSummary,
+    /// The latest progress events, serialized as JSON lines.
+    events: VecDeque<String>,
+    /// The number of earlier events dropped from `events`.
+    events_dropped: usize,
+    /// Whether all the progress events were received.
+    events_done: bool,
+    /// When the job finished running.
+    finished_at: Option<Instant>,
+}
+
+impl JobState {
+    fn push_event(&mut self, line: String) {
+        if self.events.len() >= MAX_JOB_EVENTS {
+            self.events.pop_front();
+            self.events_dropped += 1;
+        }
+        self.events.push_back(line);
+    }
+
+    /// Returns the events from the given index on, counting dropped ones,
+    /// and the index following them.
+    fn events_since(&self, index: usize) -> (Vec<&str>, usize) {
+        let start = index.saturating_sub(self.events_dropped);
+        let events = self.events.range(start.min(self.events.len())..);
+        (
+            events.map(String::as_str).collect(),
+            self.events_dropped + self.events.len(),
+        )
+    }
+}
+
+impl Job {
+    fn update(&self, update: impl FnOnce(&mut JobState)) {
+        update(&mut self.state.lock().unwrap());
+        self.changed.notify_all();
+    }
+}
+
+#[derive(Default)]
+struct Jobs {
+    jobs: Mutex<BTreeMap<u64, Arc<Job>>>,
+    last_id: AtomicU64,
+}
+
+impl Jobs {
+    /// Forgets the jobs that finished more than [`FINISHED_JOB_TTL`] ago.
+    fn expire(&self) {
+        self.jobs.lock().unwrap().retain(|_, job| {
+            let state = job.state.lock().unwrap();
+            !state
+                .finished_at
+                .is_some_and(|finished_at| finished_at.elapsed() > FINISHED_JOB_TTL)
+        });
+    }
+
+    fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.lock().unwrap().get(&id).cloned()
+    }
+
+    fn summaries(&self) -> Vec<JobSummary> {
+        let jobs = self.jobs.lock().unwrap();
+        jobs.values()
+            .map(|job| job.state.lock().unwrap().summary.clone())
+            .collect()
+    }
+
+    /// Starts running a job in the runtime, returning its initial summary.
+    fn submit(
+        &self,
+        request: JobRequest,
+        runner: &JobRunner,
+        runtime: &tokio::runtime::Handle,
+    ) -> JobSummary {
+        let mut jobs = self.jobs.lock().unwrap();
+        // IDs aren't reused once jobs expire:
+        let id = self.last_id.fetch_add(1, Ordering::Relaxed) + 1;
+        let summary = JobSummary {
+            id,
+            request: request.clone(),
+            status: JobStatus::Running,
+            error: None,
+            statements_read: 0,
+            batches_prepared: 0,
+            statements_published: 0,
+            batches_published: 0,
+            batches_skipped: 0,
+            batches_failed: 0,
+            prepared_files: Vec::new(),
+            transactions: Vec::new(),
+        };
+        let job = Arc::new(Job {
+            state: Mutex::new(JobState {
+                summary: summary.clone(),
+                events: VecDeque::new(),
+                events_dropped: 0,
+                events_done: false,
+                finished_at: None,
+            }),
+            changed: Condvar::new(),
+        });
+        jobs.insert(id, job.clone());
+
+        let (tx, rx) = crossbeam::channel::unbounded();
+        std::thread::spawn({
+            let job = job.clone();
+            move || {
+                for event in rx {
+                    let line = serde_json::to_string(&event).unwrap_or_default();
+                    job.update(|state| {
+                        state.summary.record(&event);
+                        state.push_event(line);
+                    });
+                }
+                job.update(|state| state.events_done = true);
+            }
+        });
+
+        let run = runner(id, request, tx);
+        runtime.spawn(async move {
+            let result = run.await;
+            if let Err(ref err) = result {
+                tracing::warn!(id, ?err, "job failed");
+            }
+            job.update(|state| {
+                state.finished_at = Some(Instant::now());
+                match result {
+                    Ok(()) => state.summary.status = JobStatus::Succeeded,
+                    Err(err) => {
+                        state.summary.status = JobStatus::Failed;
+                        state.summary.error = Some(format!("{err:#}"));
+                    }
+                }
+            });
+        });
+
+        summary
+    }
+}
+
+/// Serves the job API at the address until the process exits:
+///
+/// - `POST /jobs` submits a job described by a JSON [`JobRequest`]
+/// - `GET /jobs` lists the jobs
+/// - `GET /jobs/<ID>` returns the summary of a job
+/// - `GET /jobs/<ID>/events` streams the progress events of a job as JSON
+///   lines, from its start, or its latest events, until it finishes
+///
+/// Finished jobs are forgotten after an hour.
+pub async fn serve(addr: SocketAddr, runner: JobRunner) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let runtime = tokio::runtime::Handle::current();
+    let jobs = Arc::new(Jobs::default());
+
+    tokio::task::spawn_blocking(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let jobs = jobs.clone();
+            let runner = runner.clone();
+            let runtime = runtime.clone();
+            std::thread::spawn(move || {
+                if let Err(err) = respond(stream, &jobs, &runner, &runtime) {
+                    tracing::debug!(?err, "failed to serve request");
+                }
+            });
+        }
+    })
+    .await?;
+    Ok(())
+}
+
+fn respond(
+    mut stream: TcpStream,
+    jobs: &Jobs,
+    runner: &JobRunner,
+    runtime: &tokio::runtime::Handle,
+) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let (method, path, body) = match read_request(&mut stream)? {
+        Ok(request) => request,
+        Err((status, error)) => return write_error(&mut stream, status, error),
+    };
+    jobs.expire();
+    let segments: Vec<&str> = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+
+    match (method.as_str(), segments.as_slice()) {
+        ("POST", ["jobs"]) => match serde_json::from_slice::<JobRequest>(&body) {
+            Ok(request) => {
+                let summary = jobs.submit(request, runner, runtime);
+                write_json(&mut stream, "202 Accepted", &summary)
+            }
+            Err(err) => write_error(&mut stream, "400 Bad Request", &err.to_string()),
+        },
+        ("GET", ["jobs"]) => write_json(&mut stream, "200 OK", &jobs.summaries()),
+        ("GET", ["jobs", id]) => match id.parse().ok().and_then(|id| jobs.get(id)) {
+            Some(job) => {
+                let summary = job.state.lock().unwrap().summary.clone();
+                write_json(&mut stream, "200 OK", &summary)
+            }
+            None => write_error(&mut stream, "404 Not Found", "No such job"),
+        },
+        ("GET", ["jobs", id, "events"]) => match id.parse().ok().and_then(|id| jobs.get(id)) {
+            Some(job) => stream_events(&mut stream, &job),
+            None => write_error(&mut stream, "404 Not Found", "No such job"),
+        },
+        _ => write_error(&mut stream, "404 Not Found", "No such endpoint"),
+    }
+}
+
+/// The method, path and body of an HTTP request.
+type Request = (String, String, Vec<u8>);
+
+/// Reads an HTTP request, or returns the status and error of the response to
+/// an invalid one.
+fn read_request(
+    stream: &mut TcpStream,
+) -> std::io::Result<Result<Request, (&'static str, &'static str)>> {
+    let mut reader = BufReader::new(stream);
+    let mut head = (&mut reader).take(MAX_HEAD_SIZE);
+    let mut request_line = String::new();
+    head.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if head.read_line(&mut header)? == 0 {
+            if head.limit() == 0 {
+                return Ok(Err((
+                    "431 Request Header Fields Too Large",
+                    "The request headers are too large",
+                )));
+            }
+            break;
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = match value.trim().parse() {
+                    Ok(content_length) => content_length,
+                    Err(_) => return Ok(Err(("400 Bad Request", "Invalid Content-Length"))),
+                };
+            }
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Ok(Err((
+            "413 Content Too Large",
+            "The request body is too large",
+        )));
+    }
+
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok(Ok((method, path, body)))
+}
+
+fn write_json(stream: &mut TcpStream, status: &str, value: &impl Serialize) -> std::io::Result<()> {
+    let body = serde_json::to_string(value)?;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn write_error(stream: &mut TcpStream, status: &str, error: &str) -> std::io::Result<()> {
+    write_json(stream, status, &serde_json::json!({ "error": error }))
+}
+
+/// Streams the job's progress events as JSON lines until the job finishes.
+/// Events dropped from the job's buffer before being sent are skipped.
+fn stream_events(stream: &mut TcpStream, job: &Job) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/x-ndjson\r\n\
+         Connection: close\r\n\r\n"
+    )?;
+    stream.flush()?;
+
+    let mut sent = 0;
+    let mut state = job.state.lock().unwrap();
+    loop {
+        let (lines, new_events, next) = {
+            let (events, next) = state.events_since(sent);
+            (events.join("\n"), events.len(), next)
+        };
+        sent = next;
+        let finished = state.events_done && state.summary.status != JobStatus::Running;
+
+        if new_events > 0 {
+            drop(state);
+            stream.write_all(lines.as_bytes())?;
+            stream.write_all(b"\n")?;
+            stream.flush()?;
+            state = job.state.lock().unwrap();
+            continue;
+        }
+        if finished {
+            return Ok(());
+        }
+        state = job
+            .changed
+            .wait_timeout(state, Duration::from_secs(1))
+            .unwrap()
+            .0;
+    }
+}
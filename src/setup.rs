@@ -0,0 +1,335 @@
+// This is free and unencumbered software released into the public domain.
+
+use color_eyre::Section;
+use console::Term;
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{near_primitives::views::FinalExecutionStatus, Account, AccountId, NetworkConfig};
+use near_crypto::{KeyType, SecretKey};
+use std::{path::Path, str::FromStr};
+
+use crate::{
+    config::ConfigProfile,
+    keys, publish, rpc,
+    ui::{format_near, parse_near},
+};
+
+/// Walks through choosing a network, the credentials of the signer and the
+/// repository, and writes them to the config profile at `path`.
+///
+/// The answers of the profile being replaced are offered as defaults.
+pub async fn run(path: &Path) -> Result<ConfigProfile> {
+    let prompt = Prompt::new()?;
+    let previous = match path.exists() {
+        true => ConfigProfile::read(path)
+            .inspect_err(|err| tracing::warn!(?err, "ignoring the previous config profile"))
+            .ok(),
+        false => None,
+    };
+
+    let networks = ["testnet", "mainnet"];
+    let default = previous
+        .as_ref()
+        .and_then(|profile| networks.iter().position(|name| *name == profile.network))
+        .unwrap_or(0);
+    let network = match networks[prompt.choose("Network", &networks, default)?] {
+        "mainnet" => NetworkConfig::mainnet(),
+        _ => NetworkConfig::testnet(),
+    };
+    // The accounts of the previous profile only make sense on its network:
+    let previous = previous.filter(|profile| profile.network == network.network_name);
+
+    let signer: AccountId = prompt.parse(
+        "Account signing the transactions",
+        previous.as_ref().map(|profile| profile.signer.as_str()),
+    )?;
+    setup_signer(&prompt, &signer, &network).await?;
+
+    let repository: AccountId = prompt.parse(
+        "Repository account, where the dataset is stored",
+        Some(
+            previous
+                .as_ref()
+                .map_or(signer.as_str(), |profile| profile.repository.as_str()),
+        ),
+    )?;
+    setup_repository(&prompt, &repository, &signer, &network).await?;
+
+    let profile = ConfigProfile {
+        network: network.network_name.clone(),
+        signer,
+        repository,
+    };
+    profile.write(path)?;
+    Ok(profile)
+}
+
+/// Makes sure the keys of the signer are in the system keychain, importing
+/// or generating them, and creating the account with the testnet faucet if
+/// it doesn't exist.
+async fn setup_signer(prompt: &Prompt, signer: &AccountId, network: &NetworkConfig) -> Result<()> {
+    if let Err(err) = Account(signer.clone()).view().fetch_from(network).await {
+        prompt.note(&format!("Failed to view account {signer}: {err}"));
+        let testnet: AccountId = "testnet".parse()?;
+        if network.network_name != "testnet" || !signer.is_sub_account_of(&testnet) {
+            return Err(eyre!("Account {signer} doesn't exist")).with_suggestion(|| {
+                "Create the account with a wallet, then run `asimov-dataset setup` again"
+            });
+        }
+        if !prompt.confirm(&format!("Create {signer} with the testnet faucet?"), true)? {
+            bail!("Account {signer} doesn't exist");
+        }
+        return create_with_faucet(signer, network).await;
+    }
+
+    if keys::get_signer(signer, network).await.is_ok() {
+        prompt.note(&format!("Found the keys of {signer}"));
+        return Ok(());
+    }
+    let choices = [
+        "Import a private key of the account",
+        "Generate a new key to add to the account",
+    ];
+    match prompt.choose("No keys of the account were found", &choices, 0)? {
+        0 => loop {
+            let secret_key = prompt.secret("Private key (ed25519:...)")?;
+            let secret_key = match SecretKey::from_str(secret_key.trim()) {
+                Ok(secret_key) => secret_key,
+                Err(err) => {
+                    prompt.note(&format!("Invalid private key: {err}"));
+                    continue;
+                }
+            };
+            if !keys::is_full_access_key(signer, &secret_key.public_key(), network).await? {
+                prompt.note(&format!("The key isn't a full access key of {signer}"));
+                continue;
+            }
+            return keys::save_to_keychain(signer, network, secret_key).await;
+        },
+        _ => {
+            let secret_key = SecretKey::from_random(KeyType::ED25519);
+            let public_key = secret_key.public_key();
+            keys::save_to_keychain(signer, network, secret_key).await?;
+            prompt.note(&format!(
+                "Saved a new key in the system keychain. Add it as a full access key of \
+                 {signer}, e.g. with:\n\n    near account add-key {signer} grant-full-access \
+                 use-manually-provided-public-key {public_key} network-config {} \
+                 sign-with-keychain send\n",
+                network.network_name
+            ));
+            loop {
+                prompt.ask("Press Enter once the key is added", Some(""))?;
+                if keys::is_full_access_key(signer, &public_key, network).await? {
+                    return Ok(());
+                }
+                prompt.note(&format!(
+                    "{public_key} isn't a full access key of {signer} yet"
+                ));
+            }
+        }
+    }
+}
+
+/// Creates the testnet account with the faucet, saving its new key in the
+/// system keychain.
+async fn create_with_faucet(account: &AccountId, network: &NetworkConfig) -> Result<()> {
+    let secret_key = SecretKey::from_random(KeyType::ED25519);
+    let response = Account::create_account(account.clone())
+        .sponsor_by_faucet_service()
+        .public_key(secret_key.public_key())
+        .unwrap_or_else(|never| match never {})
+        .send_to_config_faucet(network)
+        .await
+        .with_context(|| format!("Failed to create {account} with the faucet"))?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        bail!("Failed to create {account} with the faucet ({status}): {text}");
+    }
+    keys::save_to_keychain(account, network, secret_key).await
+}
+
+/// Makes sure a contract is deployed at the repository, creating it as a
+/// sub-account of the signer and deploying the bundled contract if needed.
+async fn setup_repository(
+    prompt: &Prompt,
+    repository: &AccountId,
+    signer: &AccountId,
+    network: &NetworkConfig,
+) -> Result<()> {
+    match publish::repository_code_hash(repository, network, rpc::Block::Final).await {
+        Ok(Some(code_hash)) => {
+            prompt.note(&format!(
+                "A contract is deployed at {repository} ({code_hash})"
+            ));
+            return Ok(());
+        }
+        Ok(None) => {}
+        Err(err) => {
+            prompt.note(&format!("{err:#}"));
+            if !repository.is_sub_account_of(signer) {
+                return Err(eyre!("Account {repository} doesn't exist")).with_suggestion(|| {
+                    format!("Choose an existing account, or a sub-account of {signer} to create it")
+                });
+            }
+            if !prompt.confirm(&format!("Create {repository}, funded by {signer}?"), true)? {
+                bail!("Account {repository} doesn't exist");
+            }
+            let Near(deposit) = prompt.parse("Initial balance, in NEAR", Some("1"))?;
+            create_sub_account(repository, signer, network, deposit).await?;
+            prompt.note(&format!(
+                "Created {repository} with {} NEAR",
+                format_near(deposit)
+            ));
+        }
+    }
+
+    if !prompt.confirm(
+        &format!(
+            "No contract is deployed at {repository}. Deploy the bundled repository contract?"
+        ),
+        true,
+    )? {
+        return Err(eyre!("No contract is deployed at {repository}")).with_suggestion(|| {
+            "Deploy one later with `asimov-dataset publish --upload-contract`"
+        });
+    }
+    let repository_signer = keys::get_signer(repository, network).await?;
+    publish::upload_repository_contract(
+        repository.clone(),
+        repository.clone(),
+        repository_signer,
+        network,
+        publish::LOG_VAULT_WASM.to_vec(),
+        None,
+    )
+    .await?;
+    prompt.note(&format!("Deployed the bundled contract at {repository}"));
+    Ok(())
+}
+
+/// Creates the sub-account of the signer with a new key, saved in the system
+/// keychain, and the deposit, in yoctoNEAR, as its balance.
+async fn create_sub_account(
+    account: &AccountId,
+    signer_id: &AccountId,
+    network: &NetworkConfig,
+    deposit: u128,
+) -> Result<()> {
+    let signer = keys::get_signer(signer_id, network).await?;
+    let secret_key = SecretKey::from_random(KeyType::ED25519);
+    let outcome = Account::create_account(account.clone())
+        .fund_myself(
+            signer_id.clone(),
+            near_api::NearToken::from_yoctonear(deposit),
+        )
+        .public_key(secret_key.public_key())?
+        .with_signer(signer)
+        .send_to(network)
+        .await
+        .with_context(|| format!("Failed to create {account}"))?;
+    if let FinalExecutionStatus::Failure(error) = outcome.status {
+        bail!("Failed to create {account}: {error}");
+    }
+    keys::save_to_keychain(account, network, secret_key).await
+}
+
+/// Questions asked on the terminal, answered on the standard input.
+struct Prompt {
+    term: Term,
+}
+
+impl Prompt {
+    fn new() -> Result<Self> {
+        let term = Term::stderr();
+        if !term.is_term() {
+            return Err(eyre!("The setup needs an interactive terminal")).with_suggestion(|| {
+                "Run it in a terminal, or pass `--signer` to each command instead"
+            });
+        }
+        Ok(Self { term })
+    }
+
+    fn note(&self, text: &str) {
+        let _ = self.term.write_line(text);
+    }
+
+    /// Asks a question until it's answered, or the default is accepted.
+    fn ask(&self, question: &str, default: Option<&str>) -> Result<String> {
+        loop {
+            match default {
+                Some(default) if !default.is_empty() => {
+                    self.term.write_str(&format!("{question} [{default}]: "))?
+                }
+                _ => self.term.write_str(&format!("{question}: "))?,
+            }
+            let answer = self.term.read_line()?.trim().to_string();
+            match (answer.is_empty(), default) {
+                (false, _) => return Ok(answer),
+                (true, Some(default)) => return Ok(default.to_string()),
+                (true, None) => continue,
+            }
+        }
+    }
+
+    /// Asks a question until the answer parses.
+    fn parse<T>(&self, question: &str, default: Option<&str>) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        loop {
+            match self.ask(question, default)?.parse() {
+                Ok(value) => return Ok(value),
+                Err(err) => self.note(&format!("Invalid answer: {err}")),
+            }
+        }
+    }
+
+    fn confirm(&self, question: &str, default: bool) -> Result<bool> {
+        let choices = match default {
+            true => "Y/n",
+            false => "y/N",
+        };
+        loop {
+            self.term.write_str(&format!("{question} [{choices}]: "))?;
+            match self.term.read_line()?.trim().to_lowercase().as_str() {
+                "" => return Ok(default),
+                "y" | "yes" => return Ok(true),
+                "n" | "no" => return Ok(false),
+                _ => continue,
+            }
+        }
+    }
+
+    /// Asks to choose by number, returning the index of the choice.
+    fn choose(&self, question: &str, choices: &[&str], default: usize) -> Result<usize> {
+        self.note(&format!("{question}:"));
+        for (index, choice) in choices.iter().enumerate() {
+            self.note(&format!("  {}. {choice}", index + 1));
+        }
+        loop {
+            let answer = self.ask("Choice", Some(&(default + 1).to_string()))?;
+            match answer.parse::<usize>() {
+                Ok(number) if (1..=choices.len()).contains(&number) => return Ok(number - 1),
+                _ => self.note(&format!("Choose a number from 1 to {}", choices.len())),
+            }
+        }
+    }
+
+    /// Asks for a secret, without echoing it.
+    fn secret(&self, question: &str) -> Result<String> {
+        self.term.write_str(&format!("{question}: "))?;
+        Ok(self.term.read_secure_line()?)
+    }
+}
+
+/// An amount of yoctoNEAR, answered in NEAR.
+struct Near(u128);
+
+impl FromStr for Near {
+    type Err = eyre::Report;
+
+    fn from_str(value: &str) -> Result<Self> {
+        parse_near(value).map(Near)
+    }
+}
@@ -0,0 +1,218 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use oxrdfio::{RdfFormat, RdfParser};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::prepare::read_input;
+
+/// The key routing statements to shards.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShardBy {
+    /// The subject of the statement, so that all the statements describing a
+    /// resource are in the same shard.
+    #[default]
+    Subject,
+}
+
+impl ShardBy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Subject => "subject",
+        }
+    }
+}
+
+impl std::fmt::Display for ShardBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ShardBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "subject" => Ok(Self::Subject),
+            _ => Err(format!("unknown shard key `{s}`, expected one of: subject")),
+        }
+    }
+}
+
+/// Returns the index of the shard of a key among `shards` shards: the first
+/// 8 bytes of the SHA-256 digest of the key, as a big-endian integer, modulo
+/// the number of shards.
+///
+/// Subjects are hashed in their N-Triples form, e.g. `<http://example.org/a>`
+/// or `_:b0`.
+pub fn shard_of(key: &str, shards: usize) -> usize {
+    let digest = Sha256::digest(key.as_bytes());
+    let hash = u64::from_be_bytes(digest[..8].try_into().unwrap());
+    (hash % shards.max(1) as u64) as usize
+}
+
+/// The assignment of the statements of a dataset to the repositories of its
+/// shards, written next to the published data to know which repository to
+/// query for a subject.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShardManifest {
+    pub shard_by: ShardBy,
+    /// The hash function of [`shard_of`].
+    pub hash: String,
+    pub dataset: Option<String>,
+    pub shards: Vec<Shard>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Shard {
+    pub index: usize,
+    pub repository: String,
+    /// The number of statements of the raw RDF files routed to the shard.
+    pub statements: usize,
+}
+
+impl ShardManifest {
+    pub fn new(shard_by: ShardBy, dataset: Option<String>, shards: Vec<Shard>) -> Self {
+        Self {
+            shard_by,
+            hash: "sha256".into(),
+            dataset,
+            shards,
+        }
+    }
+
+    /// Returns the repository holding the statements of the given key.
+    pub fn repository_of(&self, key: &str) -> Option<&str> {
+        let index = shard_of(key, self.shards.len());
+        self.shards
+            .iter()
+            .find(|shard| shard.index == index)
+            .map(|shard| shard.repository.as_str())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write shard manifest {}", path.display()))
+    }
+}
+
+/// The files holding the statements of a shard.
+#[derive(Debug, Default)]
+pub struct ShardFiles {
+    pub files: Vec<PathBuf>,
+    /// The number of statements of the raw RDF files, not counting the
+    /// changes of patch files.
+    pub statements: usize,
+}
+
+/// Splits the statements of raw RDF files and of RDF Patch and LD Patch files
+/// into `shards` shards by the hash of their subject, writing each shard into
+/// `dir` as `<INDEX>.nq` and, for the changes of patch files, `<INDEX>.rdfp`.
+///
+/// Prepared RDF/Borsh files can't be split, since their statements are already
+/// encoded.
+pub fn split_files(files: &[PathBuf], shards: usize, dir: &Path) -> Result<Vec<ShardFiles>> {
+    let mut statements = Writers::new(dir, shards, "nq")?;
+    let mut patches = Writers::new(dir, shards, "rdfp")?;
+    for file in files {
+        if file.extension().is_some_and(|ext| ext == "rdfb") {
+            bail!(
+                "The prepared file {} can't be sharded, publish its raw RDF files instead",
+                file.display()
+            );
+        }
+        if crate::patch::is_patch(file) {
+            let delta = crate::patch::read_patch(file)
+                .with_context(|| format!("Failed to read patch {}", file.display()))?;
+            // Deletions are written first, as they are applied first:
+            let changes = (delta.removed.iter().map(|statement| ("D", statement)))
+                .chain(delta.added.iter().map(|statement| ("A", statement)));
+            for (code, statement) in changes {
+                let subject = statement_subject(statement)?;
+                patches.write(shard_of(&subject, shards), &format!("{code} {statement} ."))?;
+            }
+            continue;
+        }
+        let quads =
+            read_input(file).with_context(|| format!("Failed to read {}", file.display()))?;
+        for quad in quads {
+            let quad = quad.with_context(|| format!("Failed to read {}", file.display()))?;
+            let index = shard_of(&quad.subject.to_string(), shards);
+            statements.write(index, &format!("{quad} ."))?;
+        }
+    }
+
+    let statements = statements.finish()?;
+    let patches = patches.finish()?;
+    Ok(statements
+        .into_iter()
+        .zip(patches)
+        .map(|((nquads, count), (patch, changes))| {
+            let files = [(nquads, count), (patch, changes)]
+                .into_iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(file, _)| file)
+                .collect();
+            ShardFiles {
+                files,
+                statements: count,
+            }
+        })
+        .collect())
+}
+
+/// Returns the subject of an N-Quads statement, in its N-Triples form.
+fn statement_subject(statement: &str) -> Result<String> {
+    let nquads = format!("{statement} .");
+    let quad = RdfParser::from_format(RdfFormat::NQuads)
+        .for_slice(nquads.as_bytes())
+        .next()
+        .ok_or_else(|| eyre!("Expected a statement"))??;
+    Ok(quad.subject.to_string())
+}
+
+/// The line writers of the file of each shard.
+struct Writers {
+    files: Vec<(PathBuf, BufWriter<File>, usize)>,
+}
+
+impl Writers {
+    fn new(dir: &Path, shards: usize, extension: &str) -> Result<Self> {
+        let files = (0..shards)
+            .map(|index| {
+                let path = dir.join(format!("{index}.{extension}"));
+                let file = File::create(&path)
+                    .with_context(|| format!("Failed to create {}", path.display()))?;
+                Ok((path, BufWriter::new(file), 0))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    fn write(&mut self, index: usize, line: &str) -> Result<()> {
+        let (_, writer, count) = &mut self.files[index];
+        writeln!(writer, "{line}")?;
+        *count += 1;
+        Ok(())
+    }
+
+    /// Flushes the files, returning the path and number of lines of each.
+    fn finish(self) -> Result<Vec<(PathBuf, usize)>> {
+        self.files
+            .into_iter()
+            .map(|(path, writer, count)| {
+                writer.into_inner()?.sync_all()?;
+                Ok((path, count))
+            })
+            .collect()
+    }
+}
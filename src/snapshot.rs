@@ -0,0 +1,120 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The statements of a dataset as last published to a repository, kept
+/// locally to publish only the changes made since.
+///
+/// The snapshot file holds one N-Quads statement per line, sorted. Statements
+/// are compared by their N-Quads serialization, so blank nodes only match
+/// statements with the same blank node labels.
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    statements: BTreeSet<String>,
+}
+
+/// The statements added and removed between two states of a dataset.
+#[derive(Debug, Default)]
+pub struct Delta {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl Snapshot {
+    /// Returns the path of the snapshot of a dataset of a repository,
+    /// `~/.cache/asimov-dataset/<repository>/<dataset>.nq`, with `default`
+    /// for the unnamed dataset.
+    pub fn default_path(repository: &str, dataset: Option<&str>) -> Option<PathBuf> {
+        let dataset = dataset.filter(|dataset| !dataset.is_empty());
+        Some(
+            crate::cache::cache_dir()?
+                .join(repository)
+                .join(format!("{}.nq", dataset.unwrap_or("default"))),
+        )
+    }
+
+    pub fn new(statements: BTreeSet<String>) -> Self {
+        Self { statements }
+    }
+
+    /// Reads the snapshot at the given path, which is empty if the file doesn't exist.
+    pub fn open(path: &Path) -> std::io::Result<Self> {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err),
+        };
+        let mut statements = BTreeSet::new();
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            if let Some(statement) = line.strip_suffix(" .") {
+                statements.insert(statement.to_string());
+            }
+        }
+        Ok(Self { statements })
+    }
+
+    /// Writes the snapshot to the given path, replacing the previous one at once.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let tmp_path = path.with_extension("nq.tmp");
+        write_nquads(&tmp_path, &self.statements)?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.statements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// Returns the changes from this snapshot to the other.
+    pub fn delta(&self, other: &Snapshot) -> Delta {
+        Delta {
+            added: other
+                .statements
+                .difference(&self.statements)
+                .cloned()
+                .collect(),
+            removed: self
+                .statements
+                .difference(&other.statements)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Applies the removed statements of a delta.
+    pub fn remove(&mut self, statements: &[String]) {
+        for statement in statements {
+            self.statements.remove(statement);
+        }
+    }
+}
+
+/// Writes the statements into an N-Quads file.
+pub fn write_nquads<'a>(
+    path: &Path,
+    statements: impl IntoIterator<Item = &'a String>,
+) -> std::io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    for statement in statements {
+        writeln!(file, "{statement} .")?;
+    }
+    file.into_inner()?.sync_all()
+}
@@ -0,0 +1,87 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::collections::{BTreeMap, HashMap};
+
+/// Statistics of the statements of a dataset, to tell why its batches fill
+/// up and which terms take the most room in them.
+#[derive(Debug, Default)]
+pub struct StatementStatistics {
+    statement_count: usize,
+    /// The number of statements by the power of two bounding their size.
+    sizes: BTreeMap<u32, usize>,
+    predicates: HashMap<String, usize>,
+    subjects: HashMap<String, usize>,
+}
+
+/// A range of statement sizes, in bytes, and the number of statements in it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SizeBucket {
+    pub min: usize,
+    pub max: usize,
+    pub statement_count: usize,
+}
+
+/// A term and the number of statements in which it occurs.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TermCount {
+    pub term: String,
+    pub statement_count: usize,
+}
+
+impl StatementStatistics {
+    pub fn record(&mut self, quad: &oxrdf::Quad) {
+        self.statement_count += 1;
+        // The N-Quads serialization of the statement, with ` .\n`:
+        let size = quad.to_string().len() + 3;
+        *self
+            .sizes
+            .entry(usize::BITS - size.leading_zeros())
+            .or_default() += 1;
+        *self
+            .predicates
+            .entry(quad.predicate.to_string())
+            .or_default() += 1;
+        *self.subjects.entry(quad.subject.to_string()).or_default() += 1;
+    }
+
+    pub fn statement_count(&self) -> usize {
+        self.statement_count
+    }
+
+    /// Returns the histogram of the sizes of the statements in N-Quads, by
+    /// ranges of sizes doubling from one to the next.
+    pub fn size_histogram(&self) -> Vec<SizeBucket> {
+        self.sizes
+            .iter()
+            .map(|(bits, count)| SizeBucket {
+                min: 1 << (bits - 1),
+                max: (1 << bits) - 1,
+                statement_count: *count,
+            })
+            .collect()
+    }
+
+    /// Returns the `k` most frequent predicates, most frequent first.
+    pub fn top_predicates(&self, k: usize) -> Vec<TermCount> {
+        top_terms(&self.predicates, k)
+    }
+
+    /// Returns the `k` most frequent subjects, most frequent first.
+    pub fn top_subjects(&self, k: usize) -> Vec<TermCount> {
+        top_terms(&self.subjects, k)
+    }
+}
+
+fn top_terms(counts: &HashMap<String, usize>, k: usize) -> Vec<TermCount> {
+    let mut terms: Vec<(&String, &usize)> = counts.iter().collect();
+    // Terms equally frequent are ordered by term, for stable output:
+    terms.sort_unstable_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+    terms
+        .into_iter()
+        .take(k)
+        .map(|(term, count)| TermCount {
+            term: term.clone(),
+            statement_count: *count,
+        })
+        .collect()
+}
@@ -0,0 +1,511 @@
+// This is free and unencumbered software released into the public domain.
+
+use color_eyre::Section;
+use eyre::{bail, eyre, Context as _, Result};
+use near_api::{
+    near_primitives::{
+        action::{Action, FunctionCallAction},
+        hash::CryptoHash,
+        views::FinalExecutionStatus,
+    },
+    AccountId, NearGas, NetworkConfig, Signer, Transaction,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    context::Context,
+    decode::{self, BatchDecoder},
+    prepare::PreparedBatch,
+    publish::{self, PublishStatsReport},
+};
+
+/// Where `publish` stores the batches.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// The repository's contract on NEAR.
+    #[default]
+    Near,
+    /// Files pinned to an IPFS node.
+    Ipfs,
+    /// Transactions of a wallet on Arweave.
+    Arweave,
+    /// A local Oxigraph store.
+    Oxigraph,
+    /// A SPARQL Graph Store Protocol endpoint.
+    Sparql,
+    /// An HTTP endpoint receiving the arguments of `rdf_insert`.
+    Http,
+}
+
+impl Target {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Near => "near",
+            Self::Ipfs => "ipfs",
+            Self::Arweave => "arweave",
+            Self::Oxigraph => "oxigraph",
+            Self::Sparql => "sparql",
+            Self::Http => "http",
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "near" => Ok(Self::Near),
+            "ipfs" => Ok(Self::Ipfs),
+            "arweave" => Ok(Self::Arweave),
+            "oxigraph" => Ok(Self::Oxigraph),
+            "sparql" => Ok(Self::Sparql),
+            "http" => Ok(Self::Http),
+            _ => Err(format!(
+                "unknown target `{s}`, expected one of: near, ipfs, arweave, oxigraph, sparql, http"
+            )),
+        }
+    }
+}
+
+/// A store of prepared batches other than the repository's contract.
+#[async_trait::async_trait]
+pub trait BatchStore: Send + Sync {
+    fn target(&self) -> Target;
+
+    /// Stores the RDF/Borsh batch file of the dataset, returning the
+    /// identifier of the stored copy.
+    async fn store(&self, path: &Path, dataset: &str) -> Result<String>;
+
+    /// Returns a link to the stored copy with the identifier.
+    fn url(&self, id: &str) -> Option<String>;
+}
+
+/// Pins batches to an IPFS node through its HTTP RPC API.
+pub struct IpfsStore {
+    api_url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Deserialize)]
+struct IpfsAddResponse {
+    #[serde(rename = "Hash")]
+    hash: String,
+}
+
+impl IpfsStore {
+    /// Returns a store using the RPC API at the URL, e.g. `http://127.0.0.1:5001`.
+    pub fn new(api_url: &str) -> Self {
+        Self {
+            api_url: api_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchStore for IpfsStore {
+    fn target(&self) -> Target {
+        Target::Ipfs
+    }
+
+    async fn store(&self, path: &Path, _dataset: &str) -> Result<String> {
+        let data = std::fs::read(path)?;
+        let filename = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        // The digest of the data can't occur in the data:
+        let boundary = format!("{:x}", Sha256::digest(&data));
+        let mut body = format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{filename}\"\r\nContent-Type: application/octet-stream\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(&data);
+        body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+        let url = format!("{}/api/v0/add?pin=true&cid-version=1", self.api_url);
+        let response: IpfsAddResponse = self
+            .client
+            .post(&url)
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                format!("multipart/form-data; boundary={boundary}"),
+            )
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to add the batch to IPFS at {}", self.api_url))
+            .with_suggestion(|| {
+                "Check that an IPFS node is running and `--ipfs-api` is its RPC API URL"
+            })?
+            .json()
+            .await
+            .context("Invalid response from the IPFS RPC API")?;
+        Ok(response.hash)
+    }
+
+    fn url(&self, id: &str) -> Option<String> {
+        Some(format!("ipfs://{id}"))
+    }
+}
+
+/// Loads batches into a local Oxigraph store with the `oxigraph` command,
+/// to query a dataset before publishing it.
+pub struct OxigraphStore {
+    location: PathBuf,
+    program: PathBuf,
+    decoder: Mutex<BatchDecoder>,
+}
+
+impl OxigraphStore {
+    /// Returns a store in the directory, loaded with the `oxigraph` program.
+    pub fn new(location: impl Into<PathBuf>, program: impl Into<PathBuf>) -> Self {
+        Self {
+            location: location.into(),
+            program: program.into(),
+            decoder: Mutex::default(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchStore for OxigraphStore {
+    fn target(&self) -> Target {
+        Target::Oxigraph
+    }
+
+    /// Loads the quads of the batch, returning the SHA-256 digest of the batch.
+    async fn store(&self, path: &Path, _dataset: &str) -> Result<String> {
+        use tokio::io::AsyncWriteExt as _;
+
+        let data = std::fs::read(path)?;
+        let quads = self.decoder.lock().unwrap().decode(&data)?;
+        let mut nquads = Vec::new();
+        decode::write_nquads(&quads, &mut nquads)?;
+
+        let mut child = tokio::process::Command::new(&self.program)
+            .arg("load")
+            .arg("--location")
+            .arg(&self.location)
+            .args(["--format", "nq"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to run `{}`", self.program.display()))
+            .with_suggestion(|| {
+                "Install the Oxigraph CLI with `cargo install oxigraph-cli`, or pass its path with `--oxigraph`"
+            })?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        stdin.write_all(&nquads).await?;
+        drop(stdin);
+        let status = child.wait().await?;
+        if !status.success() {
+            bail!(
+                "`{} load` exited with {status} loading into {}",
+                self.program.display(),
+                self.location.display()
+            );
+        }
+        Ok(format!("{:x}", Sha256::digest(&data)))
+    }
+
+    fn url(&self, _id: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Adds batches to a triple store through its SPARQL Graph Store Protocol
+/// endpoint, posting the quads of each batch in N-Quads.
+pub struct SparqlStore {
+    endpoint: String,
+    decoder: Mutex<BatchDecoder>,
+    client: reqwest::Client,
+}
+
+impl SparqlStore {
+    /// Returns a store using the Graph Store Protocol endpoint at the URL,
+    /// e.g. `http://localhost:3030/dataset/data`.
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            decoder: Mutex::default(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchStore for SparqlStore {
+    fn target(&self) -> Target {
+        Target::Sparql
+    }
+
+    /// Posts the quads of the batch, returning the SHA-256 digest of the batch.
+    async fn store(&self, path: &Path, _dataset: &str) -> Result<String> {
+        let data = std::fs::read(path)?;
+        let quads = self.decoder.lock().unwrap().decode(&data)?;
+        let mut nquads = Vec::new();
+        decode::write_nquads(&quads, &mut nquads)?;
+
+        self.client
+            .post(&self.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/n-quads")
+            .body(nquads)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to post the batch to {}", self.endpoint))
+            .with_suggestion(|| {
+                "Check that `--endpoint` is the Graph Store Protocol endpoint of a dataset accepting N-Quads"
+            })?;
+        Ok(format!("{:x}", Sha256::digest(&data)))
+    }
+
+    fn url(&self, _id: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Posts batches to an HTTP endpoint in the envelope of `rdf_insert`, for
+/// ingestion services taking the same batches as the repository's contract.
+pub struct HttpStore {
+    endpoint: String,
+    client: reqwest::Client,
+}
+
+impl HttpStore {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            endpoint: endpoint.to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BatchStore for HttpStore {
+    fn target(&self) -> Target {
+        Target::Http
+    }
+
+    /// Posts the batch, returning the body of the response as the identifier
+    /// of the batch, or the SHA-256 digest of the batch if the body is empty.
+    async fn store(&self, path: &Path, dataset: &str) -> Result<String> {
+        let data = std::fs::read(path)?;
+        let args = publish::insert_args(dataset, &data)?;
+        let digest = format!("{:x}", Sha256::digest(&args));
+
+        let body = self
+            .client
+            .post(&self.endpoint)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .body(args)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .with_context(|| format!("Failed to post the batch to {}", self.endpoint))?
+            .text()
+            .await?;
+        let id = body.trim();
+        Ok(if id.is_empty() {
+            digest
+        } else {
+            id.to_string()
+        })
+    }
+
+    fn url(&self, _id: &str) -> Option<String> {
+        None
+    }
+}
+
+/// The batches stored by a publish run to a target other than NEAR, written
+/// next to the published data to find the stored copies.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoreManifest {
+    pub target: Target,
+    pub dataset: Option<String>,
+    pub batches: Vec<StoredBatch>,
+    /// The transaction recording the identifiers of the batches in the
+    /// repository's contract, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub index_transaction: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredBatch {
+    pub filename: PathBuf,
+    pub dataset: String,
+    pub statement_count: usize,
+    pub bytes: usize,
+    /// The identifier of the stored copy, e.g. its CID on IPFS or its
+    /// transaction ID on Arweave, or the digest of the batch in a triple store.
+    pub id: String,
+}
+
+impl StoreManifest {
+    pub fn new(target: Target, dataset: Option<String>) -> Self {
+        Self {
+            target,
+            dataset,
+            batches: Vec::new(),
+            index_transaction: None,
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write manifest {}", path.display()))
+    }
+}
+
+#[derive(derive_builder::Builder)]
+#[builder(pattern = "owned")]
+pub struct Params<I> {
+    store: Arc<dyn BatchStore>,
+    files: I,
+    #[builder(setter(into), default)]
+    dataset: Option<String>,
+    /// Dataset names for named graphs, keyed by graph IRI.
+    #[builder(default)]
+    graph_datasets: HashMap<String, String>,
+    /// File into which the manifest is written after each stored batch.
+    #[builder(setter(into, strip_option), default)]
+    manifest: Option<PathBuf>,
+    #[builder(setter(into, strip_option), default)]
+    report: Option<PublishStatsReport>,
+}
+
+/// Stores the batches in turn, returning the manifest of the stored batches.
+pub async fn store_batches<I>(ctx: Context, mut params: Params<I>) -> Result<StoreManifest>
+where
+    I: Iterator<Item = PreparedBatch>,
+{
+    let mut manifest = StoreManifest::new(params.store.target(), params.dataset.clone());
+
+    for batch in params.files.by_ref() {
+        ctx.wait_while_paused().await;
+        if ctx.is_cancelled() {
+            break;
+        }
+        let dataset =
+            publish::dataset_of(&batch, params.dataset.as_deref(), &params.graph_datasets);
+        let filename = batch.path;
+        let bytes = std::fs::metadata(&filename)?.len() as usize;
+
+        let id = match params.store.store(&filename, &dataset).await {
+            Ok(id) => id,
+            Err(err) => {
+                if let Some(ref report) = params.report {
+                    report.send(crate::ui::Event::PublishFailure(
+                        crate::ui::PublishFailure {
+                            filenames: vec![filename.clone()],
+                            error: err.root_cause().to_string(),
+                        },
+                    ));
+                }
+                return Err(err)
+                    .wrap_err_with(|| format!("Failed to store batch {}", filename.display()));
+            }
+        };
+        tracing::info!(?filename, %id, target = %params.store.target(), "stored dataset");
+
+        if let Some(ref report) = params.report {
+            report.send(crate::ui::Event::Publish(crate::ui::PublishProgress {
+                filename: filename.clone(),
+                bytes,
+                statement_count: batch.statement_count,
+                dataset: dataset.clone(),
+                transaction: id.clone(),
+                explorer_url: params.store.url(&id),
+                finality: Default::default(),
+                // Stored batches have no transaction to await:
+                stage: crate::finality::Stage::Final,
+                gas_burnt: 0,
+                tokens_burnt: 0,
+                unconfirmed: false,
+            }));
+        }
+
+        manifest.batches.push(StoredBatch {
+            filename: filename.file_name().map(PathBuf::from).unwrap_or_default(),
+            dataset,
+            statement_count: batch.statement_count,
+            bytes,
+            id,
+        });
+        if let Some(ref path) = params.manifest {
+            manifest.write(path)?;
+        }
+        std::fs::remove_file(&filename).ok();
+    }
+
+    Ok(manifest)
+}
+
+/// Records the identifiers of the stored batches in the repository's contract,
+/// with one call of `method` per dataset, in a single transaction, with the
+/// arguments `{"dataset": "...", "ids": ["...", ...]}`.
+pub async fn record_index(
+    manifest: &StoreManifest,
+    method: &str,
+    repository: AccountId,
+    signer_id: AccountId,
+    signer: Arc<Signer>,
+    network: &NetworkConfig,
+) -> Result<CryptoHash> {
+    // The datasets in the order of their first batch:
+    let mut datasets: Vec<(&str, Vec<&str>)> = Vec::new();
+    for batch in &manifest.batches {
+        match datasets.iter_mut().find(|(name, _)| *name == batch.dataset) {
+            Some((_, ids)) => ids.push(&batch.id),
+            None => datasets.push((&batch.dataset, vec![&batch.id])),
+        }
+    }
+    if datasets.is_empty() {
+        bail!("No stored batches to record");
+    }
+
+    let gas = NearGas::from_tgas(publish::MAX_TX_TGAS / datasets.len() as u64).as_gas();
+    let actions = datasets
+        .iter()
+        .map(|(dataset, ids)| {
+            let args = serde_json::json!({ "dataset": dataset, "ids": ids });
+            Ok(Action::FunctionCall(Box::new(FunctionCallAction {
+                method_name: method.into(),
+                args: serde_json::to_vec(&args)?,
+                gas,
+                deposit: 0,
+            })))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let tx_outcome = Transaction::construct(signer_id, repository)
+        .add_actions(actions)
+        .with_signer(signer)
+        .send_to(network)
+        .await
+        .with_context(|| format!("Failed to call `{method}` with the stored batches"))?;
+    if let FinalExecutionStatus::Failure(error) = tx_outcome.status {
+        return Err(eyre!(error))
+            .wrap_err_with(|| format!("Failed to call `{method}` with the stored batches"));
+    }
+    Ok(tx_outcome.transaction.hash)
+}
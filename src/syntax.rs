@@ -0,0 +1,80 @@
+// This is free and unencumbered software released into the public domain.
+
+use oxrdfio::{RdfFormat, RdfParseError, RdfSyntaxError};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// A syntax error in an input file, located by its line and column, both
+/// starting from 1, when the parser knows them.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SyntaxError {
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{} at line {line}, column {column}", self.message)
+            }
+            _ => f.write_str(&self.message),
+        }
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+/// Returns the error of the parser, locating its syntax errors in the file
+/// given the line of the parsed chunk, counted from 0.
+pub fn parse_error(error: RdfParseError, first_line: u64) -> eyre::Report {
+    match error {
+        RdfParseError::Io(err) => eyre::Report::new(err).wrap_err("Failed to read input file"),
+        RdfParseError::Syntax(error) => eyre::Report::new(syntax_error(error, first_line)),
+    }
+}
+
+/// Returns the syntax error located in the file given the line of the
+/// parsed chunk, counted from 0.
+pub fn syntax_error(error: RdfSyntaxError, first_line: u64) -> SyntaxError {
+    let location = error.location();
+    let message = error.to_string();
+    // The message of a Turtle error starts with its location:
+    let message = match message.strip_prefix("Parser error ") {
+        Some(located) => located
+            .split_once(": ")
+            .map_or(message.as_str(), |(_, message)| message)
+            .to_string(),
+        None => message,
+    };
+    SyntaxError {
+        line: location
+            .as_ref()
+            .map(|location| first_line + location.start.line + 1),
+        column: location.as_ref().map(|location| location.start.column + 1),
+        message,
+    }
+}
+
+/// A statement left out for failing to parse.
+#[derive(Debug, Serialize)]
+pub struct ParseErrorRecord {
+    pub file: PathBuf,
+    pub line: Option<u64>,
+    pub column: Option<u64>,
+    pub message: String,
+}
+
+/// Returns whether the parser of the format goes on with the next statement
+/// after a syntax error, rather than stopping or failing again.
+pub fn recovers_from_errors(format: RdfFormat) -> bool {
+    matches!(
+        format,
+        RdfFormat::NTriples
+            | RdfFormat::NQuads
+            | RdfFormat::Turtle
+            | RdfFormat::TriG
+            | RdfFormat::N3
+    )
+}
@@ -0,0 +1,122 @@
+// This is free and unencumbered software released into the public domain.
+
+use arrow_array::{cast::AsArray, Array, RecordBatch};
+use eyre::{eyre, Context as _, Result};
+use oxrdf::{BlankNode, GraphName, Literal, NamedNode, Quad, Subject, Term};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::{fs::File, path::Path};
+
+/// Number of rows decoded at once.
+const BATCH_SIZE: usize = 8192;
+
+/// Returns whether the file is a Parquet triple table, according to its extension.
+pub fn is_triple_table(file: &Path) -> bool {
+    file.extension().is_some_and(|ext| ext == "parquet")
+}
+
+/// Streams the statements of a Parquet triple table with `subject`, `predicate`,
+/// `object` and optional `graph` string columns, one record batch at a time.
+///
+/// The columns contain terms in N-Triples syntax (`<http://…>`, `_:b0`,
+/// `"literal"@en`). Bare IRIs are also accepted, and bare object values that
+/// are not IRIs are read as plain literals. Rows with a null or empty `graph`
+/// are in the default graph.
+pub fn read_quads(file: &Path) -> Result<impl Iterator<Item = Result<Quad>>> {
+    let reader = File::open(file).context("Failed to open input file")?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(reader)
+        .context("Failed to read Parquet metadata")?
+        .with_batch_size(BATCH_SIZE)
+        .build()
+        .context("Failed to read Parquet file")?;
+
+    Ok(reader.flat_map(|batch| match batch {
+        Ok(batch) => match batch_quads(&batch) {
+            Ok(quads) => quads.into_iter().map(Ok).collect::<Vec<_>>(),
+            Err(err) => vec![Err(err)],
+        },
+        Err(err) => vec![Err(
+            eyre!(err).wrap_err("Failed to read Parquet record batch")
+        )],
+    }))
+}
+
+fn batch_quads(batch: &RecordBatch) -> Result<Vec<Quad>> {
+    let subjects = string_column(batch, "subject")?
+        .ok_or_else(|| eyre!("Missing `subject` column in triple table"))?;
+    let predicates = string_column(batch, "predicate")?
+        .ok_or_else(|| eyre!("Missing `predicate` column in triple table"))?;
+    let objects = string_column(batch, "object")?
+        .ok_or_else(|| eyre!("Missing `object` column in triple table"))?;
+    let graphs = string_column(batch, "graph")?;
+
+    (0..batch.num_rows())
+        .map(|row| {
+            let value = |column: &[Option<String>], name: &str| {
+                column[row]
+                    .clone()
+                    .ok_or_else(|| eyre!("Null `{name}` in row {row} of triple table"))
+            };
+            let graph = match graphs.as_ref().and_then(|graphs| graphs[row].as_deref()) {
+                None | Some("") => GraphName::DefaultGraph,
+                Some(graph) => match parse_subject(graph)? {
+                    Subject::NamedNode(node) => node.into(),
+                    Subject::BlankNode(node) => node.into(),
+                    _ => return Err(eyre!("Invalid graph name `{graph}`")),
+                },
+            };
+            Ok(Quad::new(
+                parse_subject(&value(&subjects, "subject")?)?,
+                parse_named_node(&value(&predicates, "predicate")?)?,
+                parse_object(&value(&objects, "object")?),
+                graph,
+            ))
+        })
+        .collect()
+}
+
+/// Returns the values of a string column, or `None` if there is no such column.
+fn string_column(batch: &RecordBatch, name: &str) -> Result<Option<Vec<Option<String>>>> {
+    let Some(column) = batch.column_by_name(name) else {
+        return Ok(None);
+    };
+    let values: Vec<Option<String>> = if let Some(column) = column.as_string_opt::<i32>() {
+        column.iter().map(|value| value.map(String::from)).collect()
+    } else if let Some(column) = column.as_string_opt::<i64>() {
+        column.iter().map(|value| value.map(String::from)).collect()
+    } else if let Some(column) = column.as_string_view_opt() {
+        column.iter().map(|value| value.map(String::from)).collect()
+    } else {
+        return Err(eyre!(
+            "Column `{name}` of triple table has type {}, expected a string type",
+            column.data_type()
+        ));
+    };
+    Ok(Some(values))
+}
+
+fn parse_named_node(value: &str) -> Result<NamedNode> {
+    if value.starts_with('<') {
+        value
+            .parse()
+            .with_context(|| format!("Invalid IRI `{value}`"))
+    } else {
+        NamedNode::new(value).with_context(|| format!("Invalid IRI `{value}`"))
+    }
+}
+
+fn parse_subject(value: &str) -> Result<Subject> {
+    if value.starts_with("_:") {
+        let node: BlankNode = value
+            .parse()
+            .with_context(|| format!("Invalid blank node `{value}`"))?;
+        return Ok(node.into());
+    }
+    Ok(parse_named_node(value)?.into())
+}
+
+fn parse_object(value: &str) -> Term {
+    value
+        .parse()
+        .or_else(|_| NamedNode::new(value).map(Term::from))
+        .unwrap_or_else(|_| Literal::new_simple_literal(value).into())
+}
@@ -0,0 +1,136 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::channel::Sender;
+use serde::Serialize;
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use crate::finality::{Stage, WaitUntil};
+
+/// A progress event, serialized as `{"event": "publish", "progress": {…}}`.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", content = "progress", rename_all = "snake_case")]
+pub enum Event {
+    Reader(ReaderProgress),
+    Prepare(PrepareProgress),
+    Publish(PublishProgress),
+    PublishFailure(PublishFailure),
+    PublishSkipped(PublishSkipped),
+    PublishPaused(PublishPaused),
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ReaderProgress {
+    pub filename: PathBuf,
+    pub bytes: usize,
+    pub statement_count: usize,
+    /// The number of statements left out for having invalid literals.
+    pub skipped_statements: usize,
+    /// The number of statements left out for failing to parse.
+    pub parse_errors: usize,
+    /// The number of statements left out for being already published.
+    pub published_statements: usize,
+    pub finished: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct PrepareProgress {
+    pub filename: PathBuf,
+    pub bytes: usize,
+    pub statement_count: usize,
+    pub skipped_statements: usize,
+}
+
+/// A batch not published again because the publish cache lists it as published.
+#[derive(Debug, Default, Serialize)]
+pub struct PublishSkipped {
+    pub filename: PathBuf,
+    pub dataset: String,
+    /// The transaction that published the batch before.
+    pub transaction: String,
+}
+
+/// Submissions paused for the balance of the signer being too low to pay for
+/// the next transaction, or resumed once the account was topped up.
+#[derive(Debug, Default, Serialize)]
+pub struct PublishPaused {
+    pub account: String,
+    /// The balance not locked for storage, in yoctoNEAR.
+    pub balance: u128,
+    /// The balance needed to resume, in yoctoNEAR.
+    pub needed: u128,
+    pub resumed: bool,
+}
+
+/// Batches that failed to publish together, e.g. in the same transaction.
+#[derive(Debug, Default, Serialize)]
+pub struct PublishFailure {
+    pub filenames: Vec<PathBuf>,
+    /// A one-line summary of the error.
+    pub error: String,
+}
+
+/// A batch advancing through the stages of its transaction, reported at
+/// each stage up to the awaited finality, when the batch is published.
+#[derive(Debug, Default, Serialize)]
+pub struct PublishProgress {
+    pub filename: PathBuf,
+    pub bytes: usize,
+    pub statement_count: usize,
+    pub dataset: String,
+    pub transaction: String,
+    /// Link to the transaction in a block explorer of the network.
+    pub explorer_url: Option<String>,
+    pub finality: WaitUntil,
+    /// The stage reached by the transaction.
+    pub stage: Stage,
+    /// The batch's share of the gas burnt by its transaction, once published.
+    pub gas_burnt: u64,
+    /// The batch's share of the tokens burnt by its transaction, in
+    /// yoctoNEAR, once published.
+    pub tokens_burnt: u128,
+    /// Whether the transaction reached the awaited finality without its
+    /// execution outcome being observed, as with `--wait-until included`.
+    pub unconfirmed: bool,
+}
+
+impl PublishProgress {
+    /// Returns whether the transaction reached the awaited finality.
+    pub fn published(&self) -> bool {
+        self.stage >= Stage::of(self.finality)
+    }
+}
+
+/// Where progress events are reported: to a channel, or to a callback called
+/// on the thread reporting each event.
+#[derive(Clone)]
+pub enum EventReporter {
+    Channel(Sender<Event>),
+    Callback(Arc<dyn Fn(Event) + Send + Sync>),
+}
+
+impl EventReporter {
+    /// Reports the event, dropping it if the channel's receiver is gone.
+    pub fn send(&self, event: Event) {
+        match self {
+            Self::Channel(tx) => {
+                tx.send(event).ok();
+            }
+            Self::Callback(callback) => callback(event),
+        }
+    }
+}
+
+impl fmt::Debug for EventReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Channel(tx) => f.debug_tuple("Channel").field(tx).finish(),
+            Self::Callback(_) => f.write_str("Callback(..)"),
+        }
+    }
+}
+
+impl From<Sender<Event>> for EventReporter {
+    fn from(tx: Sender<Event>) -> Self {
+        Self::Channel(tx)
+    }
+}
@@ -0,0 +1,124 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+#[cfg(unix)]
+use std::time::Duration;
+
+/// How often the key reader checks whether it should stop.
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reads the keys pressed in the terminal on a thread, calling back with each
+/// of them, until dropped. The terminal is put in non-canonical mode without
+/// echo while reading, and restored when the reader stops.
+pub(super) struct KeyReader {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl KeyReader {
+    /// Starts reading keys from the standard input.
+    pub fn spawn(mut on_key: impl FnMut(char) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = std::thread::spawn({
+            let stop = stop.clone();
+            move || read_keys(&stop, &mut on_key)
+        });
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for KeyReader {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        // Elsewhere the thread blocks until the next key, so is left to exit then:
+        if let (true, Some(thread)) = (cfg!(unix), self.thread.take()) {
+            thread.join().ok();
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_keys(stop: &AtomicBool, on_key: &mut dyn FnMut(char)) {
+    let Some(_mode) = TerminalMode::non_canonical(libc::STDIN_FILENO) else {
+        return;
+    };
+    while !stop.load(Ordering::Relaxed) {
+        let mut fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut fd, 1, POLL_INTERVAL.as_millis() as libc::c_int) } {
+            0 => continue,
+            ready if ready < 0 => match std::io::Error::last_os_error().kind() {
+                std::io::ErrorKind::Interrupted => continue,
+                _ => break,
+            },
+            _ => {}
+        }
+        let mut byte = 0u8;
+        let read = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        if read <= 0 {
+            break; // end of input
+        }
+        on_key(byte as char);
+    }
+}
+
+#[cfg(not(unix))]
+fn read_keys(stop: &AtomicBool, on_key: &mut dyn FnMut(char)) {
+    let term = console::Term::stderr();
+    while !stop.load(Ordering::Relaxed) {
+        match term.read_key() {
+            Ok(console::Key::Char(key)) => on_key(key),
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+}
+
+/// Restores the terminal's mode when dropped, including when unwinding
+/// from a panic.
+#[cfg(unix)]
+struct TerminalMode {
+    fd: libc::c_int,
+    saved: libc::termios,
+}
+
+#[cfg(unix)]
+impl TerminalMode {
+    /// Switches the terminal to non-canonical mode without echo, so keys are
+    /// read as they are pressed, keeping the signals of e.g. Ctrl-C.
+    fn non_canonical(fd: libc::c_int) -> Option<Self> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(fd, &mut saved) } != 0 {
+            return None;
+        }
+        let mut mode = saved;
+        mode.c_lflag &= !(libc::ICANON | libc::ECHO);
+        mode.c_cc[libc::VMIN] = 1;
+        mode.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &mode) } != 0 {
+            return None;
+        }
+        Some(Self { fd, saved })
+    }
+}
+
+#[cfg(unix)]
+impl Drop for TerminalMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(self.fd, libc::TCSANOW, &self.saved) };
+    }
+}
@@ -2,6 +2,10 @@
 
 use std::{collections::VecDeque, path::PathBuf};
 
+use indicatif::HumanBytes;
+
+use super::{PrepareProgress, ReaderProgress, Throughput};
+
 /// Prepare contains the UI state of preparation progress.
 #[derive(Debug, Default)]
 pub struct PrepareState {
@@ -11,6 +15,9 @@ pub struct PrepareState {
 
     pub queued_files: VecDeque<(PathBuf, usize)>,
     pub total_bytes: usize,
+    /// Whether an input is a stream, whose size is unknown until it ends, so
+    /// that only the bytes read are shown.
+    pub unsized_inputs: bool,
 
     pub read_bytes: usize,
     pub read_files: Vec<PathBuf>,
@@ -20,6 +27,13 @@ pub struct PrepareState {
     pub prepared_files: Vec<PathBuf>,
     pub prepared_statements: usize,
     pub skipped_statemets: usize,
+    pub parse_errors: usize,
+    pub published_statements: usize,
+
+    /// Bytes read per second.
+    pub read_rate: Throughput,
+    /// Statements prepared per second.
+    pub prepare_rate: Throughput,
 }
 
 impl PrepareState {
@@ -43,6 +57,10 @@ impl PrepareState {
 
         self.read_bytes += progress.bytes;
         self.read_statements += progress.statement_count;
+        self.skipped_statemets += progress.skipped_statements;
+        self.parse_errors += progress.parse_errors;
+        self.published_statements += progress.published_statements;
+        self.read_rate.record(progress.bytes);
 
         if progress.finished {
             self.queued_files
@@ -52,26 +70,53 @@ impl PrepareState {
         }
     }
 
+    /// Returns the rows of the final summary table.
+    pub fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (
+                "Files read",
+                format!(
+                    "{} / {}",
+                    self.read_files.len(),
+                    self.read_files.len() + self.queued_files.len()
+                ),
+            ),
+            ("Bytes read", HumanBytes(self.read_bytes as u64).to_string()),
+            ("Statements read", self.read_statements.to_string()),
+            ("Statements prepared", self.prepared_statements.to_string()),
+            ("Statements skipped", self.skipped_statemets.to_string()),
+            ("Parse errors", self.parse_errors.to_string()),
+            ("Already published", self.published_statements.to_string()),
+            ("Batches prepared", self.prepared_files.len().to_string()),
+            (
+                "Bytes prepared",
+                HumanBytes(self.prepared_bytes as u64).to_string(),
+            ),
+        ]
+    }
+
+    /// Summarizes the progress in a single line of plain text.
+    pub fn progress_line(&self) -> String {
+        let read = match self.unsized_inputs {
+            true => HumanBytes(self.read_bytes as u64).to_string(),
+            false => format!(
+                "{} / {}",
+                HumanBytes(self.read_bytes as u64),
+                HumanBytes(self.total_bytes as u64)
+            ),
+        };
+        format!(
+            "Read {read}, prepared {} statements in {} batches",
+            self.prepared_statements,
+            self.prepared_files.len(),
+        )
+    }
+
     pub fn update_prepare_state(&mut self, progress: PrepareProgress) {
         self.prepared_bytes += progress.bytes;
         self.prepared_statements += progress.statement_count;
         self.skipped_statemets += progress.skipped_statements;
+        self.prepare_rate.record(progress.statement_count);
         self.prepared_files.push(progress.filename);
     }
 }
-
-#[derive(Debug, Default)]
-pub struct ReaderProgress {
-    pub filename: PathBuf,
-    pub bytes: usize,
-    pub statement_count: usize,
-    pub finished: bool,
-}
-
-#[derive(Debug, Default)]
-pub struct PrepareProgress {
-    pub filename: PathBuf,
-    pub bytes: usize,
-    pub statement_count: usize,
-    pub skipped_statements: usize,
-}
@@ -1,6 +1,15 @@
 // This is free and unencumbered software released into the public domain.
 
-use std::{collections::VecDeque, path::PathBuf};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    path::PathBuf,
+};
+
+use eyre::{bail, eyre, Result};
+use indicatif::HumanBytes;
+
+use super::{PublishFailure, PublishProgress, PublishSkipped, Throughput};
+use crate::finality::Stage;
 
 /// Publish contains the UI state of publishing progress.
 #[derive(Debug, Default)]
@@ -13,20 +22,256 @@ pub struct PublishState {
     pub published_bytes: usize,
     pub published_files: Vec<PathBuf>,
     pub published_statements: usize,
+    /// Batches whose transaction was included without an observed outcome.
+    pub unconfirmed_files: Vec<PathBuf>,
+    pub transactions: Vec<String>,
+    /// The cost of each published batch, in publishing order.
+    pub batch_costs: Vec<BatchCost>,
+
+    /// Bytes published per second.
+    pub publish_rate: Throughput,
+    /// Statements published per second.
+    pub statement_rate: Throughput,
+    /// Batches published per second.
+    pub batch_rate: Throughput,
+
+    /// Failures to publish batches, in the order they occurred.
+    pub failures: Vec<PublishFailure>,
+    /// Batches skipped because the publish cache lists them as published.
+    pub skipped_files: Vec<PathBuf>,
+    /// The stage reached by the transaction of each batch not yet published.
+    pub in_flight: BTreeMap<PathBuf, Stage>,
+}
+
+/// The share of its transaction's cost attributed to a published batch.
+#[derive(Debug, Default)]
+pub struct BatchCost {
+    pub filename: PathBuf,
+    pub dataset: String,
+    pub statement_count: usize,
+    pub gas_burnt: u64,
+    /// Tokens burnt, in yoctoNEAR.
+    pub tokens_burnt: u128,
 }
 
 impl PublishState {
+    /// Returns the rows of the final summary table.
+    pub fn summary_rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = match self.prepare {
+            Some(ref prepare) => prepare.summary_rows(),
+            None => Vec::new(),
+        };
+        let gas_burnt: u64 = self.batch_costs.iter().map(|cost| cost.gas_burnt).sum();
+        let tokens_burnt: u128 = self.batch_costs.iter().map(|cost| cost.tokens_burnt).sum();
+        rows.extend([
+            (
+                "Statements published",
+                self.published_statements.to_string(),
+            ),
+            ("Batches published", self.published_files.len().to_string()),
+            (
+                "Batches unconfirmed",
+                self.unconfirmed_files.len().to_string(),
+            ),
+            ("Batches skipped", self.skipped_files.len().to_string()),
+            (
+                "Bytes published",
+                HumanBytes(self.published_bytes as u64).to_string(),
+            ),
+            (
+                "Batches failed",
+                self.failures
+                    .iter()
+                    .map(|failure| failure.filenames.len())
+                    .sum::<usize>()
+                    .to_string(),
+            ),
+            ("Transactions", self.transactions.len().to_string()),
+            ("Gas burnt", format!("{:.3} Tgas", gas_burnt as f64 / 1e12)),
+            (
+                "Tokens burnt",
+                format!("{} NEAR", format_near(tokens_burnt)),
+            ),
+        ]);
+        rows
+    }
+
+    /// Summarizes the progress in a single line of plain text.
+    pub fn progress_line(&self) -> String {
+        let published = format!(
+            "{} statements in {} batches",
+            self.published_statements,
+            self.published_files.len(),
+        );
+        let line = match self.prepare {
+            Some(ref prepare) => format!("{}, published {}", prepare.progress_line(), published),
+            None => format!("Published {published}"),
+        };
+        match self.stage_line() {
+            Some(stages) => format!("{line}, {stages}"),
+            None => line,
+        }
+    }
+
+    /// Summarizes the stages of the batches in flight, if any.
+    pub fn stage_line(&self) -> Option<String> {
+        if self.in_flight.is_empty() {
+            return None;
+        }
+        let stages: Vec<String> = [Stage::Submitted, Stage::Included, Stage::Executed]
+            .into_iter()
+            .filter_map(|stage| {
+                let count = self.in_flight.values().filter(|s| **s == stage).count();
+                (count > 0).then(|| format!("{count} {stage}"))
+            })
+            .collect();
+        Some(format!(
+            "{} in flight ({})",
+            match self.in_flight.len() {
+                1 => "1 batch".to_string(),
+                count => format!("{count} batches"),
+            },
+            stages.join(", ")
+        ))
+    }
+
+    /// Records the stage reached by the transaction of a batch in flight.
+    pub fn update_stage_state(&mut self, progress: &PublishProgress) {
+        self.in_flight
+            .insert(progress.filename.clone(), progress.stage);
+    }
+
+    pub fn update_failure_state(&mut self, failure: PublishFailure) {
+        for filename in &failure.filenames {
+            self.in_flight.remove(filename);
+        }
+        self.failures.push(failure);
+    }
+
+    pub fn update_skipped_state(&mut self, skipped: PublishSkipped) {
+        self.queued_files.retain(|(f, _)| *f != skipped.filename);
+        self.skipped_files.push(skipped.filename);
+    }
+
     pub fn update_publish_state(&mut self, progress: PublishProgress) {
         self.published_bytes += progress.bytes;
         self.published_statements += progress.statement_count;
+        self.publish_rate.record(progress.bytes);
+        self.statement_rate.record(progress.statement_count);
+        self.batch_rate.record(1);
         self.queued_files.retain(|(f, _)| *f != progress.filename);
-        self.published_files.push(progress.filename);
+        self.in_flight.remove(&progress.filename);
+        self.batch_costs.push(BatchCost {
+            filename: progress.filename.clone(),
+            dataset: progress.dataset,
+            statement_count: progress.statement_count,
+            gas_burnt: progress.gas_burnt,
+            tokens_burnt: progress.tokens_burnt,
+        });
+        if progress.unconfirmed {
+            self.unconfirmed_files.push(progress.filename);
+        } else {
+            self.published_files.push(progress.filename);
+        }
+        // Batches packed into one transaction report the same hash:
+        if self.transactions.last() != Some(&progress.transaction) {
+            self.transactions.push(progress.transaction);
+        }
     }
 }
 
-#[derive(Debug, Default)]
-pub struct PublishProgress {
-    pub filename: PathBuf,
-    pub bytes: usize,
-    pub statement_count: usize,
+/// yoctoNEAR per NEAR.
+const YOCTO_PER_NEAR: u128 = 10u128.pow(24);
+
+/// yoctoNEAR locked per byte of account storage.
+pub const STORAGE_PRICE_PER_BYTE: u128 = 10u128.pow(19);
+
+impl PublishState {
+    /// Renders a table of the gas and tokens burnt per published batch and in
+    /// total, followed by the NEAR locked for the storage used by the run.
+    pub fn cost_report(&self, storage_bytes: Option<u64>) -> String {
+        let name_width = self
+            .batch_costs
+            .iter()
+            .filter_map(|cost| cost.filename.file_name())
+            .map(|name| name.len())
+            .max()
+            .unwrap_or_default()
+            .max(5);
+        let dataset_width = self
+            .batch_costs
+            .iter()
+            .map(|cost| cost.dataset.len())
+            .max()
+            .unwrap_or_default()
+            .max(7);
+
+        let mut report = format!(
+            "{:name_width$}  {:dataset_width$}  {:>12}  {:>12}  {:>14}\n",
+            "Batch", "Dataset", "Statements", "Gas (Tgas)", "Burnt (NEAR)"
+        );
+        for cost in &self.batch_costs {
+            let name = cost
+                .filename
+                .file_name()
+                .map(|name| name.to_string_lossy())
+                .unwrap_or_default();
+            report += &format!(
+                "{:name_width$}  {:dataset_width$}  {:>12}  {:>12.3}  {:>14}\n",
+                name,
+                cost.dataset,
+                cost.statement_count,
+                cost.gas_burnt as f64 / 1e12,
+                format_near(cost.tokens_burnt),
+            );
+        }
+        let gas_burnt: u64 = self.batch_costs.iter().map(|cost| cost.gas_burnt).sum();
+        let tokens_burnt: u128 = self.batch_costs.iter().map(|cost| cost.tokens_burnt).sum();
+        report += &format!(
+            "{:name_width$}  {:dataset_width$}  {:>12}  {:>12.3}  {:>14}\n",
+            "Total",
+            "",
+            self.published_statements,
+            gas_burnt as f64 / 1e12,
+            format_near(tokens_burnt),
+        );
+        if let Some(bytes) = storage_bytes {
+            report += &format!(
+                "Storage: {} bytes, {} NEAR locked\n",
+                bytes,
+                format_near(bytes as u128 * STORAGE_PRICE_PER_BYTE)
+            );
+        }
+        report
+    }
+}
+
+/// Parses an amount of NEAR, e.g. `1` or `0.5`, into yoctoNEAR.
+pub fn parse_near(value: &str) -> Result<u128> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let digits = |part: &str| part.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty() && fraction.is_empty() || !digits(whole) || !digits(fraction) {
+        bail!("Expected an amount of NEAR such as 1 or 0.5");
+    }
+    if fraction.len() > 24 {
+        bail!("An amount of NEAR has at most 24 decimals");
+    }
+    let whole: u128 = match whole {
+        "" => 0,
+        _ => whole.parse()?,
+    };
+    let fraction: u128 = format!("{fraction:0<24}").parse()?;
+    whole
+        .checked_mul(10u128.pow(24))
+        .and_then(|whole| whole.checked_add(fraction))
+        .ok_or_else(|| eyre!("Too many NEAR"))
+}
+
+/// Formats an amount of yoctoNEAR in NEAR, with 6 decimals.
+pub fn format_near(yocto: u128) -> String {
+    format!(
+        "{}.{:06}",
+        yocto / YOCTO_PER_NEAR,
+        yocto % YOCTO_PER_NEAR / 10u128.pow(18)
+    )
 }
@@ -0,0 +1,507 @@
+// This is free and unencumbered software released into the public domain.
+
+use crossbeam::channel::{Receiver, RecvTimeoutError};
+use eyre::Result;
+use indicatif::{
+    HumanBytes, HumanDuration, MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle,
+};
+use std::{
+    io::IsTerminal,
+    sync::OnceLock,
+    time::{Duration, Instant},
+};
+
+use super::{keys::KeyReader, Event, PrepareState, PublishFailure, PublishState, Throughput};
+use crate::{context::Canceller, finality::Stage};
+
+pub enum UIEvent {
+    Resize,
+}
+
+/// How the progress of a run is shown.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ProgressMode {
+    /// Progress bars on a terminal, plain lines otherwise.
+    #[default]
+    Auto,
+    /// Progress bars, redrawn in place.
+    Bars,
+    /// Periodic plain status lines, without control sequences or redrawing,
+    /// e.g. for screen readers, dumb terminals and captured logs.
+    Plain,
+}
+
+impl ProgressMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Bars => "bars",
+            Self::Plain => "plain",
+        }
+    }
+}
+
+impl std::fmt::Display for ProgressMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ProgressMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "bars" => Ok(Self::Bars),
+            "plain" => Ok(Self::Plain),
+            _ => Err(format!(
+                "unknown progress mode `{s}`, expected one of: auto, bars, plain"
+            )),
+        }
+    }
+}
+
+static PROGRESS_MODE: OnceLock<ProgressMode> = OnceLock::new();
+
+/// Sets how the progress of the runs of the process is shown, once.
+pub fn set_progress_mode(mode: ProgressMode) {
+    let _ = PROGRESS_MODE.set(mode);
+}
+
+pub(crate) fn progress_mode() -> ProgressMode {
+    PROGRESS_MODE.get().copied().unwrap_or_default()
+}
+
+pub fn run_prepare(
+    verbosity: u8,
+    mut state: PrepareState,
+    progress_rx: Receiver<Event>,
+) -> Result<PrepareState> {
+    let prepare_style = ProgressStyle::with_template(
+        "{msg:10} [{bar:40}] {human_pos} / {human_len} statements {prefix}",
+    )
+    .unwrap()
+    .progress_chars("##-");
+
+    let multi = MultiProgress::new();
+    if verbosity < 1 {
+        // only show bars for `-v`
+        multi.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    let mut plain = PlainProgress::new(&multi);
+    let reader_bar = ProgressBar::new(state.total_bytes as u64)
+        .with_message("Read")
+        .with_style(parsing_style(state.unsized_inputs));
+    let prepare_bar = ProgressBar::new(0)
+        .with_message("Batch")
+        .with_style(prepare_style);
+
+    multi.add(reader_bar.clone());
+    multi.add(prepare_bar.clone());
+
+    state.read_rate.start();
+    state.prepare_rate.start();
+
+    loop {
+        let event = match progress_rx.recv_timeout(PLAIN_PROGRESS_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => {
+                plain.print_if_due(|| state.progress_line());
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        tracing::debug!(?event);
+
+        match event {
+            Event::Reader(progress) => {
+                reader_bar.inc(progress.bytes as u64);
+                prepare_bar.inc_length(progress.statement_count as u64);
+                if progress.finished && verbosity > 1 {
+                    multi.println(format!(
+                        " ✅ Finished reading file {}",
+                        progress.filename.display()
+                    ))?;
+                }
+                state.update_reader_state(progress);
+                reader_bar.set_prefix(read_rate_message(&state));
+            }
+            Event::Prepare(progress) => {
+                prepare_bar.inc(progress.statement_count as u64);
+                if verbosity > 1 {
+                    if let Some(filename) = progress
+                        .filename
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                    {
+                        multi.println(format!(" ✅ Created batch {}", filename))?;
+                    }
+                }
+                state.update_prepare_state(progress);
+                prepare_bar.set_prefix(statement_rate_message(&state.prepare_rate));
+            }
+            Event::Publish(_)
+            | Event::PublishFailure(_)
+            | Event::PublishSkipped(_)
+            | Event::PublishPaused(_) => {
+                unreachable!()
+            }
+        }
+        plain.print_if_due(|| state.progress_line());
+    }
+    plain.print(|| state.progress_line());
+
+    reader_bar.finish();
+    prepare_bar.finish();
+
+    Ok(state)
+}
+
+/// Runs the publish UI until all progress events are received.
+///
+/// With the controls of the run, pressing `p` in an interactive terminal
+/// pauses publishing once the current batch is done, and resumes it.
+pub fn run_publish(
+    verbosity: u8,
+    mut state: PublishState,
+    progress_rx: Receiver<Event>,
+    controls: Option<Canceller>,
+) -> Result<PublishState> {
+    let prepare_style = ProgressStyle::with_template(
+        "{msg:10} [{bar:40}] {human_pos} / {human_len} statements {prefix}",
+    )
+    .unwrap()
+    .progress_chars("##-");
+
+    let upload_style = ProgressStyle::with_template(
+        "{msg:10} [{bar:40}] {human_pos} / {human_len} batches {prefix}",
+    )
+    .unwrap()
+    .progress_chars("##-");
+
+    let multi = MultiProgress::new();
+    if verbosity < 1 {
+        // only show bars for `-v`
+        multi.set_draw_target(ProgressDrawTarget::hidden());
+    }
+    let mut plain = PlainProgress::new(&multi);
+
+    let reader_bar = multi.add(
+        ProgressBar::new(
+            state
+                .prepare
+                .as_ref()
+                .map(|state| state.total_bytes)
+                .unwrap_or_default() as u64,
+        )
+        .with_message("Read")
+        .with_style(parsing_style(
+            state
+                .prepare
+                .as_ref()
+                .is_some_and(|state| state.unsized_inputs),
+        )),
+    );
+    let prepare_bar = multi.add(
+        ProgressBar::new(0)
+            .with_message("Batch")
+            .with_style(prepare_style),
+    );
+    let upload_bar = multi.add(
+        ProgressBar::new(0)
+            .with_message("Upload")
+            .with_style(upload_style),
+    );
+    // Keys are only read from a terminal that also shows the bars:
+    let interactive = std::io::stdin().is_terminal() && std::io::stderr().is_terminal();
+    let _keys = controls
+        .filter(|_| verbosity > 0 && interactive)
+        .map(|controls| {
+            let upload_bar = upload_bar.clone();
+            upload_bar.println(" ⏯  Press p to pause or resume publishing");
+            KeyReader::spawn(move |key| {
+                if key != 'p' {
+                    return;
+                }
+                if controls.toggle_pause() {
+                    upload_bar.set_message("Paused");
+                } else {
+                    upload_bar.set_message("Upload");
+                }
+            })
+        });
+
+    // Shows the stages of the transactions in flight below the bars:
+    let stage_panel =
+        multi.add(ProgressBar::new(0).with_style(ProgressStyle::with_template("{msg}").unwrap()));
+
+    // Lists the failed batches below the bars as the failures occur:
+    let error_panel =
+        multi.add(ProgressBar::new(0).with_style(ProgressStyle::with_template("{msg}").unwrap()));
+
+    if let Some(ref mut state) = state.prepare {
+        state.read_rate.start();
+        state.prepare_rate.start();
+    }
+    state.publish_rate.start();
+    state.statement_rate.start();
+    state.batch_rate.start();
+
+    loop {
+        let event = match progress_rx.recv_timeout(PLAIN_PROGRESS_INTERVAL) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => {
+                plain.print_if_due(|| state.progress_line());
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        tracing::debug!(?event);
+
+        match event {
+            Event::Reader(progress) => {
+                reader_bar.inc(progress.bytes as u64);
+                prepare_bar.inc_length(progress.statement_count as u64);
+                if progress.finished && verbosity > 1 {
+                    multi.println(format!(
+                        " ✅ Finished reading file {}",
+                        progress.filename.display()
+                    ))?;
+                }
+                if let Some(ref mut state) = state.prepare {
+                    state.update_reader_state(progress);
+                    reader_bar.set_prefix(read_rate_message(state));
+                }
+            }
+            Event::Prepare(progress) => {
+                prepare_bar.inc(progress.statement_count as u64);
+                upload_bar.inc_length(1);
+                if verbosity > 1 {
+                    if let Some(filename) = progress
+                        .filename
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                    {
+                        multi.println(format!(" ✅ Created batch {}", filename))?;
+                    }
+                }
+                if let Some(ref mut state) = state.prepare {
+                    state.update_prepare_state(progress);
+                    prepare_bar.set_prefix(statement_rate_message(&state.prepare_rate));
+                }
+            }
+            Event::Publish(progress) if !progress.published() => {
+                if verbosity > 1 {
+                    if let Some(filename) = progress
+                        .filename
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                    {
+                        let (icon, verb) = match progress.stage {
+                            Stage::Submitted => ("📨", "Submitted"),
+                            Stage::Included => ("📦", "Included"),
+                            Stage::Executed | Stage::Final => ("⚙️", "Executed"),
+                        };
+                        multi.println(format!(" {icon} {verb} batch {filename}"))?;
+                    }
+                }
+                state.update_stage_state(&progress);
+                stage_panel.set_message(stage_panel_message(&state));
+            }
+            Event::Publish(progress) => {
+                upload_bar.inc(1);
+                if verbosity > 1 {
+                    if let Some(filename) = progress
+                        .filename
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                    {
+                        if progress.unconfirmed {
+                            multi.println(format!(" 📦 Included batch {filename}, unconfirmed"))?;
+                        } else {
+                            multi.println(format!(
+                                " ✅ Uploaded batch {} ({})",
+                                filename, progress.finality
+                            ))?;
+                        }
+                    }
+                }
+                // Batches packed into one transaction share its link:
+                if verbosity > 0 && state.transactions.last() != Some(&progress.transaction) {
+                    if let Some(ref url) = progress.explorer_url {
+                        multi.println(format!(" 🔗 {url}"))?;
+                    }
+                }
+                state.update_publish_state(progress);
+                stage_panel.set_message(stage_panel_message(&state));
+                let remaining = upload_bar
+                    .length()
+                    .unwrap_or_default()
+                    .saturating_sub(upload_bar.position());
+                upload_bar.set_prefix(format!(
+                    "({}/s, {:.0} statements/s, {})",
+                    HumanBytes(state.publish_rate.rate() as u64),
+                    state.statement_rate.rate(),
+                    eta_message(state.batch_rate.eta(remaining as usize)),
+                ));
+            }
+            Event::PublishFailure(failure) => {
+                state.update_failure_state(failure);
+                stage_panel.set_message(stage_panel_message(&state));
+                error_panel.set_message(error_panel_message(&state.failures));
+            }
+            Event::PublishSkipped(skipped) => {
+                upload_bar.inc(1);
+                if verbosity > 1 {
+                    if let Some(filename) = skipped
+                        .filename
+                        .file_name()
+                        .and_then(std::ffi::OsStr::to_str)
+                    {
+                        multi.println(format!(
+                            " ⏭️ Skipped batch {}, already published in {}",
+                            filename, skipped.transaction
+                        ))?;
+                    }
+                }
+                state.update_skipped_state(skipped);
+            }
+            Event::PublishPaused(pause) => {
+                let message = match pause.resumed {
+                    true => format!(" ▶️ Resumed publishing, {} was topped up", pause.account),
+                    false => format!(
+                        " ⏸️ Paused publishing: {} has {} NEAR available, {} NEAR are needed for the next transaction. Top up the account to resume.",
+                        pause.account,
+                        super::format_near(pause.balance),
+                        super::format_near(pause.needed),
+                    ),
+                };
+                // The pause must be seen even without the progress bars:
+                match multi.is_hidden() {
+                    true => eprintln!("{message}"),
+                    false => multi.println(message)?,
+                }
+            }
+        }
+        plain.print_if_due(|| state.progress_line());
+    }
+    plain.print(|| state.progress_line());
+
+    reader_bar.finish();
+    prepare_bar.finish();
+    upload_bar.finish();
+    stage_panel.finish_and_clear();
+    error_panel.finish();
+
+    Ok(state)
+}
+
+/// Renders the rows of a final summary as an aligned two-column table.
+pub fn summary_table(rows: &[(&str, String)]) -> String {
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or_default();
+    rows.iter()
+        .map(|(name, value)| format!("{name:width$}  {value}\n"))
+        .collect()
+}
+
+/// How often plain progress lines are printed.
+const PLAIN_PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Prints periodic plain progress lines in place of the progress bars when
+/// stderr is not a terminal, e.g. in CI logs, or in the plain progress mode.
+struct PlainProgress {
+    enabled: bool,
+    last: Instant,
+}
+
+impl PlainProgress {
+    fn new(multi: &MultiProgress) -> Self {
+        let enabled = match progress_mode() {
+            ProgressMode::Auto => !std::io::stderr().is_terminal(),
+            ProgressMode::Bars => false,
+            ProgressMode::Plain => true,
+        };
+        if enabled {
+            multi.set_draw_target(ProgressDrawTarget::hidden());
+        }
+        Self {
+            enabled,
+            last: Instant::now(),
+        }
+    }
+
+    fn print_if_due(&mut self, line: impl FnOnce() -> String) {
+        if self.last.elapsed() >= PLAIN_PROGRESS_INTERVAL {
+            self.print(line);
+        }
+    }
+
+    fn print(&mut self, line: impl FnOnce() -> String) {
+        if self.enabled {
+            eprintln!("{}", line());
+            self.last = Instant::now();
+        }
+    }
+}
+
+fn stage_panel_message(state: &PublishState) -> String {
+    match state.stage_line() {
+        Some(line) => format!(" ⏳ {line}"),
+        None => String::new(),
+    }
+}
+
+fn error_panel_message(failures: &[PublishFailure]) -> String {
+    let mut message = format!("Failed batches ({}):", failures.len());
+    for failure in failures {
+        let filenames: Vec<_> = failure
+            .filenames
+            .iter()
+            .filter_map(|filename| filename.file_name())
+            .map(|filename| filename.to_string_lossy())
+            .collect();
+        message += &format!("\n ❌ {}: {}", filenames.join(", "), failure.error);
+    }
+    message
+}
+
+/// Returns the style of the bar of bytes read, without a total nor a bar when
+/// the size of the inputs is unknown.
+fn parsing_style(unsized_inputs: bool) -> ProgressStyle {
+    match unsized_inputs {
+        true => ProgressStyle::with_template("{msg:10} {binary_bytes} {prefix}").unwrap(),
+        false => ProgressStyle::with_template(
+            "{msg:10} [{bar:40}] {binary_bytes} / {binary_total_bytes} {prefix}",
+        )
+        .unwrap()
+        .progress_chars("##-"),
+    }
+}
+
+fn read_rate_message(state: &PrepareState) -> String {
+    if state.unsized_inputs {
+        return format!("({}/s)", HumanBytes(state.read_rate.rate() as u64));
+    }
+    let remaining = state.total_bytes.saturating_sub(state.read_bytes);
+    format!(
+        "({}/s, {})",
+        HumanBytes(state.read_rate.rate() as u64),
+        eta_message(state.read_rate.eta(remaining)),
+    )
+}
+
+fn statement_rate_message(rate: &Throughput) -> String {
+    format!("({:.0} statements/s)", rate.rate())
+}
+
+fn eta_message(eta: Option<Duration>) -> String {
+    match eta {
+        Some(eta) => format!("ETA {}", HumanDuration(eta)),
+        None => "ETA unknown".into(),
+    }
+}
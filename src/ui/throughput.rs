@@ -0,0 +1,43 @@
+// This is free and unencumbered software released into the public domain.
+
+use std::time::{Duration, Instant};
+
+/// Time over which older samples lose most of their weight in the average.
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// A rate of progress in units per second, averaged with an exponential
+/// moving average so that it follows recent changes in speed.
+#[derive(Clone, Debug, Default)]
+pub struct Throughput {
+    last: Option<Instant>,
+    rate: f64,
+}
+
+impl Throughput {
+    /// Starts measuring from now.
+    pub fn start(&mut self) {
+        self.last = Some(Instant::now());
+    }
+
+    /// Records progress by the given amount of units since the last record.
+    pub fn record(&mut self, amount: usize) {
+        let now = Instant::now();
+        let Some(last) = self.last.replace(now) else {
+            return;
+        };
+        // Simultaneous records still count, as a burst over a tiny interval:
+        let elapsed = now.duration_since(last).as_secs_f64().max(1e-6);
+        let weight = 1.0 - (-elapsed / WINDOW.as_secs_f64()).exp();
+        self.rate += weight * (amount as f64 / elapsed - self.rate);
+    }
+
+    /// Units per second.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Estimated time to process the remaining units at the current rate.
+    pub fn eta(&self, remaining: usize) -> Option<Duration> {
+        (self.rate > 0.0).then(|| Duration::from_secs_f64(remaining as f64 / self.rate))
+    }
+}
@@ -0,0 +1,230 @@
+// This is free and unencumbered software released into the public domain.
+
+use eyre::{bail, eyre, Context as _, Result};
+use oxrdfio::{RdfFormat, RdfParser};
+
+use crate::{
+    patch::{read_graph, skip_space, Changes},
+    snapshot::Delta,
+};
+
+/// Reads the net changes of a [SPARQL UPDATE](https://www.w3.org/TR/sparql11-update/)
+/// request, as the statements it inserts and deletes.
+///
+/// The supported operations are `INSERT DATA`, `DELETE DATA`, and
+/// `DELETE { ... } INSERT { ... } WHERE { }` templates without variables and
+/// with an empty `WHERE` clause, separated by `;` and preceded by `PREFIX` and
+/// `BASE` declarations. The operations are applied in order.
+pub fn read_update(text: &str) -> Result<Delta> {
+    let mut changes = Changes::default();
+    let mut prologue = String::new();
+    let mut rest = text;
+    loop {
+        rest = skip_space(rest);
+        if rest.is_empty() {
+            return Ok(changes.into_delta());
+        }
+        if let Some(after) = rest.strip_prefix(';') {
+            rest = after;
+            continue;
+        }
+        let (keyword, after) = read_keyword(rest);
+        match keyword.to_ascii_uppercase().as_str() {
+            "PREFIX" | "BASE" => {
+                let end = after
+                    .find('>')
+                    .ok_or_else(|| eyre!("Invalid `{keyword}` declaration"))?;
+                prologue.push_str(&format!("{keyword}{}\n", &after[..=end]));
+                rest = &after[end + 1..];
+            }
+            "INSERT" | "DELETE" => {
+                let insert = keyword.eq_ignore_ascii_case("INSERT");
+                let (next, data) = read_keyword(skip_space(after));
+                if next.eq_ignore_ascii_case("DATA") {
+                    let (quads, after) = read_graph(skip_space(data))
+                        .with_context(|| format!("Invalid `{keyword} DATA` operation"))?;
+                    apply(&mut changes, &prologue, quads, insert)
+                        .with_context(|| format!("Invalid `{keyword} DATA` operation"))?;
+                    rest = after;
+                } else if next.is_empty() {
+                    rest = read_template(&mut changes, &prologue, keyword, after)?;
+                } else {
+                    bail!("The SPARQL UPDATE operation `{keyword} {next}` isn't supported");
+                }
+            }
+            "" => bail!("Unexpected `{}`", rest.chars().next().unwrap_or_default()),
+            _ => bail!("The SPARQL UPDATE operation `{keyword}` isn't supported"),
+        }
+    }
+}
+
+/// Reads a `DELETE { ... } INSERT { ... } WHERE { }` template whose first
+/// keyword was read, returning the text following it.
+fn read_template<'a>(
+    changes: &mut Changes,
+    prologue: &str,
+    keyword: &str,
+    text: &'a str,
+) -> Result<&'a str> {
+    let invalid = || format!("Invalid `{keyword}` operation");
+    let (first, mut rest) = read_graph(skip_space(text)).with_context(invalid)?;
+    let mut deleted = None;
+    let mut inserted = None;
+    match keyword.eq_ignore_ascii_case("INSERT") {
+        true => inserted = Some(first),
+        false => deleted = Some(first),
+    }
+
+    let (next, after) = read_keyword(skip_space(rest));
+    if deleted.is_some() && next.eq_ignore_ascii_case("INSERT") {
+        let (graph, after) = read_graph(skip_space(after)).with_context(invalid)?;
+        inserted = Some(graph);
+        rest = after;
+    }
+
+    let (next, after) = read_keyword(skip_space(rest));
+    if !next.eq_ignore_ascii_case("WHERE") {
+        bail!("Expected a `WHERE` clause in the `{keyword}` operation");
+    }
+    let (pattern, after) = read_graph(skip_space(after)).with_context(invalid)?;
+    if !skip_space(pattern).is_empty() {
+        bail!("Only `{keyword}` operations with an empty `WHERE` clause are supported");
+    }
+
+    if let Some(quads) = deleted {
+        apply(changes, prologue, quads, false).with_context(invalid)?;
+    }
+    if let Some(quads) = inserted {
+        apply(changes, prologue, quads, true).with_context(invalid)?;
+    }
+    Ok(after)
+}
+
+/// Inserts or deletes the statements of the quad data.
+fn apply(changes: &mut Changes, prologue: &str, quads: &str, insert: bool) -> Result<()> {
+    if quads.contains(['?', '$']) && quads.split_whitespace().any(is_variable) {
+        bail!("Variables aren't supported");
+    }
+    let quads = quads.trim();
+    // The final triple doesn't need a `.` in SPARQL, unlike in TriG:
+    let end = match quads.ends_with(['.', '}']) || quads.is_empty() {
+        true => "",
+        false => " .",
+    };
+    let document = format!("{prologue}{quads}{end}\n");
+    for quad in RdfParser::from_format(RdfFormat::TriG).for_slice(document.as_bytes()) {
+        let statement = quad?.to_string();
+        match insert {
+            true => changes.add(statement),
+            false => changes.delete(statement),
+        }
+    }
+    Ok(())
+}
+
+fn is_variable(token: &str) -> bool {
+    token
+        .strip_prefix(['?', '$'])
+        .is_some_and(|name| name.starts_with(|c: char| c.is_alphanumeric() || c == '_'))
+}
+
+/// Splits the leading keyword, made of ASCII letters, from the text.
+fn read_keyword(text: &str) -> (&str, &str) {
+    let length = text
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or(text.len());
+    text.split_at(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str) -> String {
+        read_update(text).unwrap_err().root_cause().to_string()
+    }
+
+    #[test]
+    fn reads_data_operations() {
+        let delta = read_update(
+            "PREFIX ex: <http://example.org/>
+             # A comment
+             INSERT DATA { ex:s ex:p \"v\" . GRAPH ex:g { ex:s ex:p ex:o } } ;
+             delete data { ex:s ex:p \"old\" } ;
+             INSERT DATA { ex:s ex:p ex:transient } ;
+             DELETE DATA { ex:s ex:p ex:transient }",
+        )
+        .unwrap();
+        assert_eq!(
+            delta.added,
+            [
+                "<http://example.org/s> <http://example.org/p> \"v\"",
+                "<http://example.org/s> <http://example.org/p> <http://example.org/o> <http://example.org/g>",
+            ]
+        );
+        assert_eq!(
+            delta.removed,
+            [
+                "<http://example.org/s> <http://example.org/p> \"old\"",
+                "<http://example.org/s> <http://example.org/p> <http://example.org/transient>",
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_templates_without_variables() {
+        let delta = read_update(
+            "BASE <http://example.org/>
+             DELETE { <s> <p> \"old\" } INSERT { <s> <p> \"new\" } WHERE { } ;
+             insert { <s> <q> <o> } where {}",
+        )
+        .unwrap();
+        assert_eq!(
+            delta.added,
+            [
+                "<http://example.org/s> <http://example.org/p> \"new\"",
+                "<http://example.org/s> <http://example.org/q> <http://example.org/o>",
+            ]
+        );
+        assert_eq!(
+            delta.removed,
+            ["<http://example.org/s> <http://example.org/p> \"old\""]
+        );
+    }
+
+    #[test]
+    fn rejects_unsupported_operations() {
+        assert_eq!(
+            error("LOAD <http://example.org/data>"),
+            "The SPARQL UPDATE operation `LOAD` isn't supported"
+        );
+        assert_eq!(
+            error("DELETE WHERE { ?s ?p ?o }"),
+            "The SPARQL UPDATE operation `DELETE WHERE` isn't supported"
+        );
+        assert_eq!(
+            error("INSERT { <urn:s> <urn:p> <urn:o> } WHERE { ?s ?p ?o }"),
+            "Only `INSERT` operations with an empty `WHERE` clause are supported"
+        );
+        assert_eq!(
+            error("INSERT { <urn:s> <urn:p> <urn:o> }"),
+            "Expected a `WHERE` clause in the `INSERT` operation"
+        );
+        assert_eq!(
+            error("INSERT DATA { ?s <urn:p> <urn:o> }"),
+            "Variables aren't supported"
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        assert_eq!(
+            error("INSERT DATA { <urn:s> <urn:p> <urn:o>"),
+            "Expected `}`"
+        );
+        assert_eq!(error("INSERT DATA <urn:s>"), "Expected `{`");
+        assert_eq!(error("PREFIX ex:"), "Invalid `PREFIX` declaration");
+        assert_eq!(error("} ;"), "Unexpected `}`");
+        assert!(read_update("INSERT DATA { <urn:s> <urn:p> }").is_err());
+    }
+}